@@ -4,6 +4,11 @@ cargo test
 
 cargo build
 
+# a test can pass extra flags to luna with a `// flags: ...` first line
+flags() {
+    sed -n '1s|^// flags: ||p' "$1"
+}
+
 # Test success cases - these should pass
 echo "=== Testing Success Cases ==="
 success_count=0
@@ -12,7 +17,7 @@ for filename in tests/success/*; do
     if [ -f "$filename" ]; then
         total_success=$((total_success + 1))
         echo "Running success test: $filename";
-        if ./target/debug/luna-rs "$filename" >/dev/null 2>&1; then
+        if ./target/debug/luna-rs $(flags "$filename") "$filename" >/dev/null 2>&1; then
             echo "✓ PASSED: $filename"
             success_count=$((success_count + 1))
         else
@@ -33,7 +38,7 @@ for filename in tests/failure/*; do
     if [ -f "$filename" ]; then
         total_failure=$((total_failure + 1))
         echo "Running failure test: $filename";
-        if ! ./target/debug/luna-rs "$filename" >/dev/null 2>&1; then
+        if ! ./target/debug/luna-rs $(flags "$filename") "$filename" >/dev/null 2>&1; then
             echo "✓ PASSED: $filename (correctly failed)"
             failure_count=$((failure_count + 1))
         else
@@ -1,5 +1,6 @@
 use std::net::Ipv4Addr;
 
+use crate::runtime::ArrayHeader;
 use crate::types;
 use libc::{self};
 
@@ -255,52 +256,47 @@ pub fn builtin_stderr(_: *mut crate::runtime::RuntimeContext) -> i64 {
 }
 
 pub fn builtin_tcp_recv(ctx: *mut crate::runtime::RuntimeContext, fd: i64) -> *const i64 {
-    let mut buffer = [0u8; 1024];
-    let mut copy_buffer = Vec::new();
+    let array = unsafe {
+        (*ctx).gc.create_array(0, 1, false)
+    };
+    let header = unsafe { &mut *(array as *mut ArrayHeader) };
 
     loop {
-        let bytes_read = unsafe { libc::recv(fd as libc::c_int, buffer.as_mut_ptr() as *mut libc::c_void, buffer.len(), 0) };
+        header.reserve(1024, 1);
+        let buffer = unsafe { header.data.add(header.length as usize) };
+        let bytes_read = unsafe { libc::recv(fd as libc::c_int, buffer as *mut libc::c_void, 1024, 0) };
         if bytes_read == -1 {
-            return unsafe {
-                (*ctx).gc.create_array(0, 1, false)
-            };
-        }
-        for i in 0..bytes_read {
-            copy_buffer.push(buffer[i as usize]);
+            break;
         }
-        if (bytes_read as usize) < buffer.len() {
+        header.length += bytes_read as i64;
+        if (bytes_read as usize) < 1024 {
             break;
         }
     }
 
-    let array = unsafe {
-        (*ctx).gc.create_array(buffer.len(), 1, false)
-    };
-    unsafe {
-        std::ptr::copy_nonoverlapping(copy_buffer.as_ptr(), (array as i64 + 8) as *mut u8, copy_buffer.len());
-    }
     array
 }
 
 pub fn builtin_tcp_send(_: *mut crate::runtime::RuntimeContext, fd: i64, array: *mut i64) {
-    // Get the size of the array
-    let mut array_size = unsafe { *array } as usize;
+    let header = unsafe { &*(array as *const ArrayHeader) };
+    let mut array_size = header.length as usize;
     let mut offset = 0;
     loop {
-        let bytes_send = unsafe { libc::send(fd as libc::c_int, (array as i64 + 8 + offset) as *const libc::c_void, array_size, 0) };
+        let bytes_send = unsafe { libc::send(fd as libc::c_int, header.data.add(offset) as *const libc::c_void, array_size, 0) };
         if bytes_send == -1 {
             break;
         }
         if bytes_send as usize >= array_size {
             break;
         }
-        offset += bytes_send as i64;
+        offset += bytes_send as usize;
         array_size -= bytes_send as usize;
     }
 }
 
 pub fn builtin_byte_array_to_string(_: *mut crate::runtime::RuntimeContext, array: *const i64) -> *const u8 {
-    let slice = unsafe { std::slice::from_raw_parts((array as i64 + 8) as *const u8, *array as usize) };
+    let header = unsafe { &*(array as *const ArrayHeader) };
+    let slice = unsafe { std::slice::from_raw_parts(header.data, header.length as usize) };
     let string = String::from_utf8_lossy(slice).to_string();
     let internal = crate::runtime::string::convert_to_interal_string(&string);
     Box::into_raw(internal) as *const u8
@@ -313,7 +309,7 @@ pub fn builtin_string_to_byte_array(ctx: *mut crate::runtime::RuntimeContext, st
         (*ctx).gc.create_array(bytes.len(), 1, false)
     };
     unsafe {
-        std::ptr::copy_nonoverlapping(bytes.as_ptr(), (array as i64 + 8) as *mut u8, bytes.len());
+        std::ptr::copy_nonoverlapping(bytes.as_ptr(), (*(array as *const ArrayHeader)).data, bytes.len());
     }
     array
 }
@@ -13,7 +13,20 @@ pub enum Type {
     Bool,
     Identifier(String),
     Array(Box<Type>),
-    //UnknownReference, // An internal detail before generics is correctly implemented
+    // `map[string]int`
+    Map(Box<Type>, Box<Type>),
+    // `(int, string)`
+    Tuple(Vec<Type>),
+    Function {
+        params: Vec<Type>,
+        return_type: Option<Box<Type>>,
+    },
+    // A generic type with its arguments, `Pair<int, string>`
+    Generic(String, Vec<Type>),
+    // A type from an aliased import, `net.Listener`
+    Qualified(String, Box<Type>),
+    // A type parameter replaced by its argument when a generic is instantiated
+    Resolved(types::Type),
 }
 
 #[derive(Debug, Clone)]
@@ -24,6 +37,12 @@ pub enum BinaryExprKind {
     Subtract,
     Multiply,
     Divide,
+    Modulo,
+    BitwiseAnd,
+    BitwiseOr,
+    BitwiseXor,
+    ShiftLeft,
+    ShiftRight,
     Equal,
     NotEqual,
     LessThan,
@@ -40,7 +59,17 @@ pub struct BinaryExpr {
 }
 
 #[derive(Debug, Clone)]
-pub struct UnaryExpr {}
+pub enum UnaryExprKind {
+    Negate,
+    LogicalNot,
+    BitwiseNot,
+}
+
+#[derive(Debug, Clone)]
+pub struct UnaryExpr {
+    pub value: Expr,
+    pub kind: UnaryExprKind,
+}
 
 #[derive(Debug, Clone)]
 pub struct Assign {
@@ -48,6 +77,16 @@ pub struct Assign {
     pub value: Expr,
 }
 
+// `a += b`, also used for `a++` and `a--` where the value is 1
+#[derive(Debug, Clone)]
+pub struct CompoundAssign {
+    pub destination: Expr,
+    pub value: Expr,
+    pub kind: BinaryExprKind,
+    // `a++` and `a--` evaluate to the value from before the assignment
+    pub postfix: bool,
+}
+
 #[derive(Debug, Clone)]
 pub struct Call {
     pub function: Expr,
@@ -55,6 +94,50 @@ pub struct Call {
     // filled in by the checker for direct calls, used for codegen
     pub symbol_name: Option<String>,
     pub enum_idx: Option<usize>,
+    // `Foo(...)` makes this object and then calls `init` on it
+    pub init: Option<Box<Expr>>,
+    // `a.push(...)` and friends on arrays are lowered straight to array instructions
+    pub array_method: Option<ArrayMethod>,
+    // likewise `m.contains(...)` and `m.remove(...)` on maps
+    pub map_method: Option<MapMethod>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArrayMethod {
+    Push,
+    Pop,
+    Insert,
+    Remove,
+    Clear,
+}
+
+impl ArrayMethod {
+    pub fn from_name(name: &str) -> Option<ArrayMethod> {
+        match name {
+            "push" => Some(ArrayMethod::Push),
+            "pop" => Some(ArrayMethod::Pop),
+            "insert" => Some(ArrayMethod::Insert),
+            "remove" => Some(ArrayMethod::Remove),
+            "clear" => Some(ArrayMethod::Clear),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MapMethod {
+    Contains,
+    Remove,
+}
+
+impl MapMethod {
+    pub fn from_name(name: &str) -> Option<MapMethod> {
+        match name {
+            "contains" => Some(MapMethod::Contains),
+            "remove" => Some(MapMethod::Remove),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -82,6 +165,43 @@ pub struct Identifier {
     pub id: String,
 }
 
+// A named function used as a value, created by the checker when an
+// identifier or selector refers to a function outside of a call
+#[derive(Debug, Clone)]
+pub struct FunctionRef {
+    pub symbol_name: String,
+}
+
+// A method bound to its receiver used as a value, created by the checker when
+// a selector refers to a method outside of a call
+#[derive(Debug, Clone)]
+pub struct MethodRef {
+    pub value: Expr,
+    pub method: String,
+    pub symbol_name: String,
+}
+
+// A module-level `let` or `const`, created by the checker when an identifier
+// refers to one
+#[derive(Debug, Clone)]
+pub struct GlobalRef {
+    pub symbol_name: String,
+}
+
+// An anonymous function literal, `func(x: int): int { ... }`
+#[derive(Debug, Clone)]
+pub struct Closure {
+    pub loc: SourceLoc,
+    pub params: Vec<Param>,
+    pub return_type: Option<Box<Type>>,
+    pub body: Box<BlockStmt>,
+    // filled in by the checker, used for codegen
+    pub typ_: types::FunctionType,
+    // the variables of the enclosing function used in the body, in the order
+    // they are stored in the closure object, `self` is captured as ""
+    pub captures: Vec<(String, types::Type)>,
+}
+
 #[derive(Debug, Clone)]
 pub struct Subscript {
     pub value: Expr,
@@ -104,6 +224,18 @@ pub struct ArrayLiteral {
     pub literals: Vec<Expr>,
 }
 
+// `(a, b)`
+#[derive(Debug, Clone)]
+pub struct TupleLiteral {
+    pub values: Vec<Expr>,
+}
+
+// `["a": 1, "b": 2]`, `[:]` is an empty map
+#[derive(Debug, Clone)]
+pub struct MapLiteral {
+    pub entries: Vec<(Expr, Expr)>,
+}
+
 #[derive(Debug, Clone)]
 pub struct ObjectLiteralField {
     pub loc: SourceLoc,
@@ -113,6 +245,8 @@ pub struct ObjectLiteralField {
 
 #[derive(Debug, Clone)]
 pub struct ObjectLiteral {
+    // the import alias of `alias.Type { ... }`
+    pub alias: Option<String>,
     pub id: Option<Identifier>,
     pub fields: Vec<ObjectLiteralField>,
 }
@@ -121,6 +255,20 @@ pub struct ObjectLiteral {
 pub struct Cast {
     pub value: Expr,
     pub target_type: types::Type,
+    // the type of an explicit `as` cast, resolved into target_type by the checker
+    pub annotation: Option<Box<Type>>,
+    // `as wrapping byte` wraps instead of panicking when the value does not fit
+    pub wrapping: bool,
+    // `as?` downcasts an interface value into an Option of the target type
+    pub optional: bool,
+}
+
+// `value?`, returns the failure of a Result or Option from the function
+#[derive(Debug, Clone)]
+pub struct Try {
+    pub value: Expr,
+    // filled in by the checker, the variant holding the value, used for codegen
+    pub success_idx: usize,
 }
 
 #[derive(Debug, Clone)]
@@ -134,18 +282,30 @@ pub enum ExprKind {
     BinaryExpr(Box<BinaryExpr>),
     UnaryExpr(Box<UnaryExpr>),
     Assign(Box<Assign>),
+    CompoundAssign(Box<CompoundAssign>),
     Call(Box<Call>),
     Integer(Box<Integer>),
     Number(Box<Number>),
     StringLiteral(Box<StringLiteral>),
     Boolean(Box<Bool>),
     Identifier(Box<Identifier>),
+    FunctionRef(Box<FunctionRef>),
+    MethodRef(Box<MethodRef>),
+    GlobalRef(Box<GlobalRef>),
+    Closure(Box<Closure>),
     Subscript(Box<Subscript>),
     Selector(Box<Selector>),
     ArrayLiteral(Box<ArrayLiteral>),
+    MapLiteral(Box<MapLiteral>),
+    TupleLiteral(Box<TupleLiteral>),
     ObjectLiteral(Box<ObjectLiteral>),
     Cast(Box<Cast>),
+    Try(Box<Try>),
     Template(Box<Template>),
+    // `if` and `switch` in expression position, each branch gives the value
+    // of the expression statement it ends with
+    If(Box<IfStmt>),
+    Switch(Box<SwitchStmt>),
     _Self,
 }
 
@@ -175,7 +335,11 @@ pub struct ReturnStmt {
 pub struct VarDeclStmt {
     pub loc: SourceLoc,
     pub is_const: bool,
+    // only module-level variables can be exported
+    pub exported: bool,
     pub id: String,
+    // the names of `let (q, r) = ...`, which takes apart a tuple
+    pub destructure: Option<Vec<String>>,
     pub type_annotation: Option<Box<Type>>,
     pub value: Expr,
 }
@@ -183,34 +347,74 @@ pub struct VarDeclStmt {
 #[derive(Debug, Clone)]
 pub struct WhileStmt {
     pub loc: SourceLoc,
+    pub label: Option<String>,
     pub condition: Expr,
     pub consequent: Stmt,
 }
 
+#[derive(Debug, Clone)]
+pub struct BreakStmt {
+    pub loc: SourceLoc,
+    pub label: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ContinueStmt {
+    pub loc: SourceLoc,
+    pub label: Option<String>,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum PatternKind {
     CatchAll,
+    // matches anything and binds it to a name
+    Binding(String),
     EnumVariant {
         id: String,
-        values: Vec<(String, types::Type)>,
+        values: Vec<Pattern>,
+        // the index of the variant, resolved by the checker
+        idx: usize,
     },
     Integer(i64),
     IntegerRange(i64, i64),
     String(String),
+    // `case Foo f` matches an interface value holding a Foo
+    Type {
+        annotation: Box<Type>,
+        binding: Option<String>,
+        typ: types::Type,
+    },
+    // `Point { x: 0, y }`, fields which are left out match anything
+    Struct {
+        annotation: Box<Type>,
+        fields: Vec<FieldPattern>,
+    },
+    // `a | b`, matches when any of the alternatives does
+    Or(Vec<Pattern>),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldPattern {
+    pub id: String,
+    // the index of the field, resolved by the checker
+    pub idx: usize,
+    pub pattern: Pattern,
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Pattern {
     pub loc: SourceLoc,
     pub kind: PatternKind,
+    // the type of the value being matched, set by the checker
+    pub typ: types::Type,
 }
 
 #[derive(Debug, Clone)]
 pub struct CaseStmt {
-    // pattern??
     pub pattern: Box<Pattern>,
+    // `pattern if condition`, the case only matches when the condition holds
+    pub guard: Option<Expr>,
     pub block: Box<BlockStmt>,
-    pub case_idx: i64,
 }
 
 #[derive(Debug, Clone)]
@@ -220,12 +424,20 @@ pub struct SwitchStmt {
     pub cases: Vec<CaseStmt>,
 }
 
+// Name of the hidden variable holding the iterator while a `for` loop runs,
+// it can never clash with a user identifier
+pub const FOR_ITERATOR_ID: &str = "for.iterator";
+
 #[derive(Debug, Clone)]
 pub struct ForStmt {
     pub loc: SourceLoc,
+    pub label: Option<String>,
     pub id: String,
     pub iterator: Expr,
     pub consequent: Stmt,
+    // filled in by the checker when the iterator protocol is used, used for codegen
+    pub next_call: Option<Expr>,
+    pub some_idx: usize,
 }
 
 #[derive(Debug, Clone, Default)]
@@ -248,6 +460,8 @@ pub enum Stmt {
     While(Box<WhileStmt>),
     Switch(Box<SwitchStmt>),
     For(Box<ForStmt>),
+    Break(Box<BreakStmt>),
+    Continue(Box<ContinueStmt>),
     Block(Box<BlockStmt>),
     ExprStmt(Box<ExprStmt>),
 }
@@ -261,6 +475,7 @@ pub struct Param {
 #[derive(Debug, Default, Clone)]
 pub struct FuncSignature {
     pub id: String,
+    pub type_params: Vec<String>,
     pub symbol_name: String,
     pub params: Vec<Param>,
     pub return_type: Option<Box<Type>>,
@@ -269,6 +484,7 @@ pub struct FuncSignature {
 #[derive(Debug, Default, Clone)]
 pub struct Func {
     pub loc: SourceLoc,
+    pub exported: bool,
     pub signature: FuncSignature,
     pub typ_: types::FunctionType,
     pub body: Box<BlockStmt>,
@@ -277,19 +493,33 @@ pub struct Func {
 #[derive(Debug, Default, Clone)]
 pub struct StructField {
     pub loc: SourceLoc,
+    pub exported: bool,
     pub id: String,
     pub type_annotation: Box<Type>,
+    // `field: T = value`, used when an object literal leaves the field out
+    pub default: Option<Expr>,
 }
 
 #[derive(Debug, Default, Clone)]
 pub struct Struct {
     pub loc: SourceLoc,
+    pub exported: bool,
     pub id: String,
+    pub type_params: Vec<String>,
     pub fields: Vec<StructField>,
     pub functions: Vec<Box<Func>>,
     pub typ: types::Type,
 }
 
+// `extension Type { ... }` adds its functions to the method set of the type
+#[derive(Debug, Default, Clone)]
+pub struct Extension {
+    pub loc: SourceLoc,
+    pub annotation: Box<Type>,
+    pub functions: Vec<Box<Func>>,
+    pub typ: types::Type,
+}
+
 #[derive(Debug, Default, Clone)]
 pub struct EnumVariant {
     pub loc: SourceLoc,
@@ -300,15 +530,20 @@ pub struct EnumVariant {
 #[derive(Debug, Default, Clone)]
 pub struct Enum {
     pub loc: SourceLoc,
+    pub exported: bool,
     pub id: String,
+    pub type_params: Vec<String>,
     pub variants: Vec<EnumVariant>,
+    pub functions: Vec<Box<Func>>,
     pub typ: types::Type,
 }
 
 #[derive(Debug, Default, Clone)]
 pub struct Interface {
     pub loc: SourceLoc,
+    pub exported: bool,
     pub id: String,
+    pub type_params: Vec<String>,
     pub methods: Vec<FuncSignature>,
     pub typ: types::Type,
 }
@@ -317,6 +552,8 @@ pub struct Interface {
 pub struct Import {
     pub package: String,
     pub file: String,
+    // `import "std:net" as net;` only makes the names available as `net.name`
+    pub alias: Option<String>,
 }
 
 #[derive(Debug, Default, Clone)]
@@ -326,6 +563,9 @@ pub struct File {
     pub structs: Vec<Box<Struct>>,
     pub enums: Vec<Box<Enum>>,
     pub interfaces: Vec<Box<Interface>>,
+    pub extensions: Vec<Box<Extension>>,
+    // module-level `let` and `const`, initialised in order before main runs
+    pub globals: Vec<Box<VarDeclStmt>>,
     pub imports: Vec<Import>,
 }
 
@@ -336,7 +576,21 @@ pub struct Package {
     pub files: Vec<Box<File>>,
 }
 
+// A generic function or method checked with concrete type arguments
+#[derive(Debug, Default, Clone)]
+pub struct Instance {
+    pub file: String,
+    pub func: Box<Func>,
+    // the struct instance when this is a method
+    pub self_type: Option<types::Type>,
+}
+
 #[derive(Debug, Default, Clone)]
 pub struct Program {
     pub packages: Vec<Box<Package>>,
+    // filled in by the checker, used for codegen
+    pub instances: Vec<Instance>,
+    // the package and file ids in the order their globals are initialised,
+    // every file comes after the files it imports
+    pub init_order: Vec<(String, String)>,
 }
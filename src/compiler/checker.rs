@@ -1,7 +1,11 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::RwLock;
 
 use crate::builtins::Builtins;
+use crate::compiler::generics::{self, TypeArgs};
+use crate::compiler::init;
 use crate::compiler::mangle;
+use crate::compiler::patterns;
 use crate::compiler::{SourceLoc, ast};
 use crate::types::{self, NameSpecification, Type, clone_struct_fields};
 
@@ -10,6 +14,7 @@ pub enum SemaErrorReason {
     GenericError, // todo(caleb): Remove me!
     IdentifierNotFound,
     NonNumericTypeInBinaryExpression,
+    NonIntegerTypeInBinaryExpression,
     IncompatibleTypesInBinaryExpression,
     IncompatibleTypesInVariableDefinition,
     CannotUseExpressionInLeftHandExpression,
@@ -46,7 +51,76 @@ pub enum SemaErrorReason {
     CannotUseMethodAsSelector,
     CannotUseEnumVariantAsSelector,
     CannotStoreIntoSliceExpression,
-    ExpectedAnIndex
+    ExpectedAnIndex,
+    ValueIsNotIterable,
+    BreakOutsideOfLoop,
+    ContinueOutsideOfLoop,
+    LoopLabelNotFound,
+    InvalidTypeInUnaryExpression,
+    ValueIsNotCallable,
+    CannotAssignToCapturedVariable,
+    EnumVariantCannotBeUsedAsValue,
+    CannotInferTypeArguments,
+    TypeArgumentCountMismatch,
+    InstantiationTooDeep,
+    ValueCannotBeTried,
+    WrappingCastMustNarrowToByte,
+    TryInFunctionWithIncompatibleReturnType,
+    IteratorRequiresStdIter,
+    TypeCannotSatisfyInterface,
+    DuplicateSwitchCase,
+    OptionalCastRequiresStdResult,
+    // the names of the variants which are not covered
+    SwitchIsNotExhaustive(Vec<String>),
+    DuplicatePatternBinding,
+    OrPatternBindingsDiffer,
+    PatternTypeMismatch,
+    IfExpressionWithoutElse,
+    BranchHasNoValue,
+    IncompatibleTypesInBranches,
+    DuplicateMethod,
+    CannotExtendInterface,
+    StructFieldNotInitialised(String),
+    TypeCannotBeInitialised,
+    InitCannotReturnValue,
+    SelfUsedBeforeFieldsInitialised(String),
+    GlobalAlreadyDefined,
+    NameNotExported,
+    AmbiguousName,
+    BuiltinMethodCannotBeUsedAsValue,
+    InvalidMapKeyType,
+    MapLiteralEntryTypesIncompatible,
+    CannotSliceMap,
+    CannotDestructureNonTuple,
+    DestructureCountMismatch,
+    TupleIndexOutOfRange,
+    GlobalCannotBeDestructured,
+}
+
+// Warnings do not stop compilation, they are collected while checking and
+// returned to the driver
+#[derive(Debug, Clone, PartialEq)]
+pub enum SemaWarningReason {
+    UnreachableSwitchCase,
+    OverlappingIntegerPatterns,
+}
+
+#[derive(Debug, Clone)]
+pub struct SemaWarning {
+    pub reason: SemaWarningReason,
+    pub loc: SourceLoc,
+    pub file: String,
+    pub package: String,
+}
+
+impl std::fmt::Display for SemaWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Warning: {:?} in {}:{} at {}:{}",
+            self.reason, self.package, self.file, self.loc.line, self.loc.col
+        )
+    }
 }
 
 #[derive(Debug)]
@@ -68,8 +142,139 @@ pub fn error_loc<T>(reason: SemaErrorReason, loc: SourceLoc) -> SemaResult<T> {
     })
 }
 
+// Where a generic was defined, its instances are checked as if they were
+// written there
+#[derive(Clone)]
+struct GenericScope {
+    package: String,
+    file: String,
+    imports: Vec<ast::Import>,
+}
+
+enum GenericDefinition {
+    Struct(ast::Struct),
+    Enum(ast::Enum),
+    Interface(ast::Interface),
+}
+
+struct GenericType {
+    scope: GenericScope,
+    definition: GenericDefinition,
+}
+
+impl GenericType {
+    fn type_params(&self) -> &Vec<String> {
+        match &self.definition {
+            GenericDefinition::Struct(s) => &s.type_params,
+            GenericDefinition::Enum(e) => &e.type_params,
+            GenericDefinition::Interface(i) => &i.type_params,
+        }
+    }
+}
+
+// A generic function or method with its type arguments substituted, waiting
+// to be checked
+// The default value of a struct field, checked where the struct is declared
+// every time an object literal leaves the field out
+#[derive(Clone)]
+struct FieldDefault {
+    field: String,
+    value: ast::Expr,
+    scope: GenericScope,
+}
+
+struct PendingInstance {
+    scope: GenericScope,
+    func: Box<ast::Func>,
+    self_type: Option<Type>,
+    // how many instances led to this one, so polymorphic recursion ends
+    depth: usize,
+}
+
+// Deeper instances are assumed to come from a generic using itself with ever
+// larger type arguments
+const MAX_INSTANCE_DEPTH: usize = 64;
+
+// Looks up a name the same way as types and functions, first the file, then
+// the builtins and then the imports, names from other packages have to be
+// exported. A name found in more than one import is ambiguous, the names of
+// an aliased import are only found through its alias
+fn find_in_scope<'a, T>(
+    map: &'a HashMap<NameSpecification, T>,
+    exported: &HashSet<NameSpecification>,
+    imports: &Vec<ast::Import>,
+    package: &str,
+    file: &str,
+    name: &str,
+) -> Result<Option<(&'a T, NameSpecification)>, SemaErrorReason> {
+    for (candidate, file) in [(package, file), ("builtins", "builtins")] {
+        let name_spec = NameSpecification {
+            package: candidate.into(),
+            file: file.into(),
+            name: name.into(),
+        };
+        if let Some(t) = map.get(&name_spec) {
+            return Ok(Some((t, name_spec)));
+        }
+    }
+
+    let mut found = None;
+    for import in imports.iter().filter(|i| i.alias.is_none()) {
+        match find_in_import(map, exported, import, package, name) {
+            Ok(Some((t, spec))) => match &found {
+                Some(Ok((_, other))) if *other != spec => return Err(SemaErrorReason::AmbiguousName),
+                Some(Ok(_)) => {}
+                _ => found = Some(Ok((t, spec))),
+            },
+            Err(reason) if found.is_none() => found = Some(Err(reason)),
+            _ => {}
+        }
+    }
+    found.transpose()
+}
+
+// Looks up a name in the file of an import, which has to export it when the
+// file belongs to another package
+fn find_in_import<'a, T>(
+    map: &'a HashMap<NameSpecification, T>,
+    exported: &HashSet<NameSpecification>,
+    import: &ast::Import,
+    package: &str,
+    name: &str,
+) -> Result<Option<(&'a T, NameSpecification)>, SemaErrorReason> {
+    let name_spec = NameSpecification {
+        package: import.package.clone(),
+        file: import.file.clone(),
+        name: name.into(),
+    };
+    let Some(t) = map.get(&name_spec) else {
+        return Ok(None);
+    };
+    if name_spec.package != package && !exported.contains(&name_spec) {
+        return Err(SemaErrorReason::NameNotExported);
+    }
+    Ok(Some((t, name_spec)))
+}
+
+fn find_alias<'a>(imports: &'a [ast::Import], alias: &str) -> Option<&'a ast::Import> {
+    imports.iter().find(|i| i.alias.as_deref() == Some(alias))
+}
+
 struct TypeCollection {
     types: HashMap<NameSpecification, Type>,
+    generics: HashMap<NameSpecification, GenericType>,
+    // instances of generic types, by their mangled name
+    instances: RwLock<HashMap<String, Type>>,
+    // checked once the rest of the program has been checked
+    pending: RwLock<Vec<PendingInstance>>,
+    // depth of the instance being checked, zero outside of instances
+    instance_depth: RwLock<usize>,
+    // default field values of each struct
+    defaults: RwLock<HashMap<Type, Vec<FieldDefault>>>,
+    // types and generic types other packages can use
+    exported: HashSet<NameSpecification>,
+    // fields and methods which are not exported, with the package they belong to
+    private_members: RwLock<HashMap<(Type, String), String>>,
 }
 
 impl TypeCollection {
@@ -80,26 +285,9 @@ impl TypeCollection {
         package_id: &str,
         file_id: &str,
         name: &String,
-    ) -> Option<&Type> {
-        if let Some(typ) = self.get_exact(package_id, file_id, name) {
-            return Some(typ);
-        }
-
-        if let Some(typ) = self.get_exact("builtins", "builtins", name) {
-            return Some(typ);
-        }
-
-        for import in imports {
-            let name_spec = NameSpecification {
-                package: import.package.clone(),
-                file: import.file.clone(),
-                name: name.clone(),
-            };
-            if let Some(typ) = self.types.get(&name_spec) {
-                return Some(typ);
-            }
-        }
-        None
+    ) -> Result<Option<&Type>, SemaErrorReason> {
+        let found = find_in_scope(&self.types, &self.exported, imports, package_id, file_id, name)?;
+        Ok(found.map(|(typ, _)| typ))
     }
 
     pub fn get_exact(&self, package: &str, file: &str, name: &str) -> Option<&Type> {
@@ -110,6 +298,284 @@ impl TypeCollection {
         };
         self.types.get(&name_spec)
     }
+
+    pub fn get_generic(
+        &self,
+        imports: &Vec<ast::Import>,
+        package_id: &str,
+        file_id: &str,
+        name: &str,
+    ) -> Result<Option<NameSpecification>, SemaErrorReason> {
+        let found = find_in_scope(&self.generics, &self.exported, imports, package_id, file_id, name)?;
+        Ok(found.map(|(_, spec)| spec))
+    }
+
+    fn add_private_member(&self, typ: &Type, member: &str, package: &str) {
+        self.private_members
+            .write()
+            .unwrap()
+            .insert((typ.clone(), member.into()), package.into());
+    }
+
+    // Whether a field or method of a type can be used from the package
+    fn member_visible(&self, typ: &Type, member: &str, package: &str) -> bool {
+        match self.private_members.read().unwrap().get(&(typ.clone(), member.into())) {
+            Some(owner) => owner == package,
+            None => true,
+        }
+    }
+
+    // Queues an instance to be checked, one level deeper than the instance
+    // which uses it
+    fn add_pending(&self, scope: GenericScope, func: Box<ast::Func>, self_type: Option<Type>) -> Result<(), SemaErrorReason> {
+        let depth = *self.instance_depth.read().unwrap() + 1;
+        if depth > MAX_INSTANCE_DEPTH {
+            return Err(SemaErrorReason::InstantiationTooDeep);
+        }
+        self.pending.write().unwrap().push(PendingInstance {
+            scope,
+            func,
+            self_type,
+            depth,
+        });
+        Ok(())
+    }
+
+    fn add_default(&self, typ: &Type, field: &str, value: ast::Expr, scope: GenericScope) {
+        self.defaults
+            .write()
+            .unwrap()
+            .entry(typ.clone())
+            .or_default()
+            .push(FieldDefault {
+                field: field.into(),
+                value,
+                scope,
+            });
+    }
+
+    fn get_defaults(&self, typ: &Type) -> Vec<FieldDefault> {
+        self.defaults
+            .read()
+            .unwrap()
+            .get(typ)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    // Creates the instance of a generic type for the given type arguments,
+    // every use with the same arguments gets the same type
+    fn instantiate(&self, spec: &NameSpecification, args: Vec<Type>) -> SemaResult<Type> {
+        let key = mangle::mangle_generic_name(spec, &args);
+        if let Some(typ) = self.instances.read().unwrap().get(&key) {
+            return Ok(typ.clone());
+        }
+
+        let generic = &self.generics[spec];
+        let scope = &generic.scope;
+        let error = |reason| SemaError {
+            reason,
+            loc: SourceLoc::default(),
+            file: scope.file.clone(),
+            package: scope.package.clone(),
+        };
+        if generic.type_params().len() != args.len() {
+            return Err(error(SemaErrorReason::TypeArgumentCountMismatch));
+        }
+        let type_args: TypeArgs = generic
+            .type_params()
+            .iter()
+            .cloned()
+            .zip(args.iter().cloned())
+            .collect();
+        let lookup = |ast_type: &ast::Type| {
+            let mut ast_type = ast_type.clone();
+            generics::substitute_type(&mut ast_type, &type_args);
+            type_lookup(&ast_type, self, &scope.package, &scope.file, &scope.imports)
+        };
+        let lookup_signature = |signature: &ast::FuncSignature| -> SemaResult<types::FunctionType> {
+            Ok(types::FunctionType {
+                params: signature
+                    .params
+                    .iter()
+                    .map(|p| lookup(&p.type_annotation))
+                    .collect::<SemaResult<Vec<_>>>()?,
+                returns: types::flatten_returns(
+                    signature
+                        .return_type
+                        .iter()
+                        .map(|r| lookup(r))
+                        .collect::<SemaResult<Vec<_>>>()?,
+                ),
+            })
+        };
+
+        // the instance is registered before its members are resolved, so it
+        // can refer to itself
+        let typ = match &generic.definition {
+            GenericDefinition::Struct(_) => types::struct_type(spec.clone(), args, Vec::new()),
+            GenericDefinition::Enum(_) => types::enum_type(spec.clone(), args, Vec::new()),
+            GenericDefinition::Interface(_) => {
+                types::interface_type(spec.clone(), args, Vec::new())
+            }
+        };
+        self.instances.write().unwrap().insert(key, typ.clone());
+
+        // the methods of each instance are checked with its type arguments
+        let add_methods = |functions: &Vec<Box<ast::Func>>| -> SemaResult<()> {
+            for func in functions.iter() {
+                let mut func = func.clone();
+                generics::substitute_func(&mut func, &type_args);
+                func.typ_ = lookup_signature(&func.signature)?;
+                func.signature.symbol_name = mangle::mangle_method_name(&func.signature.id, &typ);
+                typ.add_method(&func.signature.id, func.typ_.clone());
+                if !func.exported {
+                    self.add_private_member(&typ, &func.signature.id, &scope.package);
+                }
+                self.add_pending(scope.clone(), func, Some(typ.clone()))
+                    .map_err(error)?;
+            }
+            Ok(())
+        };
+
+        match (&generic.definition, typ.kind()) {
+            (GenericDefinition::Struct(struct_), types::TypeKind::Struct(struct_type)) => {
+                for field in struct_.fields.iter() {
+                    let field_type = lookup(&field.type_annotation)?;
+                    struct_type
+                        .fields
+                        .write()
+                        .unwrap()
+                        .push((field.id.clone(), field_type));
+                    if !field.exported {
+                        self.add_private_member(&typ, &field.id, &scope.package);
+                    }
+                    if let Some(default) = &field.default {
+                        let mut value = default.clone();
+                        generics::substitute_expr(&mut value, &type_args);
+                        self.add_default(&typ, &field.id, value, scope.clone());
+                    }
+                }
+                add_methods(&struct_.functions)?;
+            }
+            (GenericDefinition::Enum(enum_), types::TypeKind::Enum(enum_type)) => {
+                for variant in enum_.variants.iter() {
+                    let variant_types = variant
+                        .variant_types
+                        .iter()
+                        .map(|t| lookup(t))
+                        .collect::<SemaResult<Vec<_>>>()?;
+                    enum_type
+                        .variants
+                        .write()
+                        .unwrap()
+                        .push((variant.id.clone(), variant_types));
+                }
+                add_methods(&enum_.functions)?;
+            }
+            (GenericDefinition::Interface(interface), types::TypeKind::Interface(interface_type)) => {
+                for method in interface.methods.iter() {
+                    let method_type = lookup_signature(method)?;
+                    interface_type
+                        .methods
+                        .write()
+                        .unwrap()
+                        .push((method.id.clone(), method_type));
+                }
+            }
+            _ => unreachable!(),
+        }
+
+        Ok(typ)
+    }
+
+    // Binds the type parameters in `ast_type` by matching it against `typ`,
+    // parameters which are already bound are left alone
+    fn infer_type_args(
+        &self,
+        scope: &GenericScope,
+        type_params: &Vec<String>,
+        ast_type: &ast::Type,
+        typ: &Type,
+        bindings: &mut TypeArgs,
+    ) {
+        match (ast_type, typ.kind()) {
+            (ast::Type::Identifier(id), _) => {
+                if type_params.contains(id) && !bindings.contains_key(id) {
+                    bindings.insert(id.clone(), typ.clone());
+                }
+            }
+            (ast::Type::Array(element_type), types::TypeKind::Array(element)) => {
+                self.infer_type_args(scope, type_params, element_type, element, bindings);
+            }
+            (ast::Type::Map(key_type, value_type), types::TypeKind::Map(key, value)) => {
+                self.infer_type_args(scope, type_params, key_type, key, bindings);
+                self.infer_type_args(scope, type_params, value_type, value, bindings);
+            }
+            (
+                ast::Type::Function {
+                    params,
+                    return_type,
+                },
+                types::TypeKind::Function(func_type),
+            ) => {
+                for (param, param_type) in params.iter().zip(func_type.params.iter()) {
+                    self.infer_type_args(scope, type_params, param, param_type, bindings);
+                }
+                if let (Some(return_type), Some(returns)) = (return_type, types::return_type(func_type)) {
+                    self.infer_type_args(scope, type_params, return_type, &returns, bindings);
+                }
+            }
+            (ast::Type::Tuple(element_types), types::TypeKind::Tuple(elements)) => {
+                for (element_type, element) in element_types.iter().zip(elements.iter()) {
+                    self.infer_type_args(scope, type_params, element_type, element, bindings);
+                }
+            }
+            (ast::Type::Generic(id, args), _) => {
+                let Ok(Some(generic_spec)) = self.get_generic(&scope.imports, &scope.package, &scope.file, id) else {
+                    return;
+                };
+                match types::generic_spec(typ) {
+                    Some((spec, type_args)) if *spec == generic_spec => {
+                        for (arg, type_arg) in args.iter().zip(type_args.iter()) {
+                            self.infer_type_args(scope, type_params, arg, type_arg, bindings);
+                        }
+                    }
+                    _ => {
+                        // a value implementing a generic interface gives the
+                        // type arguments through its methods
+                        let generic = &self.generics[&generic_spec];
+                        let GenericDefinition::Interface(interface) = &generic.definition else {
+                            return;
+                        };
+                        let mut interface_bindings = TypeArgs::new();
+                        for method in interface.methods.iter() {
+                            let Some(method_type) = typ.get_method(&method.id) else {
+                                continue;
+                            };
+                            let method_ast_type = ast::Type::Function {
+                                params: method.params.iter().map(|p| (*p.type_annotation).clone()).collect(),
+                                return_type: method.return_type.clone(),
+                            };
+                            self.infer_type_args(
+                                &generic.scope,
+                                &interface.type_params,
+                                &method_ast_type,
+                                &types::function_type(method_type.params, method_type.returns),
+                                &mut interface_bindings,
+                            );
+                        }
+                        for (arg, type_param) in args.iter().zip(interface.type_params.iter()) {
+                            if let Some(type_arg) = interface_bindings.get(type_param) {
+                                self.infer_type_args(scope, type_params, arg, type_arg, bindings);
+                            }
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
 }
 
 fn builtin_types(collection: &mut TypeCollection) {
@@ -120,6 +586,7 @@ fn builtin_types(collection: &mut TypeCollection) {
     };
     let string_interface = types::interface_type(
         string_name_spec.clone(),
+        Vec::new(),
         vec![(
             "string".into(),
             types::FunctionType {
@@ -136,19 +603,39 @@ fn builtin_types(collection: &mut TypeCollection) {
 fn collect_types(program: &ast::Program) -> TypeCollection {
     let mut collection = TypeCollection {
         types: HashMap::new(),
+        generics: HashMap::new(),
+        instances: RwLock::new(HashMap::new()),
+        pending: RwLock::new(Vec::new()),
+        instance_depth: RwLock::new(0),
+        defaults: RwLock::new(HashMap::new()),
+        exported: HashSet::new(),
+        private_members: RwLock::new(HashMap::new()),
     };
 
     builtin_types(&mut collection);
 
     for package in program.packages.iter() {
         for file in package.files.iter() {
+            let scope = GenericScope {
+                package: package.id.clone(),
+                file: file.id.clone(),
+                imports: file.imports.clone(),
+            };
             for struct_ in file.structs.iter() {
                 let name_spec = NameSpecification {
                     package: package.id.clone(),
                     file: file.id.clone(),
                     name: struct_.id.clone(),
                 };
-                let typ = types::struct_type(name_spec.clone(), Vec::new());
+                if struct_.exported {
+                    collection.exported.insert(name_spec.clone());
+                }
+                if !struct_.type_params.is_empty() {
+                    let definition = GenericDefinition::Struct(*struct_.clone());
+                    collection.generics.insert(name_spec, GenericType { scope: scope.clone(), definition });
+                    continue;
+                }
+                let typ = types::struct_type(name_spec.clone(), Vec::new(), Vec::new());
                 collection.types.insert(name_spec, typ);
             }
             for enum_ in file.enums.iter() {
@@ -157,7 +644,15 @@ fn collect_types(program: &ast::Program) -> TypeCollection {
                     file: file.id.clone(),
                     name: enum_.id.clone(),
                 };
-                let typ = types::enum_type(name_spec.clone(), Vec::new());
+                if enum_.exported {
+                    collection.exported.insert(name_spec.clone());
+                }
+                if !enum_.type_params.is_empty() {
+                    let definition = GenericDefinition::Enum(*enum_.clone());
+                    collection.generics.insert(name_spec, GenericType { scope: scope.clone(), definition });
+                    continue;
+                }
+                let typ = types::enum_type(name_spec.clone(), Vec::new(), Vec::new());
                 collection.types.insert(name_spec, typ);
             }
             for interface in file.interfaces.iter() {
@@ -166,7 +661,15 @@ fn collect_types(program: &ast::Program) -> TypeCollection {
                     file: file.id.clone(),
                     name: interface.id.clone(),
                 };
-                let typ = types::interface_type(name_spec.clone(), Vec::new());
+                if interface.exported {
+                    collection.exported.insert(name_spec.clone());
+                }
+                if !interface.type_params.is_empty() {
+                    let definition = GenericDefinition::Interface(*interface.clone());
+                    collection.generics.insert(name_spec, GenericType { scope: scope.clone(), definition });
+                    continue;
+                }
+                let typ = types::interface_type(name_spec.clone(), Vec::new(), Vec::new());
                 collection.types.insert(name_spec, typ);
             }
         }
@@ -178,7 +681,7 @@ fn collect_types(program: &ast::Program) -> TypeCollection {
 fn check_types(program: &ast::Program, collection: &TypeCollection) -> SemaResult<()> {
     for package in program.packages.iter() {
         for file in package.files.iter() {
-            for struct_ in file.structs.iter() {
+            for struct_ in file.structs.iter().filter(|s| s.type_params.is_empty()) {
                 let name_spec = NameSpecification {
                     package: package.id.clone(),
                     file: file.id.clone(),
@@ -203,9 +706,20 @@ fn check_types(program: &ast::Program, collection: &TypeCollection) -> SemaResul
                     } else {
                         unreachable!();
                     }
+                    if !field.exported {
+                        collection.add_private_member(&typ, &field.id, &package.id);
+                    }
+                    if let Some(default) = &field.default {
+                        let scope = GenericScope {
+                            package: package.id.clone(),
+                            file: file.id.clone(),
+                            imports: file.imports.clone(),
+                        };
+                        collection.add_default(&typ, &field.id, default.clone(), scope);
+                    }
                 }
             }
-            for enum_ in file.enums.iter() {
+            for enum_ in file.enums.iter().filter(|e| e.type_params.is_empty()) {
                 let name_spec = NameSpecification {
                     package: package.id.clone(),
                     file: file.id.clone(),
@@ -228,7 +742,7 @@ fn check_types(program: &ast::Program, collection: &TypeCollection) -> SemaResul
                     }
                 }
             }
-            for interface in file.interfaces.iter() {
+            for interface in file.interfaces.iter().filter(|i| i.type_params.is_empty()) {
                 let name_spec = NameSpecification {
                     package: package.id.clone(),
                     file: file.id.clone(),
@@ -258,6 +772,7 @@ fn check_types(program: &ast::Program, collection: &TypeCollection) -> SemaResul
                         )?;
                         returns.push(return_type);
                     }
+                    let returns = types::flatten_returns(returns);
                     if let types::TypeKind::Interface(interface_type) = &typ.inner.kind {
                         interface_type
                             .methods
@@ -279,6 +794,12 @@ fn type_lookup(
     file_id: &str,
     imports: &Vec<ast::Import>,
 ) -> SemaResult<Type> {
+    let error = |reason| SemaError {
+        reason,
+        loc: SourceLoc::default(),
+        file: file_id.into(),
+        package: package_id.into(),
+    };
     match ast_type {
         ast::Type::Integer => Ok(types::integer()),
         ast::Type::Byte => Ok(types::byte()),
@@ -287,13 +808,9 @@ fn type_lookup(
         ast::Type::Bool => Ok(types::bool()),
         ast::Type::Identifier(id) => collection
             .get(imports, package_id, file_id, id)
+            .map_err(error)?
             .cloned()
-            .ok_or(SemaError {
-                reason: SemaErrorReason::TypeNotFound,
-                loc: SourceLoc::default(),
-                file: file_id.into(),
-                package: package_id.into(),
-            }),
+            .ok_or(error(SemaErrorReason::TypeNotFound)),
         ast::Type::Array(element_type) => Ok(types::array(type_lookup(
             element_type,
             collection,
@@ -301,17 +818,97 @@ fn type_lookup(
             file_id,
             imports,
         )?)),
-        _ => Err(SemaError {
-            reason: SemaErrorReason::TypeNotFound,
-            loc: SourceLoc::default(),
-            file: file_id.into(),
-            package: package_id.into(),
-        }),
+        ast::Type::Map(key_type, value_type) => {
+            let key_type = type_lookup(key_type, collection, package_id, file_id, imports)?;
+            if !types::is_map_key(&key_type) {
+                return Err(error(SemaErrorReason::InvalidMapKeyType));
+            }
+            let value_type = type_lookup(value_type, collection, package_id, file_id, imports)?;
+            Ok(types::map(key_type, value_type))
+        }
+        ast::Type::Tuple(element_types) => Ok(types::tuple(
+            element_types
+                .iter()
+                .map(|t| type_lookup(t, collection, package_id, file_id, imports))
+                .collect::<SemaResult<Vec<_>>>()?,
+        )),
+        ast::Type::Function {
+            params,
+            return_type,
+        } => {
+            let params = params
+                .iter()
+                .map(|p| type_lookup(p, collection, package_id, file_id, imports))
+                .collect::<SemaResult<Vec<_>>>()?;
+            let returns = return_type
+                .iter()
+                .map(|r| type_lookup(r, collection, package_id, file_id, imports))
+                .collect::<SemaResult<Vec<_>>>()?;
+            Ok(types::function_type(params, types::flatten_returns(returns)))
+        }
+        ast::Type::Generic(id, args) => {
+            let args = args
+                .iter()
+                .map(|a| type_lookup(a, collection, package_id, file_id, imports))
+                .collect::<SemaResult<Vec<_>>>()?;
+            match collection.get_generic(imports, package_id, file_id, id).map_err(error)? {
+                Some(spec) => collection.instantiate(&spec, args),
+                None => Err(error(SemaErrorReason::TypeNotFound)),
+            }
+        }
+        ast::Type::Qualified(alias, typ) => {
+            let import = find_alias(imports, alias).ok_or(error(SemaErrorReason::TypeNotFound))?;
+            match typ.as_ref() {
+                ast::Type::Identifier(id) => {
+                    find_in_import(&collection.types, &collection.exported, import, package_id, id)
+                        .map_err(error)?
+                        .map(|(typ, _)| typ.clone())
+                        .ok_or(error(SemaErrorReason::TypeNotFound))
+                }
+                ast::Type::Generic(id, args) => {
+                    let args = args
+                        .iter()
+                        .map(|a| type_lookup(a, collection, package_id, file_id, imports))
+                        .collect::<SemaResult<Vec<_>>>()?;
+                    let generic =
+                        find_in_import(&collection.generics, &collection.exported, import, package_id, id)
+                            .map_err(error)?;
+                    match generic {
+                        Some((_, spec)) => collection.instantiate(&spec, args),
+                        None => Err(error(SemaErrorReason::TypeNotFound)),
+                    }
+                }
+                _ => Err(error(SemaErrorReason::TypeNotFound)),
+            }
+        }
+        ast::Type::Resolved(typ) => Ok(typ.clone()),
+        _ => Err(error(SemaErrorReason::TypeNotFound)),
     }
 }
 
+struct GenericFunction {
+    scope: GenericScope,
+    func: ast::Func,
+}
+
 struct FunctionCollection {
     functions: HashMap<NameSpecification, types::FunctionType>,
+    generics: HashMap<NameSpecification, GenericFunction>,
+    // symbols of the generic function instances created so far
+    instances: RwLock<HashSet<String>>,
+    // found while checking any function, returned by check_program
+    warnings: RwLock<Vec<SemaWarning>>,
+    // module-level variables, added as they are checked so a global can only
+    // use the ones initialised before it
+    globals: RwLock<HashMap<NameSpecification, Global>>,
+    // functions, generic functions and globals other packages can use
+    exported: HashSet<NameSpecification>,
+}
+
+#[derive(Clone)]
+struct Global {
+    typ: Type,
+    is_const: bool,
 }
 
 impl FunctionCollection {
@@ -322,38 +919,8 @@ impl FunctionCollection {
         package: &str,
         file: &str,
         name: &String,
-    ) -> Option<(&types::FunctionType, NameSpecification)> {
-        if let Some(typ) = self.get_exact(package, file, name) {
-            return Some((
-                typ,
-                NameSpecification {
-                    package: package.into(),
-                    file: file.into(),
-                    name: name.clone(),
-                },
-            ));
-        }
-        if let Some(typ) = self.get_exact("builtins", "builtins", name) {
-            return Some((
-                typ,
-                NameSpecification {
-                    package: "builtins".into(),
-                    file: "builtins".into(),
-                    name: name.clone(),
-                },
-            ));
-        }
-        for import in imports {
-            let name_spec = NameSpecification {
-                package: import.package.clone(),
-                file: import.file.clone(),
-                name: name.clone(),
-            };
-            if let Some(typ) = self.functions.get(&name_spec) {
-                return Some((typ, name_spec));
-            }
-        }
-        None
+    ) -> Result<Option<(&types::FunctionType, NameSpecification)>, SemaErrorReason> {
+        find_in_scope(&self.functions, &self.exported, imports, package, file, name)
     }
 
     pub fn get_exact(&self, package: &str, file: &str, name: &str) -> Option<&types::FunctionType> {
@@ -364,6 +931,86 @@ impl FunctionCollection {
         };
         self.functions.get(&name_spec)
     }
+
+    pub fn get_generic(
+        &self,
+        imports: &Vec<ast::Import>,
+        package: &str,
+        file: &str,
+        name: &str,
+    ) -> Result<Option<NameSpecification>, SemaErrorReason> {
+        let found = find_in_scope(&self.generics, &self.exported, imports, package, file, name)?;
+        Ok(found.map(|(_, spec)| spec))
+    }
+
+    fn get_global(
+        &self,
+        imports: &Vec<ast::Import>,
+        package: &str,
+        file: &str,
+        name: &str,
+    ) -> Result<Option<(Global, NameSpecification)>, SemaErrorReason> {
+        let globals = self.globals.read().unwrap();
+        let found = find_in_scope(&globals, &self.exported, imports, package, file, name)?;
+        Ok(found.map(|(global, spec)| (global.clone(), spec)))
+    }
+
+    // Creates the instance of a generic function for the given type arguments,
+    // returns its type and symbol name
+    fn instantiate(
+        &self,
+        collection: &TypeCollection,
+        spec: &NameSpecification,
+        args: Vec<Type>,
+    ) -> SemaResult<(types::FunctionType, String)> {
+        let generic = &self.generics[spec];
+        let scope = &generic.scope;
+        let type_args: TypeArgs = generic
+            .func
+            .signature
+            .type_params
+            .iter()
+            .cloned()
+            .zip(args.iter().cloned())
+            .collect();
+        let mut func = Box::new(generic.func.clone());
+        generics::substitute_func(&mut func, &type_args);
+
+        let lookup = |ast_type: &ast::Type| {
+            type_lookup(ast_type, collection, &scope.package, &scope.file, &scope.imports)
+        };
+        func.typ_ = types::FunctionType {
+            params: func
+                .signature
+                .params
+                .iter()
+                .map(|p| lookup(&p.type_annotation))
+                .collect::<SemaResult<Vec<_>>>()?,
+            returns: types::flatten_returns(
+                func.signature
+                    .return_type
+                    .iter()
+                    .map(|r| lookup(r))
+                    .collect::<SemaResult<Vec<_>>>()?,
+            ),
+        };
+        let symbol_name = mangle::mangle_generic_name(spec, &args);
+        func.signature.symbol_name = symbol_name.clone();
+
+        let func_type = func.typ_.clone();
+        if self.instances.write().unwrap().insert(symbol_name.clone()) {
+            let loc = func.loc;
+            collection
+                .add_pending(scope.clone(), func, None)
+                .map_err(|reason| SemaError {
+                    reason,
+                    loc,
+                    file: scope.file.clone(),
+                    package: scope.package.clone(),
+                })?;
+        }
+        Ok((func_type, symbol_name))
+    }
 }
 
 fn collect_functions(
@@ -373,6 +1020,11 @@ fn collect_functions(
 ) -> SemaResult<FunctionCollection> {
     let mut function_collection = FunctionCollection {
         functions: HashMap::new(),
+        generics: HashMap::new(),
+        instances: RwLock::new(HashSet::new()),
+        warnings: RwLock::new(Vec::new()),
+        globals: RwLock::new(HashMap::new()),
+        exported: HashSet::new(),
     };
 
     // collect the builtin functions into the builtin package(which is implicitly imported)
@@ -404,6 +1056,19 @@ fn collect_functions(
                     file: file.id.clone(),
                     name: func.signature.id.clone(),
                 };
+                if func.exported {
+                    function_collection.exported.insert(name_spec.clone());
+                }
+                if !func.signature.type_params.is_empty() {
+                    let scope = GenericScope {
+                        package: package.id.clone(),
+                        file: file.id.clone(),
+                        imports: file.imports.clone(),
+                    };
+                    let func = *func.clone();
+                    function_collection.generics.insert(name_spec, GenericFunction { scope, func });
+                    continue;
+                }
                 let mut params = Vec::new();
                 for param in func.signature.params.iter() {
                     params.push(type_lookup(
@@ -424,13 +1089,22 @@ fn collect_functions(
                         &file.imports,
                     )?);
                 }
+                let returns = types::flatten_returns(returns);
                 let function_type = types::FunctionType { params, returns };
                 function_collection
                     .functions
                     .insert(name_spec, function_type);
             }
 
-            for struct_ in file.structs.iter() {
+            for global in file.globals.iter().filter(|g| g.exported) {
+                function_collection.exported.insert(NameSpecification {
+                    package: package.id.clone(),
+                    file: file.id.clone(),
+                    name: global.id.clone(),
+                });
+            }
+
+            for struct_ in file.structs.iter().filter(|s| s.type_params.is_empty()) {
                 let typ = collection
                     .types
                     .get(&NameSpecification {
@@ -442,29 +1116,38 @@ fn collect_functions(
                     .clone();
 
                 for func in struct_.functions.iter() {
-                    let mut params = Vec::new();
-                    for param in func.signature.params.iter() {
-                        params.push(type_lookup(
-                            &param.type_annotation,
-                            &collection,
-                            &package.id,
-                            &file.id,
-                            &file.imports,
-                        )?);
-                    }
-                    let mut returns = Vec::new();
-                    for return_type in func.signature.return_type.iter() {
-                        returns.push(type_lookup(
-                            &return_type,
-                            &collection,
-                            &package.id,
-                            &file.id,
-                            &file.imports,
-                        )?);
-                    }
-                    let function_type = types::FunctionType { params, returns };
+                    collect_method(func, &typ, collection, &package.id, file)?;
+                }
+            }
+
+            for enum_ in file.enums.iter().filter(|e| e.type_params.is_empty()) {
+                let typ = collection
+                    .get_exact(&package.id, &file.id, &enum_.id)
+                    .unwrap()
+                    .clone();
+                for func in enum_.functions.iter() {
+                    collect_method(func, &typ, collection, &package.id, file)?;
+                }
+            }
 
-                    typ.add_method(&func.signature.id, function_type)
+            for extension in file.extensions.iter() {
+                let typ = type_lookup(
+                    &extension.annotation,
+                    collection,
+                    &package.id,
+                    &file.id,
+                    &file.imports,
+                )?;
+                if types::is_interface(&typ) {
+                    return Err(SemaError {
+                        reason: SemaErrorReason::CannotExtendInterface,
+                        loc: extension.loc,
+                        file: file.id.clone(),
+                        package: package.id.clone(),
+                    });
+                }
+                for func in extension.functions.iter() {
+                    collect_method(func, &typ, collection, &package.id, file)?;
                 }
             }
         }
@@ -473,14 +1156,72 @@ fn collect_functions(
     Ok(function_collection)
 }
 
-enum ExprResult {
-    Value(Type),
-    Type(Type),
-    Package(String),
-    Function(types::FunctionType, NameSpecification),
-    Method(Type, types::FunctionType, String),
-    EnumVariant(Type, usize),
-}
+// Adds a method to the method set of a type, which is shared by every file
+// and package so the same name can only be used once
+fn collect_method(
+    func: &ast::Func,
+    typ: &Type,
+    collection: &TypeCollection,
+    package_id: &str,
+    file: &ast::File,
+) -> SemaResult<()> {
+    let error = |reason| SemaError {
+        reason,
+        loc: func.loc,
+        file: file.id.clone(),
+        package: package_id.into(),
+    };
+    if typ.get_method(&func.signature.id).is_some() {
+        return Err(error(SemaErrorReason::DuplicateMethod));
+    }
+    if func.signature.id == "init" && func.signature.return_type.is_some() {
+        return Err(error(SemaErrorReason::InitCannotReturnValue));
+    }
+    let mut params = Vec::new();
+    for param in func.signature.params.iter() {
+        params.push(type_lookup(
+            &param.type_annotation,
+            collection,
+            package_id,
+            &file.id,
+            &file.imports,
+        )?);
+    }
+    let mut returns = Vec::new();
+    for return_type in func.signature.return_type.iter() {
+        returns.push(type_lookup(
+            return_type,
+            collection,
+            package_id,
+            &file.id,
+            &file.imports,
+        )?);
+    }
+    let returns = types::flatten_returns(returns);
+    typ.add_method(&func.signature.id, types::FunctionType { params, returns });
+    if !func.exported {
+        collection.add_private_member(typ, &func.signature.id, package_id);
+    }
+    Ok(())
+}
+
+enum ExprResult {
+    Value(Type),
+    Type(Type),
+    Package(String),
+    Function(types::FunctionType, NameSpecification),
+    Method(Type, types::FunctionType, String),
+    EnumVariant(Type, usize),
+    // generics which still need their type arguments
+    GenericFunction(NameSpecification),
+    GenericType(NameSpecification),
+    GenericEnumVariant(NameSpecification, usize),
+    // a module-level variable and its symbol
+    Global(Type, String),
+    // a built-in method on an array value like `push`
+    ArrayMethod(Type, ast::ArrayMethod),
+    MapMethod(Type, ast::MapMethod),
+}
 
 struct FuncTypeInference<'a> {
     ///structs: &'a Vec<Box<ast::Struct>>,
@@ -494,12 +1235,27 @@ struct FuncTypeInference<'a> {
     package_id: &'a str,
     file_id: &'a str,
     variable_scopes: Vec<HashMap<String, VariableBinding>>,
+    // labels and ids of the loops enclosing the current statement, innermost last
+    loops: Vec<(Option<String>, usize)>,
+    loop_count: usize,
+    // when checking a closure, the variables of the enclosing function and the
+    // ones the closure actually uses
+    captured: HashMap<String, VariableBinding>,
+    captures: Vec<(String, Type)>,
     loc: SourceLoc,
 }
 
+#[derive(Clone)]
 struct VariableBinding {
     typ: Type,
     is_const: bool,
+    // closures copy the variables they capture, so once one has been created
+    // the variable can no longer be assigned
+    captured: bool,
+    // how many loops enclosed the declaration, and the ids of the loops inside
+    // those which assign to the variable
+    loop_depth: usize,
+    assigned_in: HashSet<usize>,
 }
 
 impl<'a> FuncTypeInference<'a> {
@@ -519,6 +1275,10 @@ impl<'a> FuncTypeInference<'a> {
             package_id,
             file_id,
             variable_scopes: Vec::new(),
+            loops: Vec::new(),
+            loop_count: 0,
+            captured: HashMap::new(),
+            captures: Vec::new(),
             self_type: None,
             loc: SourceLoc::default(),
         }
@@ -541,6 +1301,10 @@ impl<'a> FuncTypeInference<'a> {
             package_id,
             file_id,
             variable_scopes: Vec::new(),
+            loops: Vec::new(),
+            loop_count: 0,
+            captured: HashMap::new(),
+            captures: Vec::new(),
             self_type: Some(self_type),
             loc: SourceLoc::default(),
         }
@@ -560,6 +1324,9 @@ impl<'a> FuncTypeInference<'a> {
             VariableBinding {
                 typ: typ.clone(),
                 is_const,
+                captured: false,
+                loop_depth: self.loops.len(),
+                assigned_in: HashSet::new(),
             },
         );
     }
@@ -573,6 +1340,20 @@ impl<'a> FuncTypeInference<'a> {
         None
     }
 
+    fn find_var_mut(&mut self, name: &String) -> Option<&mut VariableBinding> {
+        self.variable_scopes.iter_mut().rev().find_map(|scope| scope.get_mut(name))
+    }
+
+    // Looks up a variable of the enclosing function from inside a closure,
+    // recording it so codegen stores it in the closure object
+    fn capture_var(&mut self, name: &str) -> Option<Type> {
+        let typ = self.captured.get(name)?.typ.clone();
+        if !self.captures.iter().any(|(id, _)| id == name) {
+            self.captures.push((name.into(), typ.clone()));
+        }
+        Some(typ)
+    }
+
     pub fn ok(&self) -> SemaResult<()> {
         Ok(())
     }
@@ -590,29 +1371,42 @@ impl<'a> FuncTypeInference<'a> {
         })
     }
 
-    pub fn find_type(&self, name: &str) -> Option<Type> {
+    pub fn warn(&self, reason: SemaWarningReason, loc: SourceLoc) {
+        self.functions.warnings.write().unwrap().push(SemaWarning {
+            reason,
+            loc,
+            file: self.file_id.into(),
+            package: self.package_id.into(),
+        });
+    }
+
+    pub fn find_type(&self, name: &str) -> SemaResult<Option<Type>> {
         if name == "string" {
-             Some(types::string())
+             Ok(Some(types::string()))
         } else if name == "int" {
-            Some(types::integer())
+            Ok(Some(types::integer()))
         } else if name == "byte" {
-            Some(types::byte())
+            Ok(Some(types::byte()))
         } else if name == "number" {
-            Some(types::number())
+            Ok(Some(types::number()))
         } else if name == "bool" {
-            Some(types::bool())
+            Ok(Some(types::bool()))
         } else {
-            self.types
-            .get(
+            let typ = self.visible(self.types.get(
                 &self.imports,
                 self.package_id,
                 self.file_id,
                 &name.to_string(),
-            )
-            .cloned()
+            ))?;
+            Ok(typ.cloned())
         }
     }
 
+    // Turns a name which is not exported to the current package into an error
+    fn visible<T>(&self, found: Result<Option<T>, SemaErrorReason>) -> SemaResult<Option<T>> {
+        found.or_else(|reason| self.error(reason))
+    }
+
     fn binary_expr(&mut self, b: &mut ast::BinaryExpr, type_hint: Option<types::Type>) -> SemaResult<Type> {
         match b.kind {
             ast::BinaryExprKind::Add
@@ -649,6 +1443,24 @@ impl<'a> FuncTypeInference<'a> {
                 }
                 Ok(typ)
             }
+            ast::BinaryExprKind::Modulo
+            | ast::BinaryExprKind::BitwiseAnd
+            | ast::BinaryExprKind::BitwiseOr
+            | ast::BinaryExprKind::BitwiseXor
+            | ast::BinaryExprKind::ShiftLeft
+            | ast::BinaryExprKind::ShiftRight => {
+                // these only work on integers, and both sides must be the same width
+                let typ = self.expr(&mut b.lhs, type_hint)?;
+                self.expr(&mut b.rhs, Some(typ.clone()))?;
+
+                if !types::is_integer(&typ) && !types::is_byte(&typ) {
+                    return self.error_loc(SemaErrorReason::NonIntegerTypeInBinaryExpression, b.lhs.loc);
+                }
+                if types::compare(&b.lhs.typ, &b.rhs.typ) != types::ComparisonResult::Same {
+                    return self.error(SemaErrorReason::IncompatibleTypesInBinaryExpression);
+                }
+                Ok(typ)
+            }
             ast::BinaryExprKind::Equal
             | ast::BinaryExprKind::NotEqual
             | ast::BinaryExprKind::LessThan
@@ -683,24 +1495,172 @@ impl<'a> FuncTypeInference<'a> {
 
     fn unary_expr(
         &mut self,
-        _u: &mut Box<ast::UnaryExpr>,
-        _type_hint: Option<types::Type>,
+        u: &mut Box<ast::UnaryExpr>,
+        type_hint: Option<types::Type>,
     ) -> SemaResult<Type> {
-        unimplemented!()
+        match u.kind {
+            ast::UnaryExprKind::Negate => {
+                self.expr(&mut u.value, type_hint)?;
+                if !types::is_numeric(&u.value.typ) {
+                    return self.error_loc(SemaErrorReason::InvalidTypeInUnaryExpression, u.value.loc);
+                }
+            }
+            ast::UnaryExprKind::LogicalNot => {
+                // no hint, so the operand is checked before it could be cast to bool
+                self.expr(&mut u.value, None)?;
+                if !types::is_bool(&u.value.typ) {
+                    return self.error_loc(SemaErrorReason::InvalidTypeInUnaryExpression, u.value.loc);
+                }
+            }
+            ast::UnaryExprKind::BitwiseNot => {
+                self.expr(&mut u.value, type_hint)?;
+                if !types::is_integer(&u.value.typ) && !types::is_byte(&u.value.typ) {
+                    return self.error_loc(SemaErrorReason::InvalidTypeInUnaryExpression, u.value.loc);
+                }
+            }
+        }
+        Ok(u.value.typ.clone())
     }
 
     fn assign(&mut self, a: &mut ast::Assign, type_hint: Option<types::Type>) -> SemaResult<Type> {
         self.expr(&mut a.value, type_hint)?;
         self.store_expr(&mut a.destination)?;
-        if types::compare(&a.destination.typ, &a.value.typ) == types::ComparisonResult::Incompatible
+        // a struct stored where an interface is expected has to be cast to it
+        self.implicit_cast(&mut a.value, a.destination.typ.clone())
+            .or(self.error(SemaErrorReason::AssignmentTypesIncompatible))?;
+
+        Ok(a.value.typ.clone())
+    }
+
+    fn compound_assign(&mut self, a: &mut ast::CompoundAssign) -> SemaResult<Type> {
+        self.store_expr(&mut a.destination)?;
+        let typ = a.destination.typ.clone();
+        match a.kind {
+            ast::BinaryExprKind::Add
+            | ast::BinaryExprKind::Subtract
+            | ast::BinaryExprKind::Multiply
+            | ast::BinaryExprKind::Divide => {
+                if !types::is_numeric(&typ) {
+                    return self.error_loc(SemaErrorReason::NonNumericTypeInBinaryExpression, a.destination.loc);
+                }
+                // ints are promoted when the destination is a number
+                let hint = if types::is_number(&typ) { None } else { Some(typ.clone()) };
+                self.expr(&mut a.value, hint)?;
+                let promoted = types::is_number(&typ) && types::is_integer(&a.value.typ);
+                if types::compare(&typ, &a.value.typ) != types::ComparisonResult::Same && !promoted {
+                    return self.error(SemaErrorReason::AssignmentTypesIncompatible);
+                }
+            }
+            ast::BinaryExprKind::Modulo
+            | ast::BinaryExprKind::BitwiseAnd
+            | ast::BinaryExprKind::BitwiseOr
+            | ast::BinaryExprKind::BitwiseXor
+            | ast::BinaryExprKind::ShiftLeft
+            | ast::BinaryExprKind::ShiftRight => {
+                if !types::is_integer(&typ) && !types::is_byte(&typ) {
+                    return self.error_loc(SemaErrorReason::NonIntegerTypeInBinaryExpression, a.destination.loc);
+                }
+                self.expr(&mut a.value, Some(typ.clone()))?;
+                if types::compare(&typ, &a.value.typ) != types::ComparisonResult::Same {
+                    return self.error(SemaErrorReason::AssignmentTypesIncompatible);
+                }
+            }
+            _ => unreachable!("Parser only creates compound assignments for arithmetic operators"),
+        }
+        Ok(typ)
+    }
+
+    // Checks the arguments of a use of a generic, the ones whose parameter uses a
+    // type parameter are checked first without a hint to infer the type arguments.
+    // Returns the type arguments and which arguments have been checked.
+    fn infer_call_type_args(
+        &mut self,
+        scope: &GenericScope,
+        type_params: &Vec<String>,
+        params: &[ast::Type],
+        args: &mut [ast::Expr],
+        return_type: Option<&ast::Type>,
+        type_hint: &Option<Type>,
+    ) -> SemaResult<(Vec<Type>, Vec<bool>)> {
+        let mut bindings = TypeArgs::new();
+        let mut checked = Vec::new();
+        for (arg, param) in args.iter_mut().zip(params.iter()) {
+            let uses_type_param = generics::mentions_type_params(param, type_params);
+            if uses_type_param {
+                self.expr(arg, None)?;
+                self.types
+                    .infer_type_args(scope, type_params, param, &arg.typ, &mut bindings);
+            }
+            checked.push(uses_type_param);
+        }
+        if let (Some(return_type), Some(hint)) = (return_type, type_hint) {
+            self.types
+                .infer_type_args(scope, type_params, return_type, hint, &mut bindings);
+        }
+        match type_params
+            .iter()
+            .map(|p| bindings.get(p).cloned())
+            .collect::<Option<Vec<_>>>()
         {
-            return self.error(SemaErrorReason::AssignmentTypesIncompatible);
+            Some(type_args) => Ok((type_args, checked)),
+            None => self.error(SemaErrorReason::CannotInferTypeArguments),
         }
+    }
 
-        Ok(a.value.typ.clone())
+    // A generic function used as a value takes its type arguments from the hint
+    fn generic_function_value(
+        &mut self,
+        spec: &NameSpecification,
+        type_hint: &Option<Type>,
+    ) -> SemaResult<(Type, String)> {
+        let functions = self.functions;
+        let generic = &functions.generics[spec];
+        let signature = &generic.func.signature;
+        let mut bindings = TypeArgs::new();
+        if let Some(hint) = type_hint {
+            let func_type = ast::Type::Function {
+                params: signature.params.iter().map(|p| (*p.type_annotation).clone()).collect(),
+                return_type: signature.return_type.clone(),
+            };
+            self.types
+                .infer_type_args(&generic.scope, &signature.type_params, &func_type, hint, &mut bindings);
+        }
+        let type_args = match signature
+            .type_params
+            .iter()
+            .map(|p| bindings.get(p).cloned())
+            .collect::<Option<Vec<_>>>()
+        {
+            Some(type_args) => type_args,
+            None => return self.error(SemaErrorReason::CannotInferTypeArguments),
+        };
+        let (func_type, symbol_name) = functions.instantiate(self.types, spec, type_args)?;
+        Ok((types::function_type(func_type.params, func_type.returns), symbol_name))
+    }
+
+    fn try_expr(&mut self, t: &mut ast::Try) -> SemaResult<Type> {
+        self.expr(&mut t.value, None)?;
+        let Some((value_type, success_idx)) = types::try_value_type(&t.value.typ) else {
+            return self.error_loc(SemaErrorReason::ValueCannotBeTried, t.value.loc);
+        };
+        // the failure is returned from the enclosing function
+        match types::return_type(self.own_signature) {
+            Some(returns) if types::try_failure_returnable(&t.value.typ, &returns) => {}
+            _ => return self.error(SemaErrorReason::TryInFunctionWithIncompatibleReturnType),
+        }
+        t.success_idx = success_idx;
+        Ok(value_type)
+    }
+
+    // A function from an aliased import has no value to load before the call
+    fn qualified_call(&self, c: &mut ast::Call) {
+        if let (ast::ExprKind::Selector(_), Some(symbol_name)) = (&c.function.kind, &c.symbol_name) {
+            let symbol_name = symbol_name.clone();
+            c.function.kind = ast::ExprKind::FunctionRef(Box::new(ast::FunctionRef { symbol_name }));
+        }
     }
 
-    fn call(&mut self, c: &mut ast::Call, _type_hint: Option<types::Type>) -> SemaResult<Type> {
+    fn call(&mut self, c: &mut ast::Call, type_hint: Option<types::Type>) -> SemaResult<Type> {
         // assert special case
         if let ast::ExprKind::Identifier(i) = &c.function.kind {
             if i.id == "assert" {
@@ -712,12 +1672,162 @@ impl<'a> FuncTypeInference<'a> {
             }
         }
 
-        let function = self.expr_or_name(&mut c.function)?;
+        let function = self.expr_or_name(&mut c.function, type_hint.clone())?;
 
         match function {
             ExprResult::Package(_) => self.error(SemaErrorReason::GotPackageButExpectedExpression),
-            ExprResult::Value(_) => unimplemented!("Calling by value is not supported yet"),
-            ExprResult::Type(_) => unimplemented!("Initialisers arent implemented for now"),
+            ExprResult::Global(..) => unreachable!("Globals are values after expr_or_name"),
+            ExprResult::Value(typ) => {
+                let func_type = match typ.kind() {
+                    types::TypeKind::Function(func_type) => func_type,
+                    _ => return self.error_loc(SemaErrorReason::ValueIsNotCallable, c.function.loc),
+                };
+                if func_type.params.len() < c.parameters.len() {
+                    return self.error(SemaErrorReason::CallTooManyArguments);
+                }
+                if func_type.params.len() > c.parameters.len() {
+                    return self.error(SemaErrorReason::CallNotEnoughArguments);
+                }
+
+                for (arg, param) in c.parameters.iter_mut().zip(func_type.params.iter()) {
+                    self.expr(arg, Some(param.clone()))?;
+                    if types::compare(&param, &arg.typ) == types::ComparisonResult::Incompatible {
+                        return self.error_loc(SemaErrorReason::CallArgumentTypeMismatch, arg.loc);
+                    }
+                }
+
+                // symbol_name is left empty, which makes this an indirect call
+                if let Some(typ) = types::return_type(func_type) {
+                    Ok(typ)
+                } else {
+                    Ok(types::bad())
+                }
+            }
+            ExprResult::Type(typ) => {
+                let checked = vec![false; c.parameters.len()];
+                self.init_call(c, typ, &checked)
+            }
+            ExprResult::ArrayMethod(typ, method) => self.array_method_call(c, typ, method),
+            ExprResult::MapMethod(typ, method) => self.map_method_call(c, typ, method),
+            ExprResult::GenericType(spec) => {
+                // the type arguments come from the arguments of `init` and the hint
+                let type_collection = self.types;
+                let generic = &type_collection.generics[&spec];
+                let GenericDefinition::Struct(struct_) = &generic.definition else {
+                    return self.error_loc(SemaErrorReason::TypeCannotBeInitialised, c.function.loc);
+                };
+                let params = struct_
+                    .functions
+                    .iter()
+                    .find(|f| f.signature.id == "init")
+                    .map(|f| f.signature.params.iter().map(|p| (*p.type_annotation).clone()).collect())
+                    .unwrap_or_else(Vec::new);
+                if params.len() < c.parameters.len() {
+                    return self.error(SemaErrorReason::CallTooManyArguments);
+                }
+                if params.len() > c.parameters.len() {
+                    return self.error(SemaErrorReason::CallNotEnoughArguments);
+                }
+                let return_type = ast::Type::Generic(
+                    struct_.id.clone(),
+                    struct_.type_params.iter().map(|p| ast::Type::Identifier(p.clone())).collect(),
+                );
+                let (type_args, checked) = self.infer_call_type_args(
+                    &generic.scope,
+                    &struct_.type_params,
+                    &params,
+                    &mut c.parameters,
+                    Some(&return_type),
+                    &type_hint,
+                )?;
+                let typ = type_collection.instantiate(&spec, type_args)?;
+                self.init_call(c, typ, &checked)
+            }
+            ExprResult::GenericFunction(spec) => {
+                let functions = self.functions;
+                let generic = &functions.generics[&spec];
+                let signature = &generic.func.signature;
+                if signature.params.len() < c.parameters.len() {
+                    return self.error(SemaErrorReason::CallTooManyArguments);
+                }
+                if signature.params.len() > c.parameters.len() {
+                    return self.error(SemaErrorReason::CallNotEnoughArguments);
+                }
+
+                let params = signature
+                    .params
+                    .iter()
+                    .map(|p| (*p.type_annotation).clone())
+                    .collect::<Vec<_>>();
+                let (type_args, checked) = self.infer_call_type_args(
+                    &generic.scope,
+                    &signature.type_params,
+                    &params,
+                    &mut c.parameters,
+                    signature.return_type.as_deref(),
+                    &type_hint,
+                )?;
+                let (func_signature, symbol_name) =
+                    functions.instantiate(self.types, &spec, type_args)?;
+
+                for ((arg, param), checked) in c
+                    .parameters
+                    .iter_mut()
+                    .zip(func_signature.params.iter())
+                    .zip(checked.iter())
+                {
+                    if !checked {
+                        self.expr(arg, Some(param.clone()))?;
+                    }
+                    self.implicit_cast(arg, param.clone())
+                        .or(self.error_loc(SemaErrorReason::CallArgumentTypeMismatch, arg.loc))?;
+                }
+
+                c.symbol_name = Some(symbol_name);
+                self.qualified_call(c);
+
+                if let Some(typ) = types::return_type(&func_signature) {
+                    Ok(typ)
+                } else {
+                    Ok(types::bad())
+                }
+            }
+            ExprResult::GenericEnumVariant(spec, i) => {
+                let type_collection = self.types;
+                let generic = &type_collection.generics[&spec];
+                let GenericDefinition::Enum(enum_) = &generic.definition else {
+                    unreachable!();
+                };
+                let params = enum_.variants[i]
+                    .variant_types
+                    .iter()
+                    .map(|t| (**t).clone())
+                    .collect::<Vec<_>>();
+                let (type_args, checked) = self.infer_call_type_args(
+                    &generic.scope,
+                    &enum_.type_params,
+                    &params,
+                    &mut c.parameters,
+                    None,
+                    &type_hint,
+                )?;
+                let typ = type_collection.instantiate(&spec, type_args)?;
+                let values = match typ.kind() {
+                    types::TypeKind::Enum(enum_type) => enum_type.variants.read().unwrap()[i].1.clone(),
+                    _ => unreachable!(),
+                };
+
+                for ((arg, param), checked) in c.parameters.iter_mut().zip(values.iter()).zip(checked.iter()) {
+                    if !checked {
+                        self.expr(arg, Some(param.clone()))?;
+                    }
+                    self.implicit_cast(arg, param.clone())
+                        .or(self.error_loc(SemaErrorReason::EnumVariantValueTypesIncompatible, arg.loc))?;
+                }
+
+                c.enum_idx = Some(i);
+                Ok(typ)
+            }
             ExprResult::Function(func_signature, name_spec) => {
                 // Do some basic argument count checking
                 if func_signature.params.len() < c.parameters.len() {
@@ -736,10 +1846,11 @@ impl<'a> FuncTypeInference<'a> {
                 }
 
                 c.symbol_name = Some(mangle::mangle_name(&name_spec));
+                self.qualified_call(c);
 
                 // Assign this expr the return type of the function
-                if let Some(typ) = func_signature.returns.first() {
-                    Ok(typ.clone())
+                if let Some(typ) = types::return_type(&func_signature) {
+                    Ok(typ)
                 } else {
                     Ok(types::bad())
                 }
@@ -766,17 +1877,18 @@ impl<'a> FuncTypeInference<'a> {
                     }
                 }
 
-                c.symbol_name = if types::is_struct(&typ) {
-                    Some(mangle::mangle_method_name(&name, &typ))
-                } else {
+                // interface methods are found through the vtable
+                c.symbol_name = if types::is_interface(&typ) {
                     None
+                } else {
+                    Some(mangle::mangle_method_name(&name, &typ))
                 };
 
                 c.function.typ = typ.clone();
 
                 // Assign this expr the return type of the function
-                if let Some(typ) = func_signature.returns.first() { 
-                    Ok(typ.clone())
+                if let Some(typ) = types::return_type(&func_signature) {
+                    Ok(typ)
                 } else {
                     Ok(types::bad())
                 }
@@ -851,24 +1963,99 @@ impl<'a> FuncTypeInference<'a> {
         Ok(types::string())
     }
 
-    fn identifier(&mut self, i: &mut ast::Identifier, _type_hint: Option<types::Type>) -> SemaResult<ExprResult> {
-        if let Some(function) = self.functions.get(self.imports, self.package_id, self.file_id, &i.id) {
-            Ok(ExprResult::Function(function.0.clone(), function.1.clone()))
-        } else if let Some(binding) = self.find_var(&i.id) {
+    fn identifier(&mut self, i: &mut ast::Identifier, type_hint: Option<types::Type>) -> SemaResult<ExprResult> {
+        // local variables shadow functions, so a function value can be called by name
+        if let Some(binding) = self.find_var(&i.id) {
             Ok(ExprResult::Value(binding.typ.clone()))
-        } else if let Some(typ) = self.find_type(&i.id) {
+        } else if let Some(typ) = self.capture_var(&i.id) {
+            Ok(ExprResult::Value(typ))
+        } else if find_alias(self.imports, &i.id).is_some() {
+            Ok(ExprResult::Package(i.id.clone()))
+        } else if let Some((global, spec)) =
+            self.visible(self.functions.get_global(self.imports, self.package_id, self.file_id, &i.id))?
+        {
+            Ok(ExprResult::Global(global.typ, mangle::mangle_name(&spec)))
+        } else if let Some(function) =
+            self.visible(self.functions.get(self.imports, self.package_id, self.file_id, &i.id))?
+        {
+            Ok(ExprResult::Function(function.0.clone(), function.1.clone()))
+        } else if let Some(spec) =
+            self.visible(self.functions.get_generic(self.imports, self.package_id, self.file_id, &i.id))?
+        {
+            Ok(ExprResult::GenericFunction(spec))
+        } else if let Some(typ) = self.find_type(&i.id)? {
             Ok(ExprResult::Type(typ))
+        } else if let Some(spec) =
+            self.visible(self.types.get_generic(self.imports, self.package_id, self.file_id, &i.id))?
+        {
+            // the hint picks the instance, which is the only way to know it for `Option.None`
+            match type_hint {
+                Some(hint) if types::generic_spec(&hint).map(|(s, _)| s) == Some(&spec) => {
+                    Ok(ExprResult::Type(hint))
+                }
+                _ => Ok(ExprResult::GenericType(spec)),
+            }
+        } else {
+            self.error(SemaErrorReason::IdentifierNotFound)
+        }
+    }
+
+    fn package_global(&self, import: &ast::Import, name: &str) -> SemaResult<Option<(Global, NameSpecification)>> {
+        let globals = self.functions.globals.read().unwrap();
+        let found = find_in_import(&globals, &self.functions.exported, import, self.package_id, name);
+        Ok(self.visible(found)?.map(|(global, spec)| (global.clone(), spec)))
+    }
+
+    // Looks up `name` in the file imported as `alias`, `net.connect`
+    fn package_member(&self, alias: &str, name: &str, type_hint: Option<types::Type>) -> SemaResult<ExprResult> {
+        let import = find_alias(self.imports, alias).unwrap();
+        let (functions, types) = (self.functions, self.types);
+        if let Some((global, spec)) = self.package_global(import, name)? {
+            Ok(ExprResult::Global(global.typ, mangle::mangle_name(&spec)))
+        } else if let Some((function, spec)) = self.visible(find_in_import(
+            &functions.functions,
+            &functions.exported,
+            import,
+            self.package_id,
+            name,
+        ))? {
+            Ok(ExprResult::Function(function.clone(), spec))
+        } else if let Some((_, spec)) = self.visible(find_in_import(
+            &functions.generics,
+            &functions.exported,
+            import,
+            self.package_id,
+            name,
+        ))? {
+            Ok(ExprResult::GenericFunction(spec))
+        } else if let Some((typ, _)) =
+            self.visible(find_in_import(&types.types, &types.exported, import, self.package_id, name))?
+        {
+            Ok(ExprResult::Type(typ.clone()))
+        } else if let Some((_, spec)) =
+            self.visible(find_in_import(&types.generics, &types.exported, import, self.package_id, name))?
+        {
+            match type_hint {
+                Some(hint) if types::generic_spec(&hint).map(|(s, _)| s) == Some(&spec) => {
+                    Ok(ExprResult::Type(hint))
+                }
+                _ => Ok(ExprResult::GenericType(spec)),
+            }
         } else {
             self.error(SemaErrorReason::IdentifierNotFound)
         }
     }
 
-    fn selector(&mut self, s: &mut ast::Selector, _type_hint: Option<types::Type>) -> SemaResult<ExprResult> {
-        let value = self.expr_or_name(&mut s.value)?;
+    fn selector(&mut self, s: &mut ast::Selector, type_hint: Option<types::Type>) -> SemaResult<ExprResult> {
+        let value = self.expr_or_name(&mut s.value, type_hint.clone())?;
 
         match value {
-            ExprResult::Package(_) => unimplemented!(),
+            ExprResult::Package(alias) => self.package_member(&alias, &s.selector.id, type_hint),
+            ExprResult::Global(..) => unreachable!("Globals are values after expr_or_name"),
             ExprResult::Value(typ) => {
+                if !self.types.member_visible(&typ, &s.selector.id, self.package_id) {
+                    return self.error(SemaErrorReason::NameNotExported);
+                }
                 if let Some(a) = typ.get_method(&s.selector.id) {
                     Ok(ExprResult::Method(typ.clone(), a, s.selector.id.clone()))
                 } else if let types::TypeKind::Struct(struct_type) = typ.kind() {
@@ -885,8 +2072,27 @@ impl<'a> FuncTypeInference<'a> {
                     } else {
                         self.error(SemaErrorReason::CannotFindSelectorInStruct)
                     }
-                } else if types::is_array(&typ) && s.selector.id == "length" {
+                } else if let types::TypeKind::Tuple(element_types) = typ.kind() {
+                    // tuple elements are selected by their position
+                    match s.selector.id.parse::<usize>() {
+                        Ok(i) if i < element_types.len() => {
+                            s.idx = i;
+                            Ok(ExprResult::Value(element_types[i].clone()))
+                        }
+                        _ => self.error(SemaErrorReason::TupleIndexOutOfRange),
+                    }
+                } else if (types::is_array(&typ) || types::is_map(&typ)) && s.selector.id == "length" {
                     Ok(ExprResult::Value(types::integer()))
+                } else if let Some(method) = types::is_array(&typ)
+                    .then(|| ast::ArrayMethod::from_name(&s.selector.id))
+                    .flatten()
+                {
+                    Ok(ExprResult::ArrayMethod(typ.clone(), method))
+                } else if let Some(method) = types::is_map(&typ)
+                    .then(|| ast::MapMethod::from_name(&s.selector.id))
+                    .flatten()
+                {
+                    Ok(ExprResult::MapMethod(typ.clone(), method))
                 } else {
                     self.error(SemaErrorReason::InvalidUsageOfSelector)
                 }
@@ -914,15 +2120,99 @@ impl<'a> FuncTypeInference<'a> {
                     self.error(SemaErrorReason::InvalidUsageOfSelector)
                 }
             },
-            ExprResult::Function(_, _) => self.error(SemaErrorReason::CannotUseFunctionAsSelector),
-            ExprResult::Method(_, _, _) => self.error(SemaErrorReason::CannotUseMethodAsSelector),
-            ExprResult::EnumVariant(_, _) => self.error(SemaErrorReason::CannotUseEnumVariantAsSelector),
+            ExprResult::GenericType(spec) => {
+                let type_collection = self.types;
+                if let GenericDefinition::Enum(enum_) = &type_collection.generics[&spec].definition {
+                    match enum_.variants.iter().position(|v| v.id == s.selector.id) {
+                        Some(i) if !enum_.variants[i].variant_types.is_empty() => {
+                            Ok(ExprResult::GenericEnumVariant(spec, i))
+                        }
+                        Some(_) => self.error(SemaErrorReason::CannotInferTypeArguments),
+                        None => self.error(SemaErrorReason::CannotFindVariantInEnum),
+                    }
+                } else {
+                    self.error(SemaErrorReason::InvalidUsageOfSelector)
+                }
+            }
+            ExprResult::Function(_, _) | ExprResult::GenericFunction(_) => {
+                self.error(SemaErrorReason::CannotUseFunctionAsSelector)
+            }
+            ExprResult::Method(_, _, _) | ExprResult::ArrayMethod(_, _) | ExprResult::MapMethod(_, _) => {
+                self.error(SemaErrorReason::CannotUseMethodAsSelector)
+            }
+            ExprResult::EnumVariant(_, _) | ExprResult::GenericEnumVariant(_, _) => {
+                self.error(SemaErrorReason::CannotUseEnumVariantAsSelector)
+            }
+        }
+    }
+
+    fn array_method_call(&mut self, c: &mut ast::Call, typ: Type, method: ast::ArrayMethod) -> SemaResult<Type> {
+        let element_type = types::get_inner_array_type(&typ);
+        let (takes_index, takes_value) = match method {
+            ast::ArrayMethod::Push => (false, true),
+            ast::ArrayMethod::Pop | ast::ArrayMethod::Clear => (false, false),
+            ast::ArrayMethod::Insert => (true, true),
+            ast::ArrayMethod::Remove => (true, false),
+        };
+        let param_count = takes_index as usize + takes_value as usize;
+        if param_count < c.parameters.len() {
+            return self.error(SemaErrorReason::CallTooManyArguments);
+        }
+        if param_count > c.parameters.len() {
+            return self.error(SemaErrorReason::CallNotEnoughArguments);
+        }
+
+        let mut args = c.parameters.iter_mut();
+        if takes_index {
+            let index = args.next().unwrap();
+            self.expr(index, None)?;
+            if !types::is_integer(&index.typ) {
+                return self.error_loc(SemaErrorReason::ValueCannotBeUsedAsIndex, index.loc);
+            }
+        }
+        if takes_value {
+            let value = args.next().unwrap();
+            self.expr(value, Some(element_type.clone()))?;
+            if types::compare(&element_type, &value.typ) == types::ComparisonResult::Incompatible {
+                return self.error_loc(SemaErrorReason::CallArgumentTypeMismatch, value.loc);
+            }
+        }
+
+        c.function.typ = typ;
+        c.array_method = Some(method);
+
+        match method {
+            ast::ArrayMethod::Pop | ast::ArrayMethod::Remove => Ok(element_type),
+            _ => Ok(types::bad()),
         }
     }
 
+    fn map_method_call(&mut self, c: &mut ast::Call, typ: Type, method: ast::MapMethod) -> SemaResult<Type> {
+        let (key_type, _) = types::get_map_types(&typ);
+        if c.parameters.len() > 1 {
+            return self.error(SemaErrorReason::CallTooManyArguments);
+        }
+        let Some(key) = c.parameters.first_mut() else {
+            return self.error(SemaErrorReason::CallNotEnoughArguments);
+        };
+        self.expr(key, Some(key_type.clone()))?;
+        if types::compare(&key_type, &key.typ) != types::ComparisonResult::Same {
+            return self.error_loc(SemaErrorReason::CallArgumentTypeMismatch, key.loc);
+        }
+
+        c.function.typ = typ;
+        c.map_method = Some(method);
+        // both say whether the key was in the map
+        Ok(types::bool())
+    }
+
     fn subscript(&mut self, s: &mut ast::Subscript, _type_hint: Option<types::Type>) -> SemaResult<Type> {
         self.expr(&mut s.value, None)?;
 
+        if types::is_map(&s.value.typ) {
+            return self.map_subscript(s);
+        }
+
         if !types::is_array(&s.value.typ) {
             return self.error_loc(SemaErrorReason::ValueIsNotIndexable, s.value.loc);
         }
@@ -954,6 +2244,77 @@ impl<'a> FuncTypeInference<'a> {
         }
     }
 
+    // Checks the key of `m[key]`, the value type is the type of the subscript
+    fn map_subscript(&mut self, s: &mut ast::Subscript) -> SemaResult<Type> {
+        if s.is_slice {
+            return self.error_loc(SemaErrorReason::CannotSliceMap, s.value.loc);
+        }
+        let (key_type, value_type) = types::get_map_types(&s.value.typ);
+        let Some(key) = s.index.as_mut() else {
+            return self.error(SemaErrorReason::ExpectedAnIndex);
+        };
+        self.expr(key, Some(key_type.clone()))?;
+        if types::compare(&key_type, &key.typ) != types::ComparisonResult::Same {
+            return self.error_loc(SemaErrorReason::ValueCannotBeUsedAsIndex, key.loc);
+        }
+        Ok(value_type)
+    }
+
+    fn tuple_literal(
+        &mut self,
+        t: &mut ast::TupleLiteral,
+        type_hint: Option<types::Type>,
+    ) -> SemaResult<Type> {
+        let hints = type_hint
+            .filter(types::is_tuple)
+            .map(|t| types::get_tuple_types(&t))
+            .filter(|hints| hints.len() == t.values.len());
+        let mut element_types = Vec::new();
+        for (i, value) in t.values.iter_mut().enumerate() {
+            self.expr(value, hints.as_ref().map(|hints| hints[i].clone()))?;
+            element_types.push(value.typ.clone());
+        }
+        Ok(types::tuple(element_types))
+    }
+
+    fn map_literal(
+        &mut self,
+        l: &mut ast::MapLiteral,
+        type_hint: Option<types::Type>,
+    ) -> SemaResult<Type> {
+        let hint = type_hint.filter(types::is_map).map(|t| types::get_map_types(&t));
+        if l.entries.is_empty() {
+            // an empty map literal needs the hint to know its types
+            return match hint {
+                Some((key_type, value_type)) => Ok(types::map(key_type, value_type)),
+                None => Ok(types::map(types::bad(), types::bad())),
+            };
+        }
+
+        // The first entry determines the types of the map, unless there is a hint
+        let (key_hint, value_hint) = hint.unzip();
+        let (first_key, first_value) = &mut l.entries[0];
+        self.expr(first_key, key_hint)?;
+        self.expr(first_value, value_hint)?;
+        let key_type = first_key.typ.clone();
+        let value_type = first_value.typ.clone();
+        if !types::is_map_key(&key_type) {
+            return self.error_loc(SemaErrorReason::InvalidMapKeyType, first_key.loc);
+        }
+
+        for (key, value) in l.entries.iter_mut().skip(1) {
+            self.expr(key, Some(key_type.clone()))?;
+            self.expr(value, Some(value_type.clone()))?;
+            if types::compare(&key_type, &key.typ) != types::ComparisonResult::Same
+                || types::compare(&value_type, &value.typ) == types::ComparisonResult::Incompatible
+            {
+                return self.error(SemaErrorReason::MapLiteralEntryTypesIncompatible);
+            }
+        }
+
+        Ok(types::map(key_type, value_type))
+    }
+
     fn array_literal(
         &mut self,
         l: &mut ast::ArrayLiteral,
@@ -997,23 +2358,102 @@ impl<'a> FuncTypeInference<'a> {
         }
     }
 
-    fn object_literal(
+    // The type arguments of a generic struct literal come from the hint, or
+    // from the values of the fields using a type parameter
+    fn generic_object_literal(
         &mut self,
         l: &mut ast::ObjectLiteral,
-        _type_hint: Option<types::Type>,
+        spec: NameSpecification,
+        type_hint: Option<types::Type>,
+        checked: &mut Vec<bool>,
     ) -> SemaResult<Type> {
-        let id = match &l.id {
-            Some(id) => id,
-            None => unimplemented!("Anonymous object literals not supported yet"),
+        if let Some(hint) = type_hint {
+            if types::generic_spec(&hint).map(|(s, _)| s) == Some(&spec) {
+                return Ok(hint);
+            }
+        }
+        let type_collection = self.types;
+        let generic = &type_collection.generics[&spec];
+        let GenericDefinition::Struct(struct_) = &generic.definition else {
+            return self.error(SemaErrorReason::TypeNotFound);
         };
-
-        // maybe there need to be a module look up mapping to know which structs we want
-        // does the struct exist?
-        let struct_def = match self.find_type(&id.id) {
-            Some(s) => s.clone(),
-            None => return self.error(SemaErrorReason::TypeNotFound),
+        let params = l
+            .fields
+            .iter()
+            .map(|field| {
+                struct_
+                    .fields
+                    .iter()
+                    .find(|f| f.id == field.id)
+                    .map(|f| (*f.type_annotation).clone())
+                    .ok_or(field.loc)
+            })
+            .collect::<Result<Vec<_>, _>>();
+        let params = match params {
+            Ok(params) => params,
+            Err(loc) => return self.error_loc(SemaErrorReason::StructFieldNotFound, loc),
         };
-
+        let mut values = l.fields.iter().map(|f| f.value.clone()).collect::<Vec<_>>();
+        let (type_args, was_checked) = self.infer_call_type_args(
+            &generic.scope,
+            &struct_.type_params,
+            &params,
+            &mut values,
+            None,
+            &None,
+        )?;
+        for ((field, value), was_checked) in l.fields.iter_mut().zip(values).zip(was_checked.iter()) {
+            if *was_checked {
+                field.value = value;
+            }
+        }
+        *checked = was_checked;
+        type_collection.instantiate(&spec, type_args)
+    }
+
+    fn object_literal(
+        &mut self,
+        l: &mut ast::ObjectLiteral,
+        type_hint: Option<types::Type>,
+    ) -> SemaResult<Type> {
+        let id = match &l.id {
+            Some(id) => id.id.clone(),
+            None => unimplemented!("Anonymous object literals not supported yet"),
+        };
+
+        // fields used to infer the type arguments of a generic struct are already checked
+        let mut checked = vec![false; l.fields.len()];
+
+        // maybe there need to be a module look up mapping to know which structs we want
+        // does the struct exist?
+        let struct_def = match l.alias.clone() {
+            // `alias.Type { ... }` looks the type up in the imported file
+            Some(alias) => {
+                let Some(import) = find_alias(self.imports, &alias) else {
+                    return self.error(SemaErrorReason::TypeNotFound);
+                };
+                let type_collection = self.types;
+                let found = find_in_import(&type_collection.types, &type_collection.exported, import, self.package_id, &id);
+                match self.visible(found)? {
+                    Some((typ, _)) => typ.clone(),
+                    None => {
+                        let found = find_in_import(&type_collection.generics, &type_collection.exported, import, self.package_id, &id);
+                        match self.visible(found)? {
+                            Some((_, spec)) => self.generic_object_literal(l, spec, type_hint, &mut checked)?,
+                            None => return self.error(SemaErrorReason::TypeNotFound),
+                        }
+                    }
+                }
+            }
+            None => match self.find_type(&id)? {
+                Some(s) => s.clone(),
+                None => match self.visible(self.types.get_generic(self.imports, self.package_id, self.file_id, &id))? {
+                    Some(spec) => self.generic_object_literal(l, spec, type_hint, &mut checked)?,
+                    None => return self.error(SemaErrorReason::TypeNotFound),
+                },
+            },
+        };
+
         // check the type is actually a struct
         if !types::is_struct(&struct_def) {
             return self.error(SemaErrorReason::TypeNotFound);
@@ -1024,32 +2464,259 @@ impl<'a> FuncTypeInference<'a> {
         // once we find the struct, we need to check what fields we are setting and if they exist
         // run the expr sema for the fields
         // then we need to type check them
-        for field in l.fields.iter_mut() {
+        for (field, checked) in l.fields.iter_mut().zip(checked) {
             let struct_field = match struct_fields.iter().find(|f| f.0 == field.id) {
                 Some(f) => f,
                 None => return self.error_loc(SemaErrorReason::StructFieldNotFound, field.loc),
             };
+            if !self.types.member_visible(&struct_def, &field.id, self.package_id) {
+                return self.error_loc(SemaErrorReason::NameNotExported, field.loc);
+            }
             // run sema on the field value
-            self.expr(&mut field.value, Some(struct_field.1.clone()))?;
+            if !checked {
+                self.expr(&mut field.value, Some(struct_field.1.clone()))?;
+            }
             // check the type matches
             if types::compare(&struct_field.1, &field.value.typ)
                 == types::ComparisonResult::Incompatible
             {
                 return self.error_loc(SemaErrorReason::AssignmentTypesIncompatible, field.loc);
             }
+            self.implicit_cast(&mut field.value, struct_field.1.clone())?;
         }
+        self.default_fields(l, &struct_def, false)?;
 
         Ok(struct_def)
     }
 
+    // Adds the fields an object literal leaves out with their default values,
+    // fields without one start out empty. References have no empty value so
+    // they have to be given, unless the object is made for `init` which has
+    // to set them itself
+    fn default_fields(
+        &mut self,
+        l: &mut ast::ObjectLiteral,
+        struct_def: &Type,
+        for_init: bool,
+    ) -> SemaResult<()> {
+        let defaults = self.types.get_defaults(struct_def);
+        for (id, field_type) in clone_struct_fields(struct_def) {
+            if l.fields.iter().any(|f| f.id == id) {
+                continue;
+            }
+            match defaults.iter().find(|d| d.field == id) {
+                Some(default) => {
+                    let value = check_default(default, &field_type, self.types, self.functions)?;
+                    l.fields.push(ast::ObjectLiteralField {
+                        loc: self.loc,
+                        id,
+                        value,
+                    });
+                }
+                None if !for_init && !has_zero_value(&field_type) => {
+                    return self.error(SemaErrorReason::StructFieldNotInitialised(id));
+                }
+                None => {}
+            }
+        }
+        self.ok()
+    }
+
+    // `Foo(...)` makes a Foo from its field defaults and then calls its `init`
+    // method with the arguments, without an `init` it takes none. `checked` are
+    // the arguments already checked while inferring the type arguments.
+    fn init_call(&mut self, c: &mut ast::Call, typ: Type, checked: &[bool]) -> SemaResult<Type> {
+        if !types::is_struct(&typ) {
+            return self.error_loc(SemaErrorReason::TypeCannotBeInitialised, c.function.loc);
+        }
+        let init = typ.get_method("init");
+        let params = init.as_ref().map(|i| i.params.clone()).unwrap_or_default();
+        if params.len() < c.parameters.len() {
+            return self.error(SemaErrorReason::CallTooManyArguments);
+        }
+        if params.len() > c.parameters.len() {
+            return self.error(SemaErrorReason::CallNotEnoughArguments);
+        }
+
+        for ((arg, param), checked) in c.parameters.iter_mut().zip(params.iter()).zip(checked.iter()) {
+            if *checked {
+                // checked without a hint, so it may still need a cast
+                self.implicit_cast(arg, param.clone())
+                    .or(self.error_loc(SemaErrorReason::CallArgumentTypeMismatch, arg.loc))?;
+                continue;
+            }
+            self.expr(arg, Some(param.clone()))?;
+            if types::compare(param, &arg.typ) == types::ComparisonResult::Incompatible {
+                return self.error_loc(SemaErrorReason::CallArgumentTypeMismatch, arg.loc);
+            }
+        }
+
+        let mut object = ast::ObjectLiteral {
+            alias: None,
+            id: None,
+            fields: Vec::new(),
+        };
+        self.default_fields(&mut object, &typ, init.is_some())?;
+        c.init = Some(Box::new(ast::Expr {
+            kind: ast::ExprKind::ObjectLiteral(Box::new(object)),
+            typ: typ.clone(),
+            loc: c.function.loc,
+        }));
+        c.symbol_name = init.map(|_| mangle::mangle_method_name("init", &typ));
+        Ok(typ)
+    }
+
     fn _self(&mut self, e: &mut ast::Expr) -> SemaResult<Type> {
         let self_type = match &self.self_type {
             Some(t) => t.clone(),
             None => return self.error_loc(SemaErrorReason::CannotUseSelfOutsideOfMethod, e.loc),
         };
+        // inside a closure self is captured like any other variable
+        self.capture_var("");
         Ok(self_type)
     }
 
+    fn closure(&mut self, c: &mut ast::Closure) -> SemaResult<Type> {
+        let params = c
+            .params
+            .iter()
+            .map(|p| type_lookup(&p.type_annotation, self.types, self.package_id, self.file_id, self.imports))
+            .collect::<SemaResult<Vec<_>>>()?;
+        let returns = c
+            .return_type
+            .iter()
+            .map(|r| type_lookup(r, self.types, self.package_id, self.file_id, self.imports))
+            .collect::<SemaResult<Vec<_>>>()?;
+        let returns = types::flatten_returns(returns);
+        c.typ_ = types::FunctionType { params, returns };
+
+        // everything visible here can be captured, inner scopes shadow outer ones
+        let mut captured = self.captured.clone();
+        for scope in self.variable_scopes.iter() {
+            for (name, binding) in scope.iter() {
+                captured.insert(name.clone(), binding.clone());
+            }
+        }
+        if let Some(self_type) = &self.self_type {
+            captured.insert(
+                "".into(),
+                VariableBinding {
+                    typ: self_type.clone(),
+                    is_const: true,
+                    captured: false,
+                    loop_depth: 0,
+                    assigned_in: HashSet::new(),
+                },
+            );
+        }
+
+        let captures = {
+            let mut inference = FuncTypeInference {
+                imports: self.imports,
+                types: self.types,
+                self_type: self.self_type.clone(),
+                functions: self.functions,
+                own_signature: &c.typ_,
+                package_id: self.package_id,
+                file_id: self.file_id,
+                variable_scopes: Vec::new(),
+                loops: Vec::new(),
+                loop_count: 0,
+                captured,
+                captures: Vec::new(),
+                loc: c.loc,
+            };
+            inference.push_scope();
+            for (p, typ) in c.params.iter().zip(c.typ_.params.iter()) {
+                inference.create_var(p.id.clone(), typ, false);
+            }
+            inference.block_stmt(&mut c.body)?;
+            inference.pop_scope();
+            inference.captures
+        };
+
+        // a nested closure can use variables this closure has to capture itself
+        for (name, _) in captures.iter() {
+            if self.find_var(name).is_none() {
+                self.capture_var(name);
+                continue;
+            }
+            let loops: Vec<usize> = self.loops.iter().map(|(_, id)| *id).collect();
+            let binding = self.find_var_mut(name).unwrap();
+            binding.captured = true;
+            // an assignment earlier in a loop around the closure runs again
+            // after the closure has copied the variable
+            if loops[binding.loop_depth..].iter().any(|id| binding.assigned_in.contains(id)) {
+                return self.error_loc(SemaErrorReason::CannotAssignToCapturedVariable, c.loc);
+            }
+        }
+        c.captures = captures;
+
+        Ok(types::function_type(c.typ_.params.clone(), c.typ_.returns.clone()))
+    }
+
+    fn cast(&mut self, c: &mut ast::Cast) -> SemaResult<Type> {
+        // implicit casts are created after their value is checked
+        let Some(annotation) = &c.annotation else {
+            return Ok(c.target_type.clone());
+        };
+        let target = type_lookup(
+            annotation,
+            self.types,
+            self.package_id,
+            self.file_id,
+            self.imports,
+        )?;
+        self.expr(&mut c.value, None)?;
+
+        if c.optional {
+            if !types::is_interface(&c.value.typ) {
+                return self.error(SemaErrorReason::ExpressionCannotBeCasted);
+            }
+            self.satisfies_interface(&c.value.typ, &target)?;
+            // `as?` gives an Option, the std one is used so it works with `?`
+            let option = NameSpecification {
+                package: "std".into(),
+                file: "result".into(),
+                name: "Option".into(),
+            };
+            if !self.types.generics.contains_key(&option) {
+                return self.error(SemaErrorReason::OptionalCastRequiresStdResult);
+            }
+            c.target_type = self.types.instantiate(&option, vec![target])?;
+            return Ok(c.target_type.clone());
+        }
+
+        let value = &c.value.typ;
+        let is_scalar = |t: &Type| types::is_numeric(t) || types::is_bool(t);
+        if is_scalar(value) && is_scalar(&target) {
+            if c.wrapping
+                && !(types::is_byte(&target) && (types::is_integer(value) || types::is_number(value)))
+            {
+                return self.error(SemaErrorReason::WrappingCastMustNarrowToByte);
+            }
+        } else if c.wrapping
+            || types::compare(&target, value) == types::ComparisonResult::Incompatible
+        {
+            // anything else has to be an upcast
+            return self.error(SemaErrorReason::ExpressionCannotBeCasted);
+        }
+
+        c.target_type = target.clone();
+        Ok(target)
+    }
+
+    // Only concrete types are stored in interface values, so only they can be
+    // downcast to
+    fn satisfies_interface(&self, interface: &Type, typ: &Type) -> SemaResult<()> {
+        if types::is_interface(typ)
+            || types::compare(interface, typ) != types::ComparisonResult::Upcastable
+        {
+            return self.error(SemaErrorReason::TypeCannotSatisfyInterface);
+        }
+        self.ok()
+    }
+
     fn template(&mut self, t: &mut ast::Template, _type_hint: Option<types::Type>) -> SemaResult<Type> {
         // Check all substitutions are a string-compatible value:
         // string, integer, number, boolean, or an implementor of the String interface.
@@ -1093,6 +2760,9 @@ impl<'a> FuncTypeInference<'a> {
                     parameters: Vec::new(),
                     symbol_name: None,
                     enum_idx: None,
+                    init: None,
+                    array_method: None,
+                    map_method: None,
                 })),
                 typ: types::bad(), // will be filled in by the call sema
                 loc: expr.loc,
@@ -1108,10 +2778,18 @@ impl<'a> FuncTypeInference<'a> {
         let old_loc = self.loc;
         self.loc = e.loc;
 
+        // set when a named function is used as a value
+        let mut function_ref = None;
+        // set when a method is used as a value
+        let mut method_ref = None;
+        // set when an identifier refers to a global
+        let mut global_ref = None;
+
         let checked_e = match &mut e.kind {
             ast::ExprKind::BinaryExpr(b) => self.binary_expr(b, type_hint.clone()),
             ast::ExprKind::UnaryExpr(u) => self.unary_expr(u, type_hint.clone()),
             ast::ExprKind::Assign(a) => self.assign(a, type_hint.clone()),
+            ast::ExprKind::CompoundAssign(a) => self.compound_assign(a),
             ast::ExprKind::Call(c) => self.call(c, type_hint.clone()),
             ast::ExprKind::Integer(i) => self.integer(i, type_hint.clone()),
             ast::ExprKind::Number(f) => self.number(f, type_hint.clone()),
@@ -1120,29 +2798,88 @@ impl<'a> FuncTypeInference<'a> {
             ast::ExprKind::Identifier(i) => {
                 match self.identifier(i, type_hint.clone()) {
                     Ok(ExprResult::Value(typ)) => Ok(typ),
-                    Ok(ExprResult::Type(_)) => self.error(SemaErrorReason::GotTypeButExpectedExpression),
+                    Ok(ExprResult::Global(typ, symbol_name)) => {
+                        global_ref = Some(symbol_name);
+                        Ok(typ)
+                    }
+                    Ok(ExprResult::Function(func_type, name_spec)) => {
+                        function_ref = Some(mangle::mangle_name(&name_spec));
+                        Ok(types::function_type(func_type.params, func_type.returns))
+                    }
+                    Ok(ExprResult::GenericFunction(spec)) => {
+                        self.generic_function_value(&spec, &type_hint).map(|(typ, symbol_name)| {
+                            function_ref = Some(symbol_name);
+                            typ
+                        })
+                    }
+                    Ok(ExprResult::Type(_)) | Ok(ExprResult::GenericType(_)) => {
+                        self.error(SemaErrorReason::GotTypeButExpectedExpression)
+                    }
                     Ok(ExprResult::Package(_)) => self.error(SemaErrorReason::GotPackageButExpectedExpression),
+                    Ok(result) => self.not_a_value(result),
                     Err(e) => Err(e),
-                    _ => unimplemented!("Calling functions by value or using types as values is not supported yet"),
                 }
             },
             ast::ExprKind::Selector(s) => {
                 match self.selector(s, type_hint.clone()) {
                     Ok(ExprResult::Value(typ)) => Ok(typ),
-                    Ok(ExprResult::Type(_)) => self.error(SemaErrorReason::GotTypeButExpectedExpression),
+                    Ok(ExprResult::Global(typ, symbol_name)) => {
+                        global_ref = Some(symbol_name);
+                        Ok(typ)
+                    }
+                    Ok(ExprResult::Function(func_type, name_spec)) => {
+                        function_ref = Some(mangle::mangle_name(&name_spec));
+                        Ok(types::function_type(func_type.params, func_type.returns))
+                    }
+                    Ok(ExprResult::GenericFunction(spec)) => {
+                        self.generic_function_value(&spec, &type_hint).map(|(typ, symbol_name)| {
+                            function_ref = Some(symbol_name);
+                            typ
+                        })
+                    }
+                    Ok(ExprResult::Type(_)) | Ok(ExprResult::GenericType(_)) => {
+                        self.error(SemaErrorReason::GotTypeButExpectedExpression)
+                    }
+                    Ok(ExprResult::Method(typ, func_type, name)) => {
+                        method_ref = Some(mangle::mangle_method_name(&name, &typ));
+                        Ok(types::function_type(func_type.params, func_type.returns))
+                    }
                     Ok(ExprResult::Package(_)) => self.error(SemaErrorReason::GotPackageButExpectedExpression),
+                    Ok(result) => self.not_a_value(result),
                     Err(e) => Err(e),
-                    _ => unimplemented!("Calling functions by value or using types as values is not supported yet"),
                 }
             },
             ast::ExprKind::Subscript(s) => self.subscript(s, type_hint.clone()),
             ast::ExprKind::ArrayLiteral(a) => self.array_literal(a, type_hint.clone()),
+            ast::ExprKind::TupleLiteral(t) => self.tuple_literal(t, type_hint.clone()),
+            ast::ExprKind::MapLiteral(m) => self.map_literal(m, type_hint.clone()),
             ast::ExprKind::ObjectLiteral(o) => self.object_literal(o, type_hint.clone()),
             ast::ExprKind::_Self => self._self(e),
             ast::ExprKind::Template(t) => self.template(t, type_hint.clone()),
-            ast::ExprKind::Cast(_) => unimplemented!("Cast expressions not implemented yet"),
+            ast::ExprKind::Try(t) => self.try_expr(t),
+            ast::ExprKind::Cast(c) => self.cast(c),
+            ast::ExprKind::FunctionRef(_) | ast::ExprKind::MethodRef(_) | ast::ExprKind::GlobalRef(_) => {
+                Ok(e.typ.clone())
+            }
+            ast::ExprKind::Closure(c) => self.closure(c),
+            ast::ExprKind::If(i) => self.if_expr(i, type_hint.clone()),
+            ast::ExprKind::Switch(s) => self.switch_expr(s, type_hint.clone()),
         };
 
+        if let Some(symbol_name) = function_ref {
+            e.kind = ast::ExprKind::FunctionRef(Box::new(ast::FunctionRef { symbol_name }));
+        }
+        if let (Some(symbol_name), ast::ExprKind::Selector(s)) = (method_ref, &e.kind) {
+            e.kind = ast::ExprKind::MethodRef(Box::new(ast::MethodRef {
+                value: s.value.clone(),
+                method: s.selector.id.clone(),
+                symbol_name,
+            }));
+        }
+        if let Some(symbol_name) = global_ref {
+            e.kind = ast::ExprKind::GlobalRef(Box::new(ast::GlobalRef { symbol_name }));
+        }
+
         self.loc = old_loc;
 
         match &checked_e {
@@ -1154,35 +2891,68 @@ impl<'a> FuncTypeInference<'a> {
 
         // Wraps implicit casts
         if let Some(expected) = type_hint {
-            match types::compare(&expected, &e.typ) {
-                types::ComparisonResult::Incompatible => {
-                    return self.error_loc(SemaErrorReason::ExpressionCannotBeCasted, e.loc);
-                }
-                types::ComparisonResult::Upcastable => {
-                    // wrap the expression in a cast expression
-                    let original_expr = e.clone();
-                    e.kind = ast::ExprKind::Cast(Box::new(ast::Cast {
-                        value: original_expr,
-                        target_type: expected.clone(),
-                    }));
-                    e.typ = expected;
-                }
-                _ => {}
-            }
+            self.implicit_cast(e, expected)
+                .or(self.error_loc(SemaErrorReason::ExpressionCannotBeCasted, e.loc))?;
         }
 
         checked_e
     }
 
-    fn expr_or_name(&mut self, e: &mut ast::Expr) -> SemaResult<ExprResult> {
+    // The results of a name which cannot be used as values, builtin methods
+    // and enum variants holding values are only called
+    fn not_a_value(&self, result: ExprResult) -> SemaResult<Type> {
+        match result {
+            ExprResult::EnumVariant(..) | ExprResult::GenericEnumVariant(..) => {
+                self.error(SemaErrorReason::EnumVariantCannotBeUsedAsValue)
+            }
+            ExprResult::ArrayMethod(..) | ExprResult::MapMethod(..) => {
+                self.error(SemaErrorReason::BuiltinMethodCannotBeUsedAsValue)
+            }
+            _ => self.error(SemaErrorReason::GotTypeButExpectedExpression),
+        }
+    }
+
+    // Wraps a checked expression in a cast when it has to be upcasted to the
+    // expected type, errors when it cant be
+    fn implicit_cast(&mut self, e: &mut ast::Expr, expected: Type) -> SemaResult<()> {
+        match types::compare(&expected, &e.typ) {
+            types::ComparisonResult::Incompatible => {
+                return self.error_loc(SemaErrorReason::ExpressionCannotBeCasted, e.loc);
+            }
+            types::ComparisonResult::Upcastable => {
+                // wrap the expression in a cast expression
+                let original_expr = e.clone();
+                e.kind = ast::ExprKind::Cast(Box::new(ast::Cast {
+                    value: original_expr,
+                    target_type: expected.clone(),
+                    annotation: None,
+                    wrapping: false,
+                    optional: false,
+                }));
+                e.typ = expected;
+            }
+            _ => {}
+        }
+        self.ok()
+    }
+
+    // The hint is only used to pick the instance when the name is a generic type
+    fn expr_or_name(&mut self, e: &mut ast::Expr, type_hint: Option<Type>) -> SemaResult<ExprResult> {
         let old_loc = self.loc;
         self.loc = e.loc;
         
         let r = match &mut e.kind {
-            ast::ExprKind::Identifier(i) => self.identifier(i, None),
-            ast::ExprKind::Selector(s) => self.selector(s, None),
+            ast::ExprKind::Identifier(i) => self.identifier(i, type_hint),
+            ast::ExprKind::Selector(s) => self.selector(s, type_hint),
             _ => Ok(ExprResult::Value(self.expr(e, None)?.clone())),
         };
+        let r = match r {
+            Ok(ExprResult::Global(typ, symbol_name)) => {
+                e.kind = ast::ExprKind::GlobalRef(Box::new(ast::GlobalRef { symbol_name }));
+                Ok(ExprResult::Value(typ))
+            }
+            r => r,
+        };
 
         match &r {
             Ok(ExprResult::Value(typ)) => {
@@ -1214,6 +2984,10 @@ impl<'a> FuncTypeInference<'a> {
         }
 
         self.expr(&mut s.value, None)?;
+        if types::is_map(&s.value.typ) {
+            e.typ = self.map_subscript(s)?;
+            return self.ok();
+        }
         if let Some(index) = &mut s.index {
             self.expr(index, None)?;
         }
@@ -1239,12 +3013,18 @@ impl<'a> FuncTypeInference<'a> {
             _ => panic!(),
         };
 
-        self.expr(&mut s.value, None)?;
+        if let ExprResult::Package(alias) = self.expr_or_name(&mut s.value, None)? {
+            return self.store_package_global(e, &alias);
+        }
 
         if !types::is_struct(&s.value.typ) {
             return self.error_loc(SemaErrorReason::InvalidUsageOfSelector, e.loc);
         }
 
+        if !self.types.member_visible(&s.value.typ, &s.selector.id, self.package_id) {
+            return self.error_loc(SemaErrorReason::NameNotExported, e.loc);
+        }
+
         if let types::TypeKind::Struct(struct_type) = s.value.typ.kind() {
             if let Some((i, (_, ty))) = struct_type
                 .fields
@@ -1269,17 +3049,54 @@ impl<'a> FuncTypeInference<'a> {
             ast::ExprKind::Identifier(i) => i,
             _ => panic!(),
         };
-        if let Some(binding) = self.find_var(&i.id) {
+        let loops: Vec<usize> = self.loops.iter().map(|(_, id)| *id).collect();
+        if let Some(binding) = self.find_var_mut(&i.id) {
             if binding.is_const {
                 return self.error_loc(SemaErrorReason::CannotAssignToConst, e.loc);
             }
+            if binding.captured {
+                // a closure already holds a copy of the old value
+                return self.error_loc(SemaErrorReason::CannotAssignToCapturedVariable, e.loc);
+            }
+            binding.assigned_in.extend(loops[binding.loop_depth..].iter().copied());
             e.typ = binding.typ.clone();
             self.ok()
+        } else if self.captured.contains_key(&i.id) {
+            // captures are copies, writing to one would not change the original
+            self.error_loc(SemaErrorReason::CannotAssignToCapturedVariable, e.loc)
+        } else if let Some((global, spec)) =
+            self.visible(self.functions.get_global(self.imports, self.package_id, self.file_id, &i.id))?
+        {
+            if global.is_const {
+                return self.error_loc(SemaErrorReason::CannotAssignToConst, e.loc);
+            }
+            e.typ = global.typ;
+            let symbol_name = mangle::mangle_name(&spec);
+            e.kind = ast::ExprKind::GlobalRef(Box::new(ast::GlobalRef { symbol_name }));
+            self.ok()
         } else {
             self.error_loc(SemaErrorReason::IdentifierNotFound, e.loc)
         }
     }
 
+    // `config.count = 1` stores into a global of the file imported as `config`
+    fn store_package_global(&mut self, e: &mut ast::Expr, alias: &str) -> SemaResult<()> {
+        let ast::ExprKind::Selector(s) = &e.kind else {
+            unreachable!();
+        };
+        let import = find_alias(self.imports, alias).unwrap();
+        let Some((global, spec)) = self.package_global(import, &s.selector.id)? else {
+            return self.error_loc(SemaErrorReason::IdentifierNotFound, e.loc);
+        };
+        if global.is_const {
+            return self.error_loc(SemaErrorReason::CannotAssignToConst, e.loc);
+        }
+        e.typ = global.typ;
+        let symbol_name = mangle::mangle_name(&spec);
+        e.kind = ast::ExprKind::GlobalRef(Box::new(ast::GlobalRef { symbol_name }));
+        self.ok()
+    }
+
     // These are expressions which are going to be used to "store" a value
     // aka l values
     fn store_expr(&mut self, e: &mut ast::Expr) -> SemaResult<()> {
@@ -1287,6 +3104,8 @@ impl<'a> FuncTypeInference<'a> {
             ast::ExprKind::Subscript(_) => self.store_subscript(e),
             ast::ExprKind::Selector(_) => self.store_selector(e),
             ast::ExprKind::Identifier(_) => self.store_identifier(e),
+            // already checked as a destination
+            ast::ExprKind::GlobalRef(_) => self.ok(),
             _ => self.error_loc(
                 SemaErrorReason::CannotUseExpressionInLeftHandExpression,
                 e.loc,
@@ -1310,15 +3129,137 @@ impl<'a> FuncTypeInference<'a> {
         }
     }
 
-    fn for_stmt(&mut self, _f: &mut Box<ast::ForStmt>) -> SemaResult<()> {
-        unimplemented!()
+    // A type can be iterated when its method set satisfies the std
+    // `Iterator<T>` interface, gives T and the index of the `Some` variant
+    fn iterator_element_type(&self, typ: &Type) -> SemaResult<Option<(Type, usize)>> {
+        let Some(next) = typ.get_method("next") else {
+            return Ok(None);
+        };
+        let Some(returns) = types::return_type(&next) else {
+            return Ok(None);
+        };
+        let types::TypeKind::Enum(option) = returns.kind() else {
+            return Ok(None);
+        };
+        let Some(element_type) = option.type_args.first() else {
+            return Ok(None);
+        };
+        let iterator = NameSpecification {
+            package: "std".into(),
+            file: "iter".into(),
+            name: "Iterator".into(),
+        };
+        if !self.types.generics.contains_key(&iterator) {
+            return self.error(SemaErrorReason::IteratorRequiresStdIter);
+        }
+        let iterator = self.types.instantiate(&iterator, vec![element_type.clone()])?;
+        if types::compare(&iterator, typ) == types::ComparisonResult::Incompatible {
+            return Ok(None);
+        }
+        let some_idx = option
+            .variants
+            .read()
+            .unwrap()
+            .iter()
+            .position(|(id, _)| id == "Some")
+            .unwrap();
+        Ok(Some((element_type.clone(), some_idx)))
     }
 
-    fn if_stmt(&mut self, f: &mut Box<ast::IfStmt>) -> SemaResult<()> {
+    fn for_stmt(&mut self, f: &mut Box<ast::ForStmt>) -> SemaResult<()> {
+        self.expr(&mut f.iterator, None)?;
+        self.push_scope();
+        let element_type = if types::is_array(&f.iterator.typ) {
+            types::get_inner_array_type(&f.iterator.typ)
+        } else if types::is_map(&f.iterator.typ) {
+            types::get_map_types(&f.iterator.typ).0
+        } else if let Some((element_type, some_idx)) = self.iterator_element_type(&f.iterator.typ)? {
+            // Build the call to next() on the hidden iterator variable,
+            // so emit can treat it like any other method call.
+            self.create_var(ast::FOR_ITERATOR_ID.into(), &f.iterator.typ, true);
+            let mut next_call = ast::Expr {
+                kind: ast::ExprKind::Call(Box::new(ast::Call {
+                    function: ast::Expr {
+                        kind: ast::ExprKind::Selector(Box::new(ast::Selector {
+                            value: ast::Expr {
+                                kind: ast::ExprKind::Identifier(Box::new(ast::Identifier {
+                                    id: ast::FOR_ITERATOR_ID.into(),
+                                })),
+                                typ: types::bad(), // will be filled in by the identifier sema
+                                loc: f.iterator.loc,
+                            },
+                            selector: ast::Identifier { id: "next".into() },
+                            idx: 0,
+                            enum_idx: None,
+                        })),
+                        typ: types::bad(), // will be filled in by the selector sema
+                        loc: f.iterator.loc,
+                    },
+                    parameters: Vec::new(),
+                    symbol_name: None,
+                    enum_idx: None,
+                    init: None,
+                    array_method: None,
+                    map_method: None,
+                })),
+                typ: types::bad(), // will be filled in by the call sema
+                loc: f.iterator.loc,
+            };
+            self.expr(&mut next_call, None)?;
+            f.next_call = Some(next_call);
+            f.some_idx = some_idx;
+            element_type
+        } else {
+            return self.error_loc(SemaErrorReason::ValueIsNotIterable, f.iterator.loc);
+        };
+        self.push_scope();
+        self.create_var(f.id.clone(), &element_type, true);
+        self.loop_count += 1;
+        self.loops.push((f.label.clone(), self.loop_count));
+        self.stmt(&mut f.consequent)?;
+        self.loops.pop();
+        self.pop_scope();
+        self.pop_scope();
+        self.ok()
+    }
+
+    fn loop_exists(&self, label: &Option<String>) -> bool {
+        match label {
+            Some(label) => self.loops.iter().any(|(l, _)| l.as_ref() == Some(label)),
+            None => !self.loops.is_empty(),
+        }
+    }
+
+    fn break_stmt(&mut self, b: &mut Box<ast::BreakStmt>) -> SemaResult<()> {
+        if self.loops.is_empty() {
+            return self.error_loc(SemaErrorReason::BreakOutsideOfLoop, b.loc);
+        }
+        if !self.loop_exists(&b.label) {
+            return self.error_loc(SemaErrorReason::LoopLabelNotFound, b.loc);
+        }
+        self.ok()
+    }
+
+    fn continue_stmt(&mut self, c: &mut Box<ast::ContinueStmt>) -> SemaResult<()> {
+        if self.loops.is_empty() {
+            return self.error_loc(SemaErrorReason::ContinueOutsideOfLoop, c.loc);
+        }
+        if !self.loop_exists(&c.label) {
+            return self.error_loc(SemaErrorReason::LoopLabelNotFound, c.loc);
+        }
+        self.ok()
+    }
+
+    fn if_test(&mut self, f: &mut ast::IfStmt) -> SemaResult<()> {
         self.expr(&mut f.test, Some(types::bool()))?;
         if types::compare(&f.test.typ, &types::bool()) != types::ComparisonResult::Same {
             return self.error_loc(SemaErrorReason::ExpectedBooleanInTestCondition, f.test.loc);
         }
+        self.ok()
+    }
+
+    fn if_stmt(&mut self, f: &mut Box<ast::IfStmt>) -> SemaResult<()> {
+        self.if_test(f)?;
         self.stmt(&mut f.consequent)?;
         if let Some(a) = &mut f.alternate {
             self.stmt(a)?;
@@ -1326,8 +3267,150 @@ impl<'a> FuncTypeInference<'a> {
         self.ok()
     }
 
+    // The value of a branch is the expression statement its block ends with,
+    // None when the branch leaves through return, break or continue instead
+    fn branch(&mut self, s: &mut ast::Stmt, type_hint: Option<Type>) -> SemaResult<Option<Type>> {
+        match s {
+            ast::Stmt::Block(b) => self.branch_block(b, type_hint),
+            // `else if` gives the value of the nested if
+            ast::Stmt::If(i) => self.if_branches(i, type_hint),
+            _ => self.error(SemaErrorReason::BranchHasNoValue),
+        }
+    }
+
+    fn branch_block(&mut self, b: &mut ast::BlockStmt, type_hint: Option<Type>) -> SemaResult<Option<Type>> {
+        let Some((last, stmts)) = b.stmts.split_last_mut() else {
+            return self.error_loc(SemaErrorReason::BranchHasNoValue, b.loc);
+        };
+        self.push_scope();
+        for s in stmts.iter_mut() {
+            self.stmt(s)?;
+        }
+        let value = match last {
+            ast::Stmt::ExprStmt(e) => {
+                // the type after any implicit cast to the hint
+                self.expr(&mut e.expr, type_hint)?;
+                Some(e.expr.typ.clone())
+            }
+            ast::Stmt::Return(_) | ast::Stmt::Break(_) | ast::Stmt::Continue(_) => {
+                self.stmt(last)?;
+                None
+            }
+            // a nested if or switch gives the value of its own branches
+            ast::Stmt::If(i) => self.if_branches(i, type_hint)?,
+            ast::Stmt::Switch(s) => self.switch_branches(s, type_hint)?,
+            _ => return self.error_loc(SemaErrorReason::BranchHasNoValue, b.loc),
+        };
+        self.pop_scope();
+        Ok(value)
+    }
+
+    // Upcasts the value of a branch to the type of the whole expression
+    fn cast_branch(&mut self, s: &mut ast::Stmt, typ: &Type) -> SemaResult<()> {
+        match s {
+            ast::Stmt::Block(b) => self.cast_branch_block(b, typ)?,
+            ast::Stmt::If(i) => {
+                self.cast_branch(&mut i.consequent, typ)?;
+                if let Some(alternate) = &mut i.alternate {
+                    self.cast_branch(alternate, typ)?;
+                }
+            }
+            ast::Stmt::Switch(s) => {
+                for case in s.cases.iter_mut() {
+                    self.cast_branch_block(&mut case.block, typ)?;
+                }
+            }
+            _ => {}
+        }
+        self.ok()
+    }
+
+    fn cast_branch_block(&mut self, b: &mut ast::BlockStmt, typ: &Type) -> SemaResult<()> {
+        match b.stmts.last_mut() {
+            Some(ast::Stmt::ExprStmt(e)) => self.implicit_cast(&mut e.expr, typ.clone()),
+            Some(last @ (ast::Stmt::If(_) | ast::Stmt::Switch(_))) => self.cast_branch(last, typ),
+            _ => self.ok(),
+        }
+    }
+
+    // The type every branch can be converted to, either they are the same or
+    // all of them can be upcasted to the interface one of them has
+    fn unify_branches(&self, branches: &[Option<Type>], type_hint: Option<Type>) -> SemaResult<Option<Type>> {
+        let mut unified: Option<Type> = None;
+        for typ in branches.iter().flatten() {
+            unified = match unified {
+                None => Some(typ.clone()),
+                Some(u) => match types::compare(&u, typ) {
+                    types::ComparisonResult::Incompatible => {
+                        if types::compare(typ, &u) != types::ComparisonResult::Upcastable {
+                            return self.error(SemaErrorReason::IncompatibleTypesInBranches);
+                        }
+                        Some(typ.clone())
+                    }
+                    _ => Some(u),
+                },
+            };
+        }
+        // the branches were checked against the hint, so they all fit it
+        match unified {
+            Some(_) if type_hint.is_some() => Ok(type_hint),
+            _ => Ok(unified),
+        }
+    }
+
+    fn if_branches(&mut self, f: &mut ast::IfStmt, type_hint: Option<Type>) -> SemaResult<Option<Type>> {
+        self.if_test(f)?;
+        let consequent = self.branch(&mut f.consequent, type_hint.clone())?;
+        let Some(alternate) = &mut f.alternate else {
+            return self.error_loc(SemaErrorReason::IfExpressionWithoutElse, f.loc);
+        };
+        let alternate = self.branch(alternate, type_hint.clone())?;
+        self.unify_branches(&[consequent, alternate], type_hint)
+    }
+
+    fn if_expr(&mut self, f: &mut ast::IfStmt, type_hint: Option<Type>) -> SemaResult<Type> {
+        let Some(typ) = self.if_branches(f, type_hint)? else {
+            return self.error_loc(SemaErrorReason::BranchHasNoValue, f.loc);
+        };
+        self.cast_branch(&mut f.consequent, &typ)?;
+        if let Some(alternate) = &mut f.alternate {
+            self.cast_branch(alternate, &typ)?;
+        }
+        Ok(typ)
+    }
+
+    fn switch_expr(&mut self, s: &mut ast::SwitchStmt, type_hint: Option<Type>) -> SemaResult<Type> {
+        let Some(typ) = self.switch_branches(s, type_hint)? else {
+            return self.error_loc(SemaErrorReason::BranchHasNoValue, s.loc);
+        };
+        for case in s.cases.iter_mut() {
+            self.cast_branch_block(&mut case.block, &typ)?;
+        }
+        Ok(typ)
+    }
+
+    // The type of the value of every case, None when all of them leave
+    // through return, break or continue
+    fn switch_branches(&mut self, s: &mut ast::SwitchStmt, type_hint: Option<Type>) -> SemaResult<Option<Type>> {
+        let branches = self.switch(s, Some(type_hint.clone()))?;
+        let typ = self.unify_branches(&branches, type_hint)?;
+        // without a value to give the switch has to match everything
+        let wildcard = ast::Pattern {
+            loc: s.loc,
+            kind: ast::PatternKind::CatchAll,
+            typ: s.value.typ.clone(),
+        };
+        let matches_all = s.cases.iter().any(|c| {
+            c.guard.is_none() && patterns::is_irrefutable(&c.pattern, &s.value.typ, &wildcard)
+        });
+        if !types::is_enum(&s.value.typ) && !matches_all {
+            return self.error_loc(SemaErrorReason::SwitchIsNotExhaustive(vec!["_".into()]), s.loc);
+        }
+        Ok(typ)
+    }
+
     fn return_stmt(&mut self, r: &mut Box<ast::ReturnStmt>) -> SemaResult<()> {
-        if let Some(return_type) = self.own_signature.returns.first().cloned() {
+        if let Some(return_type) = types::return_type(self.own_signature) {
             if let Some(r) = &mut r.value {
                 self.expr(r, Some(return_type.clone()))?;
                 if types::compare(&return_type, &r.typ) == types::ComparisonResult::Incompatible {
@@ -1345,6 +3428,25 @@ impl<'a> FuncTypeInference<'a> {
     }
 
     fn var_decl_stmt(&mut self, v: &mut Box<ast::VarDeclStmt>) -> SemaResult<()> {
+        let typ = self.var_decl_type(v)?;
+        if let Some(ids) = &v.destructure {
+            let types::TypeKind::Tuple(element_types) = typ.kind() else {
+                return self.error_loc(SemaErrorReason::CannotDestructureNonTuple, v.loc);
+            };
+            if element_types.len() != ids.len() {
+                return self.error_loc(SemaErrorReason::DestructureCountMismatch, v.loc);
+            }
+            for (id, element_type) in ids.iter().zip(element_types.iter()) {
+                self.create_var(id.clone(), element_type, v.is_const);
+            }
+            return self.ok();
+        }
+        self.create_var(v.id.clone(), &typ, v.is_const);
+        self.ok()
+    }
+
+    // Checks the value of a variable declaration against its annotation
+    fn var_decl_type(&mut self, v: &mut Box<ast::VarDeclStmt>) -> SemaResult<Type> {
         if let Some(annotation) = &v.type_annotation {
             let annotation = type_lookup(
                 &annotation,
@@ -1361,93 +3463,253 @@ impl<'a> FuncTypeInference<'a> {
                     v.loc,
                 );
             }
-            self.create_var(v.id.clone(), ret, v.is_const);
+            Ok(ret.clone())
         } else {
             self.expr(&mut v.value, None)?;
-            let ret = &v.value.typ;
             //v.type_annotation = Some(ret.clone());
-            self.create_var(v.id.clone(), ret, v.is_const);
+            Ok(v.value.typ.clone())
         }
-        self.ok()
     }
 
     fn while_stmt(&mut self, w: &mut Box<ast::WhileStmt>) -> SemaResult<()> {
         self.expr(&mut w.condition, Some(types::bool()))?;
+        self.loop_count += 1;
+        self.loops.push((w.label.clone(), self.loop_count));
         self.stmt(&mut w.consequent)?;
+        self.loops.pop();
         self.ok()
     }
 
-    fn switch_stmt(&mut self, s: &mut ast::SwitchStmt) -> SemaResult<()> {
-        self.expr(&mut s.value, None)?;
-        if types::is_enum(&s.value.typ) {
-            let enum_type = match &s.value.typ.kind() {
-                types::TypeKind::Enum(enum_type) => enum_type,
-                _ => unreachable!(),
-            };
-            for case in s.cases.iter_mut() {
-                match &mut case.pattern.kind {
-                    ast::PatternKind::CatchAll => {
-                        self.block_stmt(&mut case.block)?;
-                    }
-                    ast::PatternKind::EnumVariant { id, values } => {
-                        match enum_type
-                            .variants
-                            .read()
-                            .unwrap()
-                            .iter()
-                            .enumerate()
-                            .find(|v| v.1.0 == *id)
-                        {
-                            Some(v) => {
-                                if values.len() != v.1.1.len() {
-                                    return self.error_loc(
-                                        SemaErrorReason::EnumVariantPatternFieldCountMismatch,
-                                        case.pattern.loc,
-                                    );
-                                }
-                                self.push_scope();
-                                for (typ, (name, val_typ)) in v.1.1.iter().zip(values.iter_mut()) {
-                                    self.create_var(name.clone(), typ, true);
-                                    *val_typ = typ.clone();
-                                }
-                                self.block_stmt(&mut case.block)?;
-                                self.pop_scope();
-                                case.case_idx = v.0 as i64;
-                            }
-                            None => {
-                                return self.error_loc(
-                                    SemaErrorReason::EnumVariantNotFound,
-                                    case.pattern.loc,
-                                );
-                            }
-                        };
-                    }
-                    _ => {
+    fn bind_pattern_var(
+        &self,
+        bindings: &mut Vec<(String, Type)>,
+        name: &str,
+        typ: &Type,
+        loc: SourceLoc,
+    ) -> SemaResult<()> {
+        if bindings.iter().any(|(n, _)| n == name) {
+            return self.error_loc(SemaErrorReason::DuplicatePatternBinding, loc);
+        }
+        bindings.push((name.into(), typ.clone()));
+        self.ok()
+    }
+
+    // Checks a pattern against the type of the value it matches, collecting the
+    // variables it binds
+    fn pattern(
+        &mut self,
+        p: &mut ast::Pattern,
+        typ: &Type,
+        bindings: &mut Vec<(String, Type)>,
+    ) -> SemaResult<()> {
+        let loc = p.loc;
+        p.typ = typ.clone();
+        match &mut p.kind {
+            ast::PatternKind::CatchAll => {}
+            ast::PatternKind::Binding(name) => {
+                self.bind_pattern_var(bindings, name, typ, loc)?;
+            }
+            ast::PatternKind::Integer(_) | ast::PatternKind::IntegerRange(_, _) => {
+                if !types::is_integer(typ) {
+                    return self.error_loc(SemaErrorReason::InvalidPatternKind, loc);
+                }
+            }
+            ast::PatternKind::String(_) => {
+                if !types::is_string(typ) {
+                    return self.error_loc(SemaErrorReason::InvalidPatternKind, loc);
+                }
+            }
+            ast::PatternKind::EnumVariant { id, values, idx } => {
+                let types::TypeKind::Enum(enum_type) = typ.kind() else {
+                    return self.error_loc(SemaErrorReason::InvalidPatternKind, loc);
+                };
+                let variants = enum_type.variants.read().unwrap().clone();
+                let Some(i) = variants.iter().position(|(v, _)| v == id) else {
+                    return self.error_loc(SemaErrorReason::EnumVariantNotFound, loc);
+                };
+                let variant_types = &variants[i].1;
+                if values.len() != variant_types.len() {
+                    return self
+                        .error_loc(SemaErrorReason::EnumVariantPatternFieldCountMismatch, loc);
+                }
+                *idx = i;
+                for (value, value_type) in values.iter_mut().zip(variant_types.iter()) {
+                    self.pattern(value, value_type, bindings)?;
+                }
+            }
+            ast::PatternKind::Type {
+                annotation,
+                binding,
+                typ: case_type,
+            } => {
+                if !types::is_interface(typ) {
+                    return self.error_loc(SemaErrorReason::InvalidPatternKind, loc);
+                }
+                *case_type = type_lookup(
+                    annotation,
+                    self.types,
+                    self.package_id,
+                    self.file_id,
+                    self.imports,
+                )?;
+                self.loc = loc;
+                self.satisfies_interface(typ, case_type)?;
+                if let Some(binding) = binding {
+                    self.bind_pattern_var(bindings, binding, case_type, loc)?;
+                }
+            }
+            ast::PatternKind::Struct { annotation, fields } => {
+                let struct_type = type_lookup(
+                    annotation,
+                    self.types,
+                    self.package_id,
+                    self.file_id,
+                    self.imports,
+                )?;
+                if types::compare(&struct_type, typ) != types::ComparisonResult::Same {
+                    return self.error_loc(SemaErrorReason::PatternTypeMismatch, loc);
+                }
+                let types::TypeKind::Struct(struct_type) = typ.kind() else {
+                    return self.error_loc(SemaErrorReason::InvalidPatternKind, loc);
+                };
+                let struct_fields = struct_type.fields.read().unwrap().clone();
+                for field in fields.iter_mut() {
+                    let Some(i) = struct_fields.iter().position(|(id, _)| *id == field.id) else {
                         return self
-                            .error_loc(SemaErrorReason::InvalidPatternKind, case.pattern.loc);
+                            .error_loc(SemaErrorReason::StructFieldNotFound, field.pattern.loc);
+                    };
+                    if !self.types.member_visible(typ, &field.id, self.package_id) {
+                        return self.error_loc(SemaErrorReason::NameNotExported, field.pattern.loc);
                     }
+                    field.idx = i;
+                    self.pattern(&mut field.pattern, &struct_fields[i].1, bindings)?;
                 }
             }
-        } else if types::is_integer(&s.value.typ) {
-            for case in s.cases.iter_mut() {
-                match &case.pattern.kind {
-                    ast::PatternKind::CatchAll => {
-                        self.block_stmt(&mut case.block)?;
-                    }
-                    ast::PatternKind::Integer(_) => {
-                        self.block_stmt(&mut case.block)?;
-                    }
-                    ast::PatternKind::IntegerRange(_, _) => {
-                        self.block_stmt(&mut case.block)?;
-                    }
-                    _ => {
-                        return self
-                            .error_loc(SemaErrorReason::InvalidPatternKind, case.pattern.loc);
+            ast::PatternKind::Or(alternatives) => {
+                // every alternative has to bind the same variables, whichever
+                // one matches the case sees them all
+                let mut alternative_bindings: Option<Vec<(String, Type)>> = None;
+                for alternative in alternatives.iter_mut() {
+                    let mut these = Vec::new();
+                    self.pattern(alternative, typ, &mut these)?;
+                    these.sort_by(|a, b| a.0.cmp(&b.0));
+                    if let Some(first) = &alternative_bindings {
+                        let same = first.len() == these.len()
+                            && first.iter().zip(these.iter()).all(|(a, b)| {
+                                a.0 == b.0
+                                    && types::compare(&a.1, &b.1) == types::ComparisonResult::Same
+                            });
+                        if !same {
+                            return self.error_loc(
+                                SemaErrorReason::OrPatternBindingsDiffer,
+                                alternative.loc,
+                            );
+                        }
+                    } else {
+                        alternative_bindings = Some(these);
                     }
                 }
+                for (name, binding_type) in alternative_bindings.unwrap_or_default() {
+                    self.bind_pattern_var(bindings, &name, &binding_type, loc)?;
+                }
+            }
+        }
+        self.ok()
+    }
+
+    fn switch_stmt(&mut self, s: &mut ast::SwitchStmt) -> SemaResult<()> {
+        self.switch(s, None).map(|_| ())
+    }
+
+    // When the switch is an expression `value_hint` is set, and the type of
+    // the value each case gives is returned
+    fn switch(&mut self, s: &mut ast::SwitchStmt, value_hint: Option<Option<Type>>) -> SemaResult<Vec<Option<Type>>> {
+        let mut branches = Vec::new();
+        self.expr(&mut s.value, None)?;
+        let typ = s.value.typ.clone();
+        for case in s.cases.iter_mut() {
+            let mut bindings = Vec::new();
+            self.pattern(&mut case.pattern, &typ, &mut bindings)?;
+            self.push_scope();
+            for (name, binding_type) in bindings.iter() {
+                self.create_var(name.clone(), binding_type, true);
+            }
+            if let Some(guard) = &mut case.guard {
+                self.expr(guard, Some(types::bool()))?;
+                if types::compare(&guard.typ, &types::bool()) != types::ComparisonResult::Same {
+                    return self
+                        .error_loc(SemaErrorReason::ExpectedBooleanInTestCondition, guard.loc);
+                }
+            }
+            match &value_hint {
+                Some(type_hint) => branches.push(self.branch_block(&mut case.block, type_hint.clone())?),
+                None => self.block_stmt(&mut case.block)?,
+            }
+            self.pop_scope();
+        }
+        self.check_cases(s)?;
+        Ok(branches)
+    }
+
+    // Reports cases which can never match, and enum variants which no case
+    // does. Cases with a guard might not match so they do not cover anything.
+    fn check_cases(&self, s: &ast::SwitchStmt) -> SemaResult<()> {
+        let typ = &s.value.typ;
+        let wildcard = ast::Pattern {
+            loc: SourceLoc::default(),
+            kind: ast::PatternKind::CatchAll,
+            typ: typ.clone(),
+        };
+
+        let mut rows: Vec<Vec<&ast::Pattern>> = Vec::new();
+        for (i, case) in s.cases.iter().enumerate() {
+            let row = vec![case.pattern.as_ref()];
+            if !patterns::is_useful(&rows, &row, std::slice::from_ref(typ), &wildcard) {
+                // repeating an earlier case is most likely a mistake
+                let repeated = s.cases[..i]
+                    .iter()
+                    .any(|c| c.guard.is_none() && c.pattern.kind == case.pattern.kind);
+                if repeated {
+                    return self.error_loc(SemaErrorReason::DuplicateSwitchCase, case.pattern.loc);
+                }
+                self.warn(SemaWarningReason::UnreachableSwitchCase, case.pattern.loc);
+            }
+            if case.guard.is_none() {
+                rows.push(row);
+            }
+        }
+
+        if types::is_integer(typ) {
+            let mut seen: Vec<(i64, i64, bool)> = Vec::new();
+            for case in s.cases.iter() {
+                let keys = integer_keys(&case.pattern);
+                let overlaps = keys.iter().any(|&(lo, hi, is_range)| {
+                    seen.iter()
+                        .any(|&(l, h, r)| (r || is_range) && lo <= h && hi >= l)
+                });
+                if overlaps {
+                    self.warn(
+                        SemaWarningReason::OverlappingIntegerPatterns,
+                        case.pattern.loc,
+                    );
+                }
+                if case.guard.is_none() {
+                    seen.extend(keys);
+                }
+            }
+        }
+
+        if types::is_enum(typ) {
+            let covering = s
+                .cases
+                .iter()
+                .filter(|c| c.guard.is_none())
+                .map(|c| c.pattern.as_ref())
+                .collect::<Vec<_>>();
+            let missing = patterns::missing_variants(&covering, typ, &wildcard);
+            if !missing.is_empty() {
+                return self.error_loc(SemaErrorReason::SwitchIsNotExhaustive(missing), s.loc);
             }
-        } else {
-            unimplemented!();
         }
         self.ok()
     }
@@ -1457,6 +3719,8 @@ impl<'a> FuncTypeInference<'a> {
             ast::Stmt::Block(b) => self.block_stmt(b),
             ast::Stmt::ExprStmt(e) => self.expr_stmt(e),
             ast::Stmt::For(f) => self.for_stmt(f),
+            ast::Stmt::Break(b) => self.break_stmt(b),
+            ast::Stmt::Continue(c) => self.continue_stmt(c),
             ast::Stmt::If(i) => self.if_stmt(i),
             ast::Stmt::Return(r) => self.return_stmt(r),
             ast::Stmt::VarDecl(v) => self.var_decl_stmt(v),
@@ -1483,13 +3747,23 @@ impl<'a> FuncTypeInference<'a> {
     }
 }
 
+// The integers a pattern matches at the top level, with whether they came from a range
+fn integer_keys(p: &ast::Pattern) -> Vec<(i64, i64, bool)> {
+    match &p.kind {
+        ast::PatternKind::Integer(i) => vec![(*i, *i, false)],
+        ast::PatternKind::IntegerRange(lo, hi) => vec![(*lo, *hi, true)],
+        ast::PatternKind::Or(alternatives) => alternatives.iter().flat_map(integer_keys).collect(),
+        _ => Vec::new(),
+    }
+}
+
 fn check_file(
     file: &mut Box<ast::File>,
     package_id: &str,
     collection: &TypeCollection,
     functions: &FunctionCollection,
 ) -> SemaResult<()> {
-    for func in file.functions.iter_mut() {
+    for func in file.functions.iter_mut().filter(|f| f.signature.type_params.is_empty()) {
         let own_signature = functions
             .get_exact(package_id, &file.id, &func.signature.id)
             .unwrap();
@@ -1511,32 +3785,152 @@ fn check_file(
         });
     }
 
-    for _struct in file.structs.iter_mut() {
+    for _struct in file.structs.iter_mut().filter(|s| s.type_params.is_empty()) {
         let typ = collection
             .get_exact(package_id, &file.id, &_struct.id)
             .unwrap()
             .clone();
         _struct.typ = typ.clone();
+        for default in collection.get_defaults(&typ) {
+            let field_type = clone_struct_fields(&typ)
+                .into_iter()
+                .find(|f| f.0 == default.field)
+                .unwrap()
+                .1;
+            check_default(&default, &field_type, collection, functions)?;
+        }
         for func in _struct.functions.iter_mut() {
-            let own_signature = typ.get_method(&func.signature.id).unwrap();
-            func.typ_ = own_signature.clone();
-            func.signature.symbol_name = mangle::mangle_method_name(&func.signature.id, &typ);
-            FuncTypeInference::new_for_method(
-                &file.imports,
-                collection,
-                &own_signature,
-                functions,
-                package_id,
-                &file.id,
-                typ.clone(),
-            )
-            .check(func)?;
+            check_method(func, &typ, &file.imports, package_id, &file.id, collection, functions)?;
+        }
+    }
+
+    for enum_ in file.enums.iter_mut().filter(|e| e.type_params.is_empty()) {
+        let typ = collection
+            .get_exact(package_id, &file.id, &enum_.id)
+            .unwrap()
+            .clone();
+        enum_.typ = typ.clone();
+        for func in enum_.functions.iter_mut() {
+            check_method(func, &typ, &file.imports, package_id, &file.id, collection, functions)?;
+        }
+    }
+
+    for extension in file.extensions.iter_mut() {
+        let typ = type_lookup(
+            &extension.annotation,
+            collection,
+            package_id,
+            &file.id,
+            &file.imports,
+        )?;
+        extension.typ = typ.clone();
+        for func in extension.functions.iter_mut() {
+            check_method(func, &typ, &file.imports, package_id, &file.id, collection, functions)?;
         }
     }
 
     Ok(())
 }
 
+fn check_method(
+    func: &mut ast::Func,
+    typ: &Type,
+    imports: &Vec<ast::Import>,
+    package_id: &str,
+    file_id: &str,
+    collection: &TypeCollection,
+    functions: &FunctionCollection,
+) -> SemaResult<()> {
+    let own_signature = typ.get_method(&func.signature.id).unwrap();
+    func.typ_ = own_signature.clone();
+    func.signature.symbol_name = mangle::mangle_method_name(&func.signature.id, typ);
+    FuncTypeInference::new_for_method(
+        imports,
+        collection,
+        &own_signature,
+        functions,
+        package_id,
+        file_id,
+        typ.clone(),
+    )
+    .check(func)?;
+
+    check_init(func, typ, collection, package_id, file_id)
+}
+
+// `init` gets an object with only the defaults set, so it has to set the
+// references itself on every path before anything can use them
+fn check_init(
+    func: &ast::Func,
+    typ: &Type,
+    collection: &TypeCollection,
+    package_id: &str,
+    file_id: &str,
+) -> SemaResult<()> {
+    if func.signature.id != "init" || !types::is_struct(typ) {
+        return Ok(());
+    }
+    let defaults = collection.get_defaults(typ);
+    let fields = clone_struct_fields(typ);
+    let required: Vec<String> = fields
+        .iter()
+        .filter(|(id, field_type)| !has_zero_value(field_type) && !defaults.iter().any(|d| &d.field == id))
+        .map(|(id, _)| id.clone())
+        .collect();
+    let fields: Vec<String> = fields.into_iter().map(|(id, _)| id).collect();
+    if let Err((violation, loc)) = init::check(&func.body, &fields, &required) {
+        let (reason, loc) = match violation {
+            init::Violation::Return(id) => (SemaErrorReason::StructFieldNotInitialised(id), loc),
+            init::Violation::Missing(id) => (SemaErrorReason::StructFieldNotInitialised(id), func.loc),
+            init::Violation::Use(id) => (SemaErrorReason::SelfUsedBeforeFieldsInitialised(id), loc),
+        };
+        return Err(SemaError {
+            reason,
+            loc,
+            file: file_id.into(),
+            package: package_id.into(),
+        });
+    }
+    Ok(())
+}
+
+// Fields of these types start out empty when nothing else is given
+fn has_zero_value(typ: &Type) -> bool {
+    matches!(
+        typ.kind(),
+        types::TypeKind::Integer
+            | types::TypeKind::Byte
+            | types::TypeKind::Number
+            | types::TypeKind::Bool
+            | types::TypeKind::String
+            | types::TypeKind::Array(_)
+            | types::TypeKind::Map(..)
+    )
+}
+
+// Checks a copy of a field default in the scope of its struct, each object
+// literal gets its own
+fn check_default(
+    default: &FieldDefault,
+    field_type: &Type,
+    collection: &TypeCollection,
+    functions: &FunctionCollection,
+) -> SemaResult<ast::Expr> {
+    let own_signature = types::FunctionType::default();
+    let scope = &default.scope;
+    let mut value = default.value.clone();
+    FuncTypeInference::new(
+        &scope.imports,
+        collection,
+        &own_signature,
+        functions,
+        &scope.package,
+        &scope.file,
+    )
+    .expr(&mut value, Some(field_type.clone()))?;
+    Ok(value)
+}
+
 fn check_package(
     package: &mut ast::Package,
     collection: &TypeCollection,
@@ -1548,14 +3942,191 @@ fn check_package(
     Ok(())
 }
 
-pub fn check_program(program: &mut ast::Program, builtins: &Builtins) -> SemaResult<()> {
+// Files are initialised after the files they import, so the std package goes
+// before anything using it
+fn init_order(program: &ast::Program) -> Vec<(String, String)> {
+    fn visit(
+        program: &ast::Program,
+        key: (String, String),
+        visited: &mut HashSet<(String, String)>,
+        order: &mut Vec<(String, String)>,
+    ) {
+        // marked before the imports so import cycles end here
+        if !visited.insert(key.clone()) {
+            return;
+        }
+        let file = program
+            .packages
+            .iter()
+            .find(|p| p.id == key.0)
+            .and_then(|p| p.files.iter().find(|f| f.id == key.1));
+        for import in file.iter().flat_map(|f| f.imports.iter()) {
+            visit(program, (import.package.clone(), import.file.clone()), visited, order);
+        }
+        if file.is_some() {
+            order.push(key);
+        }
+    }
+
+    let mut visited = HashSet::new();
+    let mut order = Vec::new();
+    for package in program.packages.iter() {
+        for file in package.files.iter() {
+            visit(program, (package.id.clone(), file.id.clone()), &mut visited, &mut order);
+        }
+    }
+    order
+}
+
+// Globals are checked in the order they are initialised, each one can use the
+// globals before it
+fn check_globals(
+    program: &mut ast::Program,
+    collection: &TypeCollection,
+    functions: &FunctionCollection,
+) -> SemaResult<()> {
+    let own_signature = types::FunctionType::default();
+    for (package_id, file_id) in program.init_order.clone() {
+        let package = program.packages.iter_mut().find(|p| p.id == package_id).unwrap();
+        let file = package.files.iter_mut().find(|f| f.id == file_id).unwrap();
+        for global in file.globals.iter_mut() {
+            let name_spec = NameSpecification {
+                package: package.id.clone(),
+                file: file.id.clone(),
+                name: global.id.clone(),
+            };
+            let mut inference = FuncTypeInference::new(
+                &file.imports,
+                collection,
+                &own_signature,
+                functions,
+                &package.id,
+                &file.id,
+            );
+            if functions.functions.contains_key(&name_spec)
+                || functions.generics.contains_key(&name_spec)
+                || functions.globals.read().unwrap().contains_key(&name_spec)
+            {
+                return inference.error_loc(SemaErrorReason::GlobalAlreadyDefined, global.loc);
+            }
+            if global.destructure.is_some() {
+                return inference.error_loc(SemaErrorReason::GlobalCannotBeDestructured, global.loc);
+            }
+            let typ = inference.var_decl_type(global)?;
+            functions.globals.write().unwrap().insert(
+                name_spec,
+                Global {
+                    typ,
+                    is_const: global.is_const,
+                },
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Checks the program, returning the warnings found when it has no errors
+pub fn check_program(program: &mut ast::Program, builtins: &Builtins) -> SemaResult<Vec<SemaWarning>> {
     let collection = collect_types(program);
     check_types(program, &collection)?;
 
     let function_collection = collect_functions(program, builtins, &collection)?;
 
+    program.init_order = init_order(program);
+    check_globals(program, &collection, &function_collection)?;
+
     for file in program.packages.iter_mut() {
         check_package(file, &collection, &function_collection)?;
     }
-    Ok(())
+
+    // instances can use more generics, so keep going until none are left
+    loop {
+        let Some(instance) = collection.pending.write().unwrap().pop() else {
+            break;
+        };
+        let PendingInstance {
+            scope,
+            mut func,
+            self_type,
+            depth,
+        } = instance;
+        *collection.instance_depth.write().unwrap() = depth;
+        let own_signature = func.typ_.clone();
+        match &self_type {
+            Some(self_type) => {
+                FuncTypeInference::new_for_method(
+                    &scope.imports,
+                    &collection,
+                    &own_signature,
+                    &function_collection,
+                    &scope.package,
+                    &scope.file,
+                    self_type.clone(),
+                )
+                .check(&mut func)?;
+                check_init(&func, self_type, &collection, &scope.package, &scope.file)?;
+            }
+            None => FuncTypeInference::new(
+                &scope.imports,
+                &collection,
+                &own_signature,
+                &function_collection,
+                &scope.package,
+                &scope.file,
+            )
+            .check(&mut func)?,
+        }
+        program.instances.push(ast::Instance {
+            file: scope.file,
+            func,
+            self_type,
+        });
+    }
+    Ok(function_collection.warnings.into_inner().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::parser::Parser;
+
+    fn check_source(src: &str) -> SemaResult<Vec<SemaWarning>> {
+        let mut parser = Parser::new("main", src);
+        let mut file = parser.parse_file().unwrap();
+        file.id = "main.luna".into();
+        let mut program = ast::Program {
+            packages: vec![Box::new(ast::Package {
+                id: "main".into(),
+                base_path: None,
+                files: vec![file],
+            })],
+            ..Default::default()
+        };
+        check_program(&mut program, &crate::builtins::default_builtins())
+    }
+
+    #[test]
+    fn test_check_switch_warnings() {
+        let reasons = |src| {
+            check_source(src)
+                .unwrap()
+                .into_iter()
+                .map(|w| w.reason)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            reasons("func main() { let a = 3; switch a { 0..5: {} 3..8: {} _: {} } }"),
+            vec![SemaWarningReason::OverlappingIntegerPatterns]
+        );
+        assert_eq!(
+            reasons("func main() { let a = 3; switch a { _: {} 1: {} } }"),
+            vec![SemaWarningReason::UnreachableSwitchCase]
+        );
+    }
+
+    #[test]
+    fn test_check_without_warnings() {
+        let warnings = check_source("func main() { let a = 3; switch a { 1: {} _: {} } }").unwrap();
+        assert!(warnings.is_empty());
+    }
 }
@@ -1,6 +1,6 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-use crate::compiler::{SourceLoc, ast, mangle};
+use crate::compiler::{SourceLoc, ast, mangle, patterns};
 use crate::ir::builder::FuncBuilder;
 use crate::ir::{
     self, BlockRef, GlobalRef, GlobalValueMap, Signature, StringMap, StringRef, Type, VariableRef,
@@ -99,7 +99,7 @@ impl SwitchEmitter {
             }
 
             if contiguous_case_ranges.len() <= 3 {
-                Self::build_search_branches(bx, temp, otherwise, contiguous_case_ranges);
+                Self::build_search_branches(bx, otherwise, temp, contiguous_case_ranges);
             } else {
                 let split_point = contiguous_case_ranges.len() / 2;
                 let (left, right) = contiguous_case_ranges.split_at(split_point);
@@ -171,7 +171,7 @@ impl SwitchEmitter {
         bx.load(temp);
 
         if first_index != 0 {
-            bx.load_const_int(first_index.wrapping_neg());
+            bx.load_const_int(first_index);
             bx.sub_int();
         };
 
@@ -263,12 +263,35 @@ impl Into<ir::Type> for crate::types::Type {
     }
 }
 
+// The blocks `break` and `continue` jump to for an enclosing loop
+struct LoopBlocks {
+    label: Option<String>,
+    continue_block: BlockRef,
+    break_block: BlockRef,
+    // whether a break leaves the loop
+    broken: bool,
+}
+
+// Functions used as values are found while generating another function, they
+// are generated once that function is finished
+enum PendingFunc {
+    // a named function, called through a wrapper taking the closure object
+    Wrapper(String, types::FunctionType),
+    // a method, called through a wrapper taking the closure object holding its receiver
+    BoundMethod(String, types::Type, String, types::FunctionType),
+    Closure(String, ast::Closure),
+}
+
 struct FuncGen<'a> {
     interned_file_name: StringRef,
+    symbol_name: String,
     bld: FuncBuilder<'a>,
     str_map: &'a mut StringMap,
     global_map: &'a mut GlobalValueMap,
     self_var: Option<ir::VariableRef>,
+    loops: Vec<LoopBlocks>,
+    closures: usize,
+    pending: Vec<PendingFunc>,
 }
 
 impl<'a> FuncGen<'a> {
@@ -300,7 +323,71 @@ impl<'a> FuncGen<'a> {
             .iter()
             .map(|(id, _)| mangle::mangle_method_name(id, typ))
             .collect::<Vec<_>>();
-        self.global_map.intern(ir::GlobalValue::VirtualTable(v))
+        let type_id = self.type_id(typ);
+        self.global_map.intern(ir::GlobalValue::VirtualTable(type_id, v))
+    }
+
+    // The runtime identity of a type stored in interface values, type names are
+    // unique in a program so the interned name is used
+    fn type_id(&mut self, typ: &types::Type) -> StringRef {
+        self.str_map.intern(&types::name(typ))
+    }
+
+    // Pushes the runtime identity of the type held by the interface value on
+    // the stack
+    fn load_type_id(&mut self, interface: VariableRef) {
+        self.bld.load(interface);
+        self.bld.get_object(1, Type::Reference);
+        self.bld.get_object(0, Type::Integer);
+    }
+
+    // Emits the instruction for an arithmetic operator, both operands are expected on the stack
+    fn arithmetic_op(&mut self, kind: &ast::BinaryExprKind, typ: &types::Type) {
+        match typ.kind() {
+            crate::types::TypeKind::Number => match kind {
+                ast::BinaryExprKind::Add => self.bld.add_number(),
+                ast::BinaryExprKind::Subtract => self.bld.sub_number(),
+                ast::BinaryExprKind::Multiply => self.bld.mul_number(),
+                ast::BinaryExprKind::Divide => self.bld.div_number(),
+                _ => {
+                    panic!("Invalid condition for type");
+                }
+            },
+            crate::types::TypeKind::Integer => match kind {
+                ast::BinaryExprKind::Add => self.bld.add_int(),
+                ast::BinaryExprKind::Subtract => self.bld.sub_int(),
+                ast::BinaryExprKind::Multiply => self.bld.mul_int(),
+                ast::BinaryExprKind::Divide => self.bld.div_int(),
+                ast::BinaryExprKind::Modulo => self.bld.mod_int(),
+                ast::BinaryExprKind::BitwiseAnd => self.bld.and_int(),
+                ast::BinaryExprKind::BitwiseOr => self.bld.or_int(),
+                ast::BinaryExprKind::BitwiseXor => self.bld.xor_int(),
+                ast::BinaryExprKind::ShiftLeft => self.bld.shl_int(),
+                ast::BinaryExprKind::ShiftRight => self.bld.shr_int(),
+                _ => {
+                    panic!("Invalid condition for type");
+                }
+            },
+            // bytes are unsigned, so division and right shifts need their own instructions
+            crate::types::TypeKind::Byte => match kind {
+                ast::BinaryExprKind::Add => self.bld.add_int(),
+                ast::BinaryExprKind::Subtract => self.bld.sub_int(),
+                ast::BinaryExprKind::Multiply => self.bld.mul_int(),
+                ast::BinaryExprKind::Divide => self.bld.div_byte(),
+                ast::BinaryExprKind::Modulo => self.bld.mod_byte(),
+                ast::BinaryExprKind::BitwiseAnd => self.bld.and_int(),
+                ast::BinaryExprKind::BitwiseOr => self.bld.or_int(),
+                ast::BinaryExprKind::BitwiseXor => self.bld.xor_int(),
+                ast::BinaryExprKind::ShiftLeft => self.bld.shl_int(),
+                ast::BinaryExprKind::ShiftRight => self.bld.shr_byte(),
+                _ => {
+                    panic!("Invalid condition for type");
+                }
+            },
+            _ => {
+                panic!("Cant generate arithmetic for {:?}", typ);
+            }
+        }
     }
 
     fn binary_expr(&mut self, e: &ast::Expr, b: &Box<ast::BinaryExpr>) {
@@ -316,29 +403,15 @@ impl<'a> FuncGen<'a> {
                 if types::is_integer(&b.rhs.typ) {
                     self.bld.promote();
                 }
-                match b.kind {
-                    ast::BinaryExprKind::Add => self.bld.add_number(),
-                    ast::BinaryExprKind::Subtract => self.bld.sub_number(),
-                    ast::BinaryExprKind::Multiply => self.bld.mul_number(),
-                    ast::BinaryExprKind::Divide => self.bld.div_number(),
-                    _ => {
-                        panic!("Invalid condition for type");
-                    }
-                }
+                self.arithmetic_op(&b.kind, &e.typ);
             }
-            crate::types::TypeKind::Integer => {
+            crate::types::TypeKind::Integer | crate::types::TypeKind::Byte => {
                 // auto cast to integer is not supported
                 self.expr(&b.lhs);
                 self.expr(&b.rhs);
-                match b.kind {
-                    ast::BinaryExprKind::Add => self.bld.add_int(),
-                    ast::BinaryExprKind::Subtract => self.bld.sub_int(),
-                    ast::BinaryExprKind::Multiply => self.bld.mul_int(),
-                    ast::BinaryExprKind::Divide => self.bld.div_int(),
-                    _ => {
-                        panic!("Invalid condition for type");
-                    }
-                }
+                // division and checked arithmetic can panic, so point it at the operator
+                self.emit_source_loc(e.loc);
+                self.arithmetic_op(&b.kind, &e.typ);
             }
             crate::types::TypeKind::Bool => {
                 self.expr(&b.lhs);
@@ -392,8 +465,19 @@ impl<'a> FuncGen<'a> {
         }
     }
 
-    fn unary_expr(&mut self, _u: &Box<ast::UnaryExpr>) {
-        todo!()
+    fn unary_expr(&mut self, u: &Box<ast::UnaryExpr>) {
+        self.expr(&u.value);
+        match u.kind {
+            ast::UnaryExprKind::Negate => match u.value.typ.kind() {
+                crate::types::TypeKind::Number => self.bld.neg_number(),
+                crate::types::TypeKind::Integer | crate::types::TypeKind::Byte => {
+                    self.bld.neg_int()
+                }
+                _ => panic!("Invalid type for negation"),
+            },
+            ast::UnaryExprKind::LogicalNot => self.bld.not(),
+            ast::UnaryExprKind::BitwiseNot => self.bld.bit_not(),
+        }
     }
 
     fn assign(&mut self, a: &Box<ast::Assign>) {
@@ -401,21 +485,137 @@ impl<'a> FuncGen<'a> {
         self.store_expr(&a.destination);
     }
 
+    // Applies the operator to the current value of the destination, which is expected on the stack
+    fn compound_value(&mut self, e: &ast::Expr, a: &ast::CompoundAssign) {
+        self.expr(&a.value);
+        if types::is_number(&a.destination.typ) && types::is_integer(&a.value.typ) {
+            self.bld.promote();
+        }
+        self.emit_source_loc(e.loc);
+        self.arithmetic_op(&a.kind, &a.destination.typ);
+    }
+
+    // Computes the new value from the current one on the stack, leaving the
+    // value of the expression under it. That is the new value, or the old one
+    // for `a++` and `a--`
+    fn compound_result(&mut self, e: &ast::Expr, a: &ast::CompoundAssign) {
+        if a.postfix {
+            self.bld.dup(0);
+            self.compound_value(e, a);
+        } else {
+            self.compound_value(e, a);
+            self.bld.dup(0);
+        }
+    }
+
+    // The destination is only evaluated once, its object or array and index
+    // are kept in temps while the new value is computed
+    fn compound_assign(&mut self, e: &ast::Expr, a: &Box<ast::CompoundAssign>) {
+        let typ: Type = a.destination.typ.clone().into();
+        match &a.destination.kind {
+            ast::ExprKind::Identifier(i) => {
+                let var_id = self
+                    .bld
+                    .find_var(&i.id)
+                    .unwrap_or_else(|| panic!("Undefined variable {}", i.id));
+                self.bld.load(var_id);
+                self.compound_result(e, a);
+                self.bld.store(var_id);
+            }
+            ast::ExprKind::GlobalRef(g) => {
+                self.load_global(g, typ);
+                self.compound_result(e, a);
+                self.bld.store_static(g.symbol_name.clone(), typ);
+            }
+            ast::ExprKind::Selector(s) => {
+                self.expr(&s.value);
+                let object = self.bld.create_temp(Type::Reference);
+                self.bld.store(object);
+                self.bld.load(object);
+                self.bld.get_object(s.idx, typ);
+                self.compound_result(e, a);
+                self.bld.load(object);
+                self.bld.set_object(s.idx, typ);
+            }
+            ast::ExprKind::Subscript(l) if types::is_map(&l.value.typ) => {
+                let key_type: Type = types::get_map_types(&l.value.typ).0.into();
+                self.expr(l.index.as_ref().expect("Expected a key"));
+                let key = self.bld.create_temp(key_type);
+                self.bld.store(key);
+                self.expr(&l.value);
+                let map = self.bld.create_temp(Type::Reference);
+                self.bld.store(map);
+                self.bld.load(key);
+                self.bld.load(map);
+                self.bld.load_map(key_type, typ);
+                self.compound_result(e, a);
+                self.bld.load(key);
+                self.bld.load(map);
+                self.bld.store_map(key_type, typ);
+            }
+            ast::ExprKind::Subscript(l) => {
+                self.expr(l.index.as_ref().expect("Expected an index"));
+                let index = self.bld.create_temp(Type::Integer);
+                self.bld.store(index);
+                self.expr(&l.value);
+                let array = self.bld.create_temp(Type::Array);
+                self.bld.store(array);
+                self.bld.load(index);
+                self.bld.load(array);
+                self.bld.load_array(typ);
+                self.compound_result(e, a);
+                self.bld.load(index);
+                self.bld.load(array);
+                self.bld.store_array(typ);
+            }
+            _ => panic!("Cant use {:?} as a compound assignment destination", a.destination.kind),
+        }
+    }
+
     fn enum_literal(&mut self, typ: &types::Type, i: usize, values: &Vec<ast::Expr>) {
         let enum_size = types::get_max_enum_values(typ);
+        // the object lives in a variable so the GC can find it while the values are evaluated
+        let object = self.bld.create_temp(Type::Reference);
         self.bld.new_object(enum_size + 1);
+        self.bld.store(object);
         self.bld.load_const_int(i as i64);
-        self.bld.dup(1);
+        self.bld.load(object);
         self.bld.set_object(0, Type::Integer);
 
         for (i, value) in values.iter().enumerate() {
             self.expr(value);
-            self.bld.dup(1);
+            self.bld.load(object);
             self.bld.set_object(i + 1, value.typ.clone().into());
         }
+        self.bld.load(object);
     }
 
     fn call(&mut self, c: &ast::Call, e: &ast::Expr) {
+        self.call_values(c, e);
+        if types::is_tuple(&e.typ) {
+            // the returned values are packed into a tuple object
+            let element_types = types::get_tuple_types(&e.typ);
+            let values = element_types
+                .iter()
+                .map(|t| self.bld.create_temp(t.clone().into()))
+                .collect::<Vec<_>>();
+            for value in values.iter().rev() {
+                self.bld.store(*value);
+            }
+            let tuple = self.bld.create_temp(Type::Reference);
+            self.bld.new_object(values.len());
+            self.bld.store(tuple);
+            for (i, (value, typ)) in values.iter().zip(element_types.iter()).enumerate() {
+                self.bld.load(*value);
+                self.bld.load(tuple);
+                self.bld.set_object(i, typ.clone().into());
+            }
+            self.bld.load(tuple);
+        }
+    }
+
+    // Calls leave each of their return values on the stack
+    fn call_values(&mut self, c: &ast::Call, e: &ast::Expr) {
         if let Some(i) = c.enum_idx {
             self.enum_literal(&e.typ, i, &c.parameters);
             return;
@@ -431,7 +631,24 @@ impl<'a> FuncGen<'a> {
         }
 
         self.bld.check_yield();
-        if let Some(name) = &c.symbol_name {
+        if let Some(method) = c.array_method {
+            self.array_method(c, method);
+        } else if let Some(method) = c.map_method {
+            self.map_method(c, method);
+        } else if let Some(object) = &c.init {
+            // the new object is passed to init as self and is the value of the call
+            self.expr(object);
+            let object_id = self.bld.create_temp(Type::Reference);
+            self.bld.store(object_id);
+            if let Some(name) = &c.symbol_name {
+                self.bld.load(object_id);
+                for arg in c.parameters.iter() {
+                    self.expr(arg);
+                }
+                self.bld.call(name.clone());
+            }
+            self.bld.load(object_id);
+        } else if let Some(name) = &c.symbol_name {
             // when it is a struct function call, we need to load self first
             if let ast::ExprKind::Selector(s) = &c.function.kind {
                 self.expr(&s.value);
@@ -450,8 +667,9 @@ impl<'a> FuncGen<'a> {
                 // load vtable and function pointer
                 self.bld.load(interface_id);
                 self.bld.get_object(1, Type::Reference);
+                // the methods come after the type identity
                 let idx = types::get_interface_func_index(&c.function.typ, &s.selector.id);
-                self.bld.get_object(idx, Type::Reference);
+                self.bld.get_object(idx + 1, Type::Reference);
                 // load self
                 self.bld.load(interface_id);
                 self.bld.get_object(0, Type::Reference);
@@ -459,21 +677,98 @@ impl<'a> FuncGen<'a> {
                 for arg in c.parameters.iter() {
                     self.expr(arg);
                 }
+                let ret_types = if types::is_tuple(&e.typ) {
+                    types::get_tuple_types(&e.typ).into_iter().map(|t| t.into()).collect()
+                } else {
+                    vec![e.typ.clone().into()]
+                };
                 let mut signature = Signature {
                     parameters: c.parameters.iter().map(|p| p.typ.clone().into()).collect(),
-                    ret_types: vec![e.typ.clone().into()],
+                    ret_types,
                 };
                 signature.parameters.insert(0, ir::Type::Reference);
                 self.bld.indirect_call(signature);
             } else {
                 panic!("Trying to call an interface method but the function was not a selector!");
             }
-        } else {
+        } else if let types::TypeKind::Function(func_type) = c.function.typ.kind() {
+            // calling a function value, the code pointer is the first field of
+            // the closure object, which is passed along as a hidden argument
             self.expr(&c.function);
-            self.bld.indirect_call(Signature {
-                parameters: c.parameters.iter().map(|p| p.typ.clone().into()).collect(),
-                ret_types: vec![e.typ.clone().into()],
-            });
+            let closure = self.bld.create_temp(Type::Reference);
+            self.bld.store(closure);
+            self.bld.load(closure);
+            self.bld.get_object(0, Type::Reference);
+            self.bld.load(closure);
+            for arg in c.parameters.iter() {
+                self.expr(arg);
+            }
+            let mut signature = Signature {
+                parameters: func_type.params.iter().map(|p| p.clone().into()).collect(),
+                ret_types: func_type.returns.iter().map(|r| r.clone().into()).collect(),
+            };
+            signature.parameters.insert(0, Type::Reference);
+            self.bld.indirect_call(signature);
+        } else {
+            panic!("Cant call a value of type {:?}", c.function.typ);
+        }
+    }
+
+    fn array_method(&mut self, c: &ast::Call, method: ast::ArrayMethod) {
+        let ast::ExprKind::Selector(s) = &c.function.kind else {
+            panic!("Array methods are always called through a selector");
+        };
+        let element_type: Type = types::get_inner_array_type(&c.function.typ).into();
+        self.expr(&s.value);
+        let array = self.bld.create_temp(Type::Array);
+        self.bld.store(array);
+        match method {
+            ast::ArrayMethod::Push => {
+                self.expr(&c.parameters[0]);
+                self.bld.load(array);
+                self.bld.array_len();
+                self.bld.load(array);
+                self.bld.array_insert(element_type);
+            }
+            ast::ArrayMethod::Insert => {
+                self.expr(&c.parameters[0]);
+                let index = self.bld.create_temp(Type::Integer);
+                self.bld.store(index);
+                self.expr(&c.parameters[1]);
+                self.bld.load(index);
+                self.bld.load(array);
+                self.bld.array_insert(element_type);
+            }
+            ast::ArrayMethod::Pop => {
+                self.bld.load(array);
+                self.bld.array_len();
+                self.bld.load_const_int(1);
+                self.bld.sub_int();
+                self.bld.load(array);
+                self.bld.array_remove(element_type);
+            }
+            ast::ArrayMethod::Remove => {
+                self.expr(&c.parameters[0]);
+                self.bld.load(array);
+                self.bld.array_remove(element_type);
+            }
+            ast::ArrayMethod::Clear => {
+                self.bld.load(array);
+                self.bld.array_clear();
+            }
+        }
+    }
+
+    fn map_method(&mut self, c: &ast::Call, method: ast::MapMethod) {
+        let ast::ExprKind::Selector(s) = &c.function.kind else {
+            panic!("Map methods are always called through a selector");
+        };
+        let key_type: Type = types::get_map_types(&c.function.typ).0.into();
+        self.expr(&c.parameters[0]);
+        self.expr(&s.value);
+        match method {
+            ast::MapMethod::Contains => self.bld.map_contains(key_type),
+            ast::MapMethod::Remove => self.bld.map_remove(key_type),
         }
     }
 
@@ -506,6 +801,66 @@ impl<'a> FuncGen<'a> {
         }
     }
 
+    // Function values are closure objects holding the code pointer followed by
+    // the captured variables, named functions have nothing to capture
+    fn function_ref(&mut self, e: &ast::Expr, f: &ast::FunctionRef) {
+        let func_type = match e.typ.kind() {
+            types::TypeKind::Function(func_type) => func_type.clone(),
+            _ => panic!("Function reference does not have a function type"),
+        };
+        self.pending
+            .push(PendingFunc::Wrapper(f.symbol_name.clone(), func_type));
+        self.bld.new_object(1);
+        self.bld.load_function(format!("{}.value", f.symbol_name));
+        self.bld.dup(1);
+        self.bld.set_object(0, Type::Reference);
+    }
+
+    fn method_ref(&mut self, e: &ast::Expr, m: &ast::MethodRef) {
+        let func_type = match e.typ.kind() {
+            types::TypeKind::Function(func_type) => func_type.clone(),
+            _ => panic!("Method reference does not have a function type"),
+        };
+        self.pending.push(PendingFunc::BoundMethod(
+            m.symbol_name.clone(),
+            m.value.typ.clone(),
+            m.method.clone(),
+            func_type,
+        ));
+        // evaluate the receiver before allocating, the object is not a gc root
+        self.expr(&m.value);
+        let receiver = self.bld.create_temp(m.value.typ.clone().into());
+        self.bld.store(receiver);
+        self.bld.new_object(2);
+        self.bld.load_function(format!("{}.bound", m.symbol_name));
+        self.bld.dup(1);
+        self.bld.set_object(0, Type::Reference);
+        self.bld.load(receiver);
+        self.bld.dup(1);
+        self.bld.set_object(1, m.value.typ.clone().into());
+    }
+
+    fn closure(&mut self, c: &ast::Closure) {
+        let symbol_name = format!("{}.closure{}", self.symbol_name, self.closures);
+        self.closures += 1;
+        self.pending
+            .push(PendingFunc::Closure(symbol_name.clone(), c.clone()));
+        self.bld.new_object(1 + c.captures.len());
+        self.bld.load_function(symbol_name);
+        self.bld.dup(1);
+        self.bld.set_object(0, Type::Reference);
+        for (i, (id, typ)) in c.captures.iter().enumerate() {
+            // self is a variable named "", so it is captured the same way
+            let var_id = self
+                .bld
+                .find_var(id)
+                .unwrap_or_else(|| panic!("Undefined captured variable {}", id));
+            self.bld.load(var_id);
+            self.bld.dup(1);
+            self.bld.set_object(i + 1, typ.clone().into());
+        }
+    }
+
     fn subscript(&mut self, e: &ast::Expr, l: &Box<ast::Subscript>) {
         if l.is_slice {
             if let Some(index) = &l.index {
@@ -521,6 +876,11 @@ impl<'a> FuncGen<'a> {
             }
             self.expr(&l.value);
             self.bld.create_slice(types::get_inner_array_type(&e.typ).into());
+        } else if types::is_map(&l.value.typ) {
+            let (key_type, _) = types::get_map_types(&l.value.typ);
+            self.expr(l.index.as_ref().expect("Expected a key"));
+            self.expr(&l.value);
+            self.bld.load_map(key_type.into(), e.typ.clone().into());
         } else {
             self.expr(l.index.as_ref().expect("Expected an index"));
             self.expr(&l.value);
@@ -534,6 +894,9 @@ impl<'a> FuncGen<'a> {
         } else if types::is_array(&s.value.typ) && s.selector.id == "length" {
             self.expr(&s.value);
             self.bld.array_len();
+        } else if types::is_map(&s.value.typ) && s.selector.id == "length" {
+            self.expr(&s.value);
+            self.bld.map_len();
         } else {
             self.expr(&s.value);
             self.bld.get_object(s.idx, e.typ.clone().into());
@@ -541,17 +904,81 @@ impl<'a> FuncGen<'a> {
     }
 
     fn array_literal(&mut self, e: &ast::Expr, a: &Box<ast::ArrayLiteral>) {
+        // the array lives in a variable so the GC can find it while the literals are evaluated
+        let array = self.bld.create_temp(Type::Array);
         self.bld.new_array(a.literals.len(), translate_type(&types::get_inner_array_type(&e.typ)));
+        self.bld.store(array);
         for (i, literal) in a.literals.iter().enumerate() {
             self.expr(literal);
             self.bld.load_const_int(i as i64);
-            self.bld.dup(2);
+            self.bld.load(array);
             self.bld.store_array(literal.typ.clone().into());
         }
+        self.bld.load(array);
+    }
+
+    fn tuple_literal(&mut self, t: &ast::TupleLiteral) {
+        // the tuple lives in a variable so the GC can find it while the values are evaluated
+        let tuple = self.bld.create_temp(Type::Reference);
+        self.bld.new_object(t.values.len());
+        self.bld.store(tuple);
+        for (i, value) in t.values.iter().enumerate() {
+            self.expr(value);
+            self.bld.load(tuple);
+            self.bld.set_object(i, value.typ.clone().into());
+        }
+        self.bld.load(tuple);
+    }
+
+    // Leaves each value of a tuple on the stack, without building the tuple
+    // object when the values are written out or returned by a call
+    fn tuple_values(&mut self, e: &ast::Expr) {
+        match &e.kind {
+            ast::ExprKind::TupleLiteral(t) => {
+                for value in t.values.iter() {
+                    self.expr(value);
+                }
+            }
+            ast::ExprKind::Call(c) => {
+                self.emit_source_loc(e.loc);
+                self.call_values(c, e);
+            }
+            _ => {
+                self.expr(e);
+                let tuple = self.bld.create_temp(Type::Reference);
+                self.bld.store(tuple);
+                for (i, typ) in types::get_tuple_types(&e.typ).into_iter().enumerate() {
+                    self.bld.load(tuple);
+                    self.bld.get_object(i, typ.into());
+                }
+            }
+        }
+    }
+
+    fn map_literal(&mut self, e: &ast::Expr, m: &Box<ast::MapLiteral>) {
+        // the map lives in a variable so the GC can find it while the entries are evaluated
+        let (key_type, value_type) = types::get_map_types(&e.typ);
+        let map = self.bld.create_temp(Type::Reference);
+        self.bld.new_map(translate_type(&key_type), translate_type(&value_type));
+        self.bld.store(map);
+        for (key, value) in m.entries.iter() {
+            self.expr(value);
+            self.expr(key);
+            self.bld.load(map);
+            self.bld.store_map(translate_type(&key_type), translate_type(&value_type));
+        }
+        self.bld.load(map);
     }
 
     fn object_literal(&mut self, typ: &types::Type, o: &Box<ast::ObjectLiteral>) {
-        self.bld.new_object(o.fields.len());
+        // the object lives in a variable so the GC can find it while the fields are evaluated
+        let object = self.bld.create_temp(Type::Reference);
+        let field_count = match typ.kind() {
+            crate::types::TypeKind::Struct(struct_type) => struct_type.fields.read().unwrap().len(),
+            _ => o.fields.len(),
+        };
+        self.bld.new_object(field_count);
+        self.bld.store(object);
         // We need to set all the fields which we got then we need to provide defaults for the rest
         if let crate::types::TypeKind::Struct(struct_fields) = typ.kind() {
             for (i, (field_name, field_type)) in
@@ -573,17 +1000,17 @@ impl<'a> FuncGen<'a> {
                         crate::types::TypeKind::Array(typ) => {
                             self.bld.new_array(0, translate_type(typ));
                         }
-                        _ => {
-                            panic!(
-                                "Cannot provide default value for field type {:?}",
-                                field_type
-                            );
+                        crate::types::TypeKind::Map(key_type, value_type) => {
+                            self.bld.new_map(translate_type(key_type), translate_type(value_type));
                         }
+                        // only objects made for `init` leave references out, it has to set them
+                        _ => self.bld.load_const_int(0),
                     }
                 }
-                self.bld.dup(1);
+                self.bld.load(object);
                 self.bld.set_object(i, field_type.clone().into());
             }
+            self.bld.load(object);
         } else {
             panic!("Trying to emit object literal but type was not struct!")
         }
@@ -597,11 +1024,82 @@ impl<'a> FuncGen<'a> {
         }
     }
 
+    fn try_expr(&mut self, e: &ast::Expr, t: &ast::Try) {
+        let value = self.bld.create_temp(Type::Reference);
+        let failure_block = self.bld.new_block();
+        let success_block = self.bld.new_block();
+
+        self.expr(&t.value);
+        self.bld.store(value);
+        self.bld.load(value);
+        self.bld.get_object(0, Type::Integer);
+        self.bld.load_const_int(t.success_idx as i64);
+        self.bld.eq_int();
+        self.bld.condbr(success_block, failure_block);
+
+        // the failure has the same layout in the return type, so it is returned as is
+        self.bld.switch_to_block(failure_block);
+        self.bld.load(value);
+        self.bld.ret();
+
+        self.bld.switch_to_block(success_block);
+        self.bld.load(value);
+        self.bld.get_object(1, e.typ.clone().into());
+    }
+
+    // `value as? T`, an Option holding the value when the interface value holds a T
+    fn optional_cast(&mut self, c: &ast::Cast) {
+        let (typ, some_idx) = types::try_value_type(&c.target_type).unwrap();
+        let value = self.bld.create_temp(Type::Reference);
+        let option = self.bld.create_temp(Type::Reference);
+        let some_block = self.bld.new_block();
+        let none_block = self.bld.new_block();
+        let finish_block = self.bld.new_block();
+
+        self.expr(&c.value);
+        self.bld.store(value);
+        self.bld.new_object(types::get_max_enum_values(&c.target_type) + 1);
+        self.bld.store(option);
+        self.load_type_id(value);
+        let type_id = self.type_id(&typ);
+        self.bld.load_const_int(type_id as i64);
+        self.bld.eq_int();
+        self.bld.condbr(some_block, none_block);
+
+        self.bld.switch_to_block(some_block);
+        self.bld.load_const_int(some_idx as i64);
+        self.bld.load(option);
+        self.bld.set_object(0, Type::Integer);
+        self.bld.load(value);
+        self.bld.get_object(0, typ.clone().into());
+        self.bld.load(option);
+        self.bld.set_object(1, typ.into());
+        self.bld.br(finish_block);
+
+        self.bld.switch_to_block(none_block);
+        self.bld.load_const_int(1 - some_idx as i64);
+        self.bld.load(option);
+        self.bld.set_object(0, Type::Integer);
+        self.bld.br(finish_block);
+
+        self.bld.switch_to_block(finish_block);
+        self.bld.load(option);
+    }
+
     fn cast(&mut self, c: &ast::Cast) {
+        if c.optional {
+            self.optional_cast(c);
+            return;
+        }
         match c.target_type.kind() {
             crate::types::TypeKind::Interface(interface) => {
-                self.bld.new_object(2);
+                // evaluate the value before allocating, the object is not a gc root
+                // until it is stored so a call in the value could collect it
                 self.expr(&c.value);
+                let value = self.bld.create_temp(c.value.typ.clone().into());
+                self.bld.store(value);
+                self.bld.new_object(2);
+                self.bld.load(value);
                 self.bld.dup(1);
                 self.bld.set_object(0, Type::Reference);
                 let vtable = self.generate_interface_vtable(interface, &c.value.typ);
@@ -609,7 +1107,39 @@ impl<'a> FuncGen<'a> {
                 self.bld.dup(1);
                 self.bld.set_object(1, Type::Reference);
             }
-            _ => unimplemented!(),
+            _ if types::compare(&c.target_type, &c.value.typ) == types::ComparisonResult::Same => {
+                self.expr(&c.value);
+            }
+            target => {
+                self.expr(&c.value);
+                // convert the value to an int and then the int to the target,
+                // only a number to bool compares the number directly
+                let source = c.value.typ.kind();
+                match source {
+                    types::TypeKind::Byte | types::TypeKind::Bool => self.bld.extend(),
+                    types::TypeKind::Number if !matches!(target, types::TypeKind::Bool) => {
+                        self.bld.truncate()
+                    }
+                    _ => {}
+                }
+                match target {
+                    types::TypeKind::Integer => {}
+                    types::TypeKind::Number => self.bld.promote(),
+                    types::TypeKind::Byte => {
+                        self.emit_source_loc(c.value.loc);
+                        self.bld.narrow(c.wrapping);
+                    }
+                    types::TypeKind::Bool if matches!(source, types::TypeKind::Number) => {
+                        self.bld.load_const_number(0.0);
+                        self.bld.neq_number();
+                    }
+                    types::TypeKind::Bool => {
+                        self.bld.load_const_int(0);
+                        self.bld.neq_int();
+                    }
+                    _ => unreachable!("The checker only allows casts between int, number, byte and bool"),
+                }
+            }
         }
     }
 
@@ -658,26 +1188,41 @@ impl<'a> FuncGen<'a> {
             ast::ExprKind::BinaryExpr(b) => self.binary_expr(e, b),
             ast::ExprKind::UnaryExpr(u) => self.unary_expr(u),
             ast::ExprKind::Assign(a) => self.assign(a),
+            ast::ExprKind::CompoundAssign(a) => self.compound_assign(e, a),
             ast::ExprKind::Call(c) => self.call(c, e),
             ast::ExprKind::Integer(i) => self.integer(e, i),
             ast::ExprKind::Number(f) => self.number(f),
             ast::ExprKind::Boolean(b) => self.boolean(b),
             ast::ExprKind::StringLiteral(s) => self.string_literal(s),
             ast::ExprKind::Cast(c) => self.cast(c),
+            ast::ExprKind::Try(t) => self.try_expr(e, t),
             ast::ExprKind::Identifier(i) => self.identifier(i),
+            ast::ExprKind::FunctionRef(f) => self.function_ref(e, f),
+            ast::ExprKind::MethodRef(m) => self.method_ref(e, m),
+            ast::ExprKind::GlobalRef(g) => self.load_global(g, e.typ.clone().into()),
+            ast::ExprKind::Closure(c) => self.closure(c),
             ast::ExprKind::Subscript(l) => self.subscript(e, l),
             ast::ExprKind::Selector(l) => self.selector(e, l),
             ast::ExprKind::ArrayLiteral(a) => self.array_literal(e, a),
+            ast::ExprKind::TupleLiteral(t) => self.tuple_literal(t),
+            ast::ExprKind::MapLiteral(m) => self.map_literal(e, m),
             ast::ExprKind::ObjectLiteral(o) => self.object_literal(&e.typ, o),
             ast::ExprKind::_Self => self._self(),
             ast::ExprKind::Template(t) => self.template(t),
+            ast::ExprKind::If(i) => self.if_expr(e, i),
+            ast::ExprKind::Switch(s) => self.switch_expr(e, s),
         }
     }
 
     fn store_subscript(&mut self, e: &ast::Expr, l: &Box<ast::Subscript>) {
         self.expr(&l.index.as_ref().expect("Expected an index"));
         self.expr(&l.value);
-        self.bld.store_array(e.typ.clone().into());
+        if types::is_map(&l.value.typ) {
+            let (key_type, _) = types::get_map_types(&l.value.typ);
+            self.bld.store_map(key_type.into(), e.typ.clone().into());
+        } else {
+            self.bld.store_array(e.typ.clone().into());
+        }
     }
 
     fn store_selector(&mut self, e: &ast::Expr, s: &ast::Selector) {
@@ -685,6 +1230,20 @@ impl<'a> FuncGen<'a> {
         self.bld.set_object(s.idx, e.typ.clone().into());
     }
 
+    // A function can run while the globals are initialised, so reading one
+    // which has not been stored yet panics instead of giving a null reference
+    fn load_global(&mut self, g: &ast::GlobalRef, typ: Type) {
+        self.bld.load_static(mangle::mangle_initialised_name(&g.symbol_name), Type::Bool);
+        self.bld.check_initialised();
+        self.bld.load_static(g.symbol_name.clone(), typ);
+    }
+
+    // the value stays on the stack, the same as storing a variable
+    fn store_global(&mut self, e: &ast::Expr, g: &ast::GlobalRef) {
+        self.bld.dup(0);
+        self.bld.store_static(g.symbol_name.clone(), e.typ.clone().into());
+    }
+
     fn store_identifier(&mut self, _e: &ast::Expr, i: &Box<ast::Identifier>) {
         if let Some(var_id) = self.bld.find_var(&i.id) {
             self.bld.tee(var_id);
@@ -702,6 +1261,7 @@ impl<'a> FuncGen<'a> {
             ast::ExprKind::Subscript(l) => self.store_subscript(e, l),
             ast::ExprKind::Selector(l) => self.store_selector(e, l),
             ast::ExprKind::Identifier(i) => self.store_identifier(e, i),
+            ast::ExprKind::GlobalRef(g) => self.store_global(e, g),
             //ast::ExprKind::Assign(a) => self.store_assign(e, a),
             //ast::Expr::BinaryExpr(b)
             //ast::Expr::UnaryExpr(u)
@@ -738,7 +1298,134 @@ impl<'a> FuncGen<'a> {
 
     fn for_stmt(&mut self, f: &Box<ast::ForStmt>) -> bool {
         self.emit_source_loc(f.loc);
-        unimplemented!()
+        if let Some(next_call) = &f.next_call {
+            return self.for_iterator_stmt(f, next_call);
+        }
+        // maps are iterated through a snapshot of their keys, so the body can
+        // add and remove entries without skipping any
+        let is_map = types::is_map(&f.iterator.typ);
+        let element_type = if is_map {
+            types::get_map_types(&f.iterator.typ).0
+        } else {
+            types::get_inner_array_type(&f.iterator.typ)
+        };
+
+        // evaluate the array once, and keep the index in a temp
+        self.expr(&f.iterator);
+        if is_map {
+            self.bld.map_keys(element_type.clone().into());
+        }
+        let array = self.bld.create_temp(Type::Array);
+        self.bld.store(array);
+        let index = self.bld.create_temp(Type::Integer);
+        self.bld.load_const_int(0);
+        self.bld.store(index);
+
+        let condition_block = self.bld.new_block();
+        let body_block = self.bld.new_block();
+        let increment_block = self.bld.new_block();
+        let finish_block = self.bld.new_block();
+
+        self.bld.br(condition_block);
+        self.bld.switch_to_block(condition_block);
+        self.bld.load(index);
+        self.bld.load(array);
+        self.bld.array_len();
+        self.bld.lt_int();
+        self.bld.condbr(body_block, finish_block);
+
+        self.bld.switch_to_block(body_block);
+        self.bld.push_scope();
+        let element = self
+            .bld
+            .create_var(f.id.clone(), element_type.clone().into());
+        self.emit_source_loc(f.loc);
+        self.bld.load(index);
+        self.bld.load(array);
+        self.bld.load_array(element_type.into());
+        self.bld.store(element);
+        self.loops.push(LoopBlocks {
+            label: f.label.clone(),
+            continue_block: increment_block,
+            break_block: finish_block,
+            broken: false,
+        });
+        let did_return = self.stmt(&f.consequent);
+        self.loops.pop();
+        self.bld.pop_scope();
+        // the body may never run, so the loop itself never counts as returning
+        if !did_return {
+            self.bld.br(increment_block);
+        }
+        self.bld.switch_to_block(increment_block);
+        self.bld.load(index);
+        self.bld.load_const_int(1);
+        self.bld.add_int();
+        self.bld.store(index);
+        self.bld.check_yield();
+        self.bld.br(condition_block);
+        self.bld.switch_to_block(finish_block);
+        false
+    }
+
+    fn for_iterator_stmt(&mut self, f: &Box<ast::ForStmt>, next_call: &ast::Expr) -> bool {
+        // next() returns an Option of the element type
+        let crate::types::TypeKind::Enum(option) = next_call.typ.kind() else {
+            panic!("For loop iterator does not satisfy the iterator protocol");
+        };
+        let element_type = option.type_args[0].clone();
+
+        self.bld.push_scope();
+        self.expr(&f.iterator);
+        let iterator = self.bld.create_var(
+            ast::FOR_ITERATOR_ID.into(),
+            f.iterator.typ.clone().into(),
+        );
+        self.bld.store(iterator);
+        let option = self.bld.create_temp(Type::Reference);
+
+        let condition_block = self.bld.new_block();
+        let body_block = self.bld.new_block();
+        let continue_block = self.bld.new_block();
+        let finish_block = self.bld.new_block();
+
+        // call next() and keep going while it returns Some
+        self.bld.br(condition_block);
+        self.bld.switch_to_block(condition_block);
+        self.expr(next_call);
+        self.bld.store(option);
+        self.bld.load(option);
+        self.bld.get_object(0, Type::Integer);
+        self.bld.load_const_int(f.some_idx as i64);
+        self.bld.eq_int();
+        self.bld.condbr(body_block, finish_block);
+
+        self.bld.switch_to_block(body_block);
+        self.bld.push_scope();
+        let element = self
+            .bld
+            .create_var(f.id.clone(), element_type.clone().into());
+        self.bld.load(option);
+        self.bld.get_object(1, element_type.into());
+        self.bld.store(element);
+        self.loops.push(LoopBlocks {
+            label: f.label.clone(),
+            continue_block,
+            break_block: finish_block,
+            broken: false,
+        });
+        let did_return = self.stmt(&f.consequent);
+        self.loops.pop();
+        self.bld.pop_scope();
+        if !did_return {
+            self.bld.br(continue_block);
+        }
+        self.bld.switch_to_block(continue_block);
+        self.bld.check_yield();
+        self.bld.br(condition_block);
+        self.bld.switch_to_block(finish_block);
+        self.bld.pop_scope();
+        false
     }
 
     fn if_stmt(&mut self, f: &Box<ast::IfStmt>) -> bool {
@@ -754,17 +1441,20 @@ impl<'a> FuncGen<'a> {
             }
             self.bld.switch_to_block(consequent_block);
             let consequent_returned = self.stmt(&f.consequent);
+            // nested statements can leave us in a different block
+            let consequent_end = self.bld.current_block();
             self.bld.switch_to_block(alternate_block);
             let alternate_returned = self.stmt(alternate);
+            let alternate_end = self.bld.current_block();
             let needs_finish = !(consequent_returned && alternate_returned);
             if needs_finish {
                 let finish_block = self.bld.new_block();
                 if !consequent_returned {
-                    self.bld.switch_to_block(consequent_block);
+                    self.bld.switch_to_block(consequent_end);
                     self.bld.br(finish_block);
                 }
                 if !alternate_returned {
-                    self.bld.switch_to_block(alternate_block);
+                    self.bld.switch_to_block(alternate_end);
                     self.bld.br(finish_block)
                 };
                 self.bld.switch_to_block(finish_block);
@@ -792,10 +1482,87 @@ impl<'a> FuncGen<'a> {
         }
     }
 
+    // Stores the value of a branch of an if or switch expression in `result`,
+    // returns true when the branch leaves through return, break or continue
+    fn branch(&mut self, s: &ast::Stmt, result: VariableRef) -> bool {
+        match s {
+            ast::Stmt::Block(b) => self.branch_block(b, result),
+            ast::Stmt::If(i) => {
+                self.if_branches(i, result);
+                false
+            }
+            _ => self.stmt(s),
+        }
+    }
+
+    fn branch_block(&mut self, b: &ast::BlockStmt, result: VariableRef) -> bool {
+        self.emit_source_loc(b.loc);
+        self.bld.push_scope();
+        let mut did_return = false;
+        for (i, s) in b.stmts.iter().enumerate() {
+            let last = i + 1 == b.stmts.len();
+            let returned = match s {
+                ast::Stmt::ExprStmt(e) if last => {
+                    self.expr(&e.expr);
+                    self.bld.store(result);
+                    false
+                }
+                // a nested if or switch gives the value of its own branches
+                ast::Stmt::If(i) if last => {
+                    self.emit_source_loc(i.loc);
+                    self.if_branches(i, result);
+                    false
+                }
+                ast::Stmt::Switch(s) if last => self.switch(s, Some(result)),
+                _ => self.stmt(s),
+            };
+            if returned {
+                did_return = true;
+                break;
+            }
+        }
+        self.bld.pop_scope();
+        did_return
+    }
+
+    fn if_branches(&mut self, f: &ast::IfStmt, result: VariableRef) {
+        let consequent_block = self.bld.new_block();
+        let alternate_block = self.bld.new_block();
+        let finish_block = self.bld.new_block();
+        self.expr(&f.test);
+        if f.not {
+            self.bld.condbr(alternate_block, consequent_block);
+        } else {
+            self.bld.condbr(consequent_block, alternate_block);
+        }
+        self.bld.switch_to_block(consequent_block);
+        if !self.branch(&f.consequent, result) {
+            self.bld.br(finish_block);
+        }
+        self.bld.switch_to_block(alternate_block);
+        let alternate = f.alternate.as_ref().expect("checker requires an else");
+        if !self.branch(alternate, result) {
+            self.bld.br(finish_block);
+        }
+        self.bld.switch_to_block(finish_block);
+    }
+
+    fn if_expr(&mut self, e: &ast::Expr, f: &ast::IfStmt) {
+        self.emit_source_loc(f.loc);
+        let result = self.bld.create_temp(e.typ.clone().into());
+        self.if_branches(f, result);
+        self.bld.load(result);
+    }
+
     fn return_stmt(&mut self, r: &Box<ast::ReturnStmt>) -> bool {
         self.emit_source_loc(r.loc);
         if let Some(r) = &r.value {
-            self.expr(r);
+            // a tuple is returned as multiple values
+            if types::is_tuple(&r.typ) {
+                self.tuple_values(r);
+            } else {
+                self.expr(r);
+            }
         }
         self.bld.ret();
         true
@@ -803,6 +1570,20 @@ impl<'a> FuncGen<'a> {
 
     fn var_decl_stmt(&mut self, v: &Box<ast::VarDeclStmt>) -> bool {
         self.emit_source_loc(v.loc);
+        if let Some(ids) = &v.destructure {
+            self.tuple_values(&v.value);
+            let element_types = types::get_tuple_types(&v.value.typ);
+            let vars = ids
+                .iter()
+                .zip(element_types.iter())
+                .map(|(id, typ)| self.bld.create_var(id.clone(), typ.clone().into()))
+                .collect::<Vec<_>>();
+            // the last value is on top of the stack
+            for var in vars.iter().rev() {
+                self.bld.store(*var);
+            }
+            return false;
+        }
         self.expr(&v.value);
         let id = self
             .bld
@@ -815,6 +1596,7 @@ impl<'a> FuncGen<'a> {
         self.emit_source_loc(w.loc);
         let condition_block = self.bld.new_block();
         let body_block = self.bld.new_block();
+        let continue_block = self.bld.new_block();
         let finish_block = self.bld.new_block();
 
         self.bld.br(condition_block);
@@ -822,96 +1604,336 @@ impl<'a> FuncGen<'a> {
         self.expr(&w.condition);
         self.bld.condbr(body_block, finish_block);
         self.bld.switch_to_block(body_block);
+        self.loops.push(LoopBlocks {
+            label: w.label.clone(),
+            continue_block,
+            break_block: finish_block,
+            broken: false,
+        });
         let did_return = self.stmt(&w.consequent);
-        if did_return {
-            true
-        } else {
-            self.bld.check_yield();
-            self.bld.br(condition_block);
-            self.bld.switch_to_block(finish_block);
-            false
+        let broken = self.loops.pop().is_some_and(|l| l.broken);
+        if !did_return {
+            self.bld.br(continue_block);
+        }
+        self.bld.switch_to_block(continue_block);
+        self.bld.check_yield();
+        self.bld.br(condition_block);
+        self.bld.switch_to_block(finish_block);
+        // `while true` without a break can only be left by returning, otherwise
+        // the loop can always leave through its condition
+        if matches!(&w.condition.kind, ast::ExprKind::Boolean(b) if b.value) && !broken {
+            self.emit_source_loc(w.loc);
+            self.bld.unreachable();
+            return true;
         }
+        false
     }
 
-    fn switch_stmt(&mut self, s: &ast::SwitchStmt) -> bool {
-        self.emit_source_loc(s.loc);
-        let mut did_return = s.cases.len() > 0;
-        let prev_block = self.bld.current_block();
+    fn find_loop(&mut self, label: &Option<String>) -> &mut LoopBlocks {
+        let found = match label {
+            Some(label) => self
+                .loops
+                .iter_mut()
+                .rev()
+                .find(|l| l.label.as_ref() == Some(label)),
+            None => self.loops.last_mut(),
+        };
+        found.expect("break or continue outside of a loop")
+    }
 
-        let finish_block = self.bld.new_block();
-        //let mut blocks = Vec::new();
+    fn break_stmt(&mut self, b: &Box<ast::BreakStmt>) -> bool {
+        self.emit_source_loc(b.loc);
+        let found = self.find_loop(&b.label);
+        found.broken = true;
+        let break_block = found.break_block;
+        self.bld.br(break_block);
+        true
+    }
 
-        self.expr(&s.value);
+    fn continue_stmt(&mut self, c: &Box<ast::ContinueStmt>) -> bool {
+        self.emit_source_loc(c.loc);
+        let continue_block = self.find_loop(&c.label).continue_block;
+        self.bld.br(continue_block);
+        true
+    }
+
+    // Branches to `fail` unless the condition on the stack holds
+    fn test_or_fail(&mut self, fail: BlockRef) {
+        let next = self.bld.new_block();
+        self.bld.condbr(next, fail);
+        self.bld.switch_to_block(next);
+    }
+
+    // Emits the checks of a pattern against the value in a variable, the
+    // builder is left in the block reached when it matched with the bindings
+    // stored, otherwise it branches to `fail`
+    fn pattern_test(
+        &mut self,
+        p: &ast::Pattern,
+        value: VariableRef,
+        fail: BlockRef,
+        vars: &HashMap<String, VariableRef>,
+    ) {
+        match &p.kind {
+            ast::PatternKind::CatchAll => {}
+            ast::PatternKind::Binding(name) => {
+                self.bld.load(value);
+                self.bld.store(vars[name]);
+            }
+            ast::PatternKind::Integer(i) => {
+                self.bld.load(value);
+                self.bld.load_const_int(*i);
+                self.bld.eq_int();
+                self.test_or_fail(fail);
+            }
+            ast::PatternKind::IntegerRange(lo, hi) => {
+                self.bld.load(value);
+                self.bld.load_const_int(*lo);
+                self.bld.geq_int();
+                self.bld.load(value);
+                self.bld.load_const_int(*hi);
+                self.bld.leq_int();
+                self.bld.and();
+                self.test_or_fail(fail);
+            }
+            ast::PatternKind::String(string) => {
+                self.bld.load(value);
+                self.bld.load_const_string(self.str_map.intern(string));
+                self.bld.eq_string();
+                self.test_or_fail(fail);
+            }
+            ast::PatternKind::EnumVariant { values, idx, .. } => {
+                self.bld.load(value);
+                self.bld.get_object(0, Type::Integer);
+                self.bld.load_const_int(*idx as i64);
+                self.bld.eq_int();
+                self.test_or_fail(fail);
+                for (i, sub) in values.iter().enumerate() {
+                    self.sub_pattern_test(sub, value, i + 1, fail, vars);
+                }
+            }
+            ast::PatternKind::Type { binding, typ, .. } => {
+                self.load_type_id(value);
+                let type_id = self.type_id(typ);
+                self.bld.load_const_int(type_id as i64);
+                self.bld.eq_int();
+                self.test_or_fail(fail);
+                if let Some(binding) = binding {
+                    self.bld.load(value);
+                    self.bld.get_object(0, typ.clone().into());
+                    self.bld.store(vars[binding]);
+                }
+            }
+            ast::PatternKind::Struct { fields, .. } => {
+                for field in fields.iter() {
+                    self.sub_pattern_test(&field.pattern, value, field.idx, fail, vars);
+                }
+            }
+            ast::PatternKind::Or(alternatives) => {
+                let matched = self.bld.new_block();
+                for (i, alternative) in alternatives.iter().enumerate() {
+                    let alternative_fail = if i + 1 == alternatives.len() {
+                        fail
+                    } else {
+                        self.bld.new_block()
+                    };
+                    self.pattern_test(alternative, value, alternative_fail, vars);
+                    self.bld.br(matched);
+                    self.bld.switch_to_block(alternative_fail);
+                }
+                self.bld.switch_to_block(matched);
+            }
+        }
+    }
+
+    // Matches a pattern against a field of an object
+    fn sub_pattern_test(
+        &mut self,
+        p: &ast::Pattern,
+        object: VariableRef,
+        idx: usize,
+        fail: BlockRef,
+        vars: &HashMap<String, VariableRef>,
+    ) {
+        match &p.kind {
+            ast::PatternKind::CatchAll => {}
+            ast::PatternKind::Binding(name) => {
+                self.bld.load(object);
+                self.bld.get_object(idx, p.typ.clone().into());
+                self.bld.store(vars[name]);
+            }
+            _ => {
+                let value = self.bld.create_temp(p.typ.clone().into());
+                self.bld.load(object);
+                self.bld.get_object(idx, p.typ.clone().into());
+                self.bld.store(value);
+                self.pattern_test(p, value, fail, vars);
+            }
+        }
+    }
 
-        let default = if let Some(c) = s
+    // The values a pattern can match at the top level as inclusive ranges, with
+    // whether they came from a range pattern. None when it matches anything.
+    fn switch_keys(&mut self, p: &ast::Pattern) -> Option<Vec<(i64, i64, bool)>> {
+        match &p.kind {
+            ast::PatternKind::Integer(i) => Some(vec![(*i, *i, false)]),
+            ast::PatternKind::IntegerRange(lo, hi) => Some(vec![(*lo, *hi, true)]),
+            ast::PatternKind::EnumVariant { idx, .. } => {
+                Some(vec![(*idx as i64, *idx as i64, false)])
+            }
+            ast::PatternKind::Type { typ, .. } => {
+                let type_id = self.type_id(typ) as i64;
+                Some(vec![(type_id, type_id, false)])
+            }
+            ast::PatternKind::Or(alternatives) => {
+                let mut keys = Vec::new();
+                for alternative in alternatives.iter() {
+                    keys.extend(self.switch_keys(alternative)?);
+                }
+                Some(keys)
+            }
+            _ => None,
+        }
+    }
+
+    // Jumps to the first case which can match the value, by the tag of an enum,
+    // the type held by an interface or the integer itself. From there each case
+    // tests its whole pattern, so starting early is never wrong.
+    fn switch_dispatch(
+        &mut self,
+        s: &ast::SwitchStmt,
+        value: VariableRef,
+        tests: &[BlockRef],
+        no_match: BlockRef,
+    ) {
+        let typ = &s.value.typ;
+        let first = tests.first().copied().unwrap_or(no_match);
+        if !(types::is_enum(typ) || types::is_interface(typ) || types::is_integer(typ)) {
+            self.bld.br(first);
+            return;
+        }
+
+        let keys = s
             .cases
             .iter()
-            .find(|c| matches!(c.pattern.kind, ast::PatternKind::CatchAll))
-        {
-            let catch_all = self.bld.new_block();
-            self.bld.switch_to_block(catch_all);
-            did_return &= self.block_stmt(&c.block);
-            self.bld.br(finish_block);
-            self.bld.switch_to_block(prev_block);
-            catch_all
-        } else {
-            finish_block
-        };
+            .map(|c| self.switch_keys(&c.pattern))
+            .collect::<Vec<_>>();
+        let default = keys
+            .iter()
+            .position(|k| k.is_none())
+            .map(|i| tests[i])
+            .unwrap_or(no_match);
 
         let mut switch_emitter = SwitchEmitter::new();
+        let mut exact = HashSet::new();
+        for (i, case_keys) in keys.iter().enumerate() {
+            for &(lo, hi, is_range) in case_keys.iter().flatten() {
+                let target = keys[..=i]
+                    .iter()
+                    .position(|k| match k {
+                        None => true,
+                        Some(k) => k.iter().any(|&(l, h, _)| lo <= h && hi >= l),
+                    })
+                    .unwrap();
+                if is_range {
+                    switch_emitter.set_range_entry(lo, hi, tests[target]);
+                } else if exact.insert(lo) {
+                    switch_emitter.set_entry(lo, tests[target]);
+                }
+            }
+        }
 
-        if types::is_enum(&s.value.typ) {
-            let temp = self.bld.create_temp(Type::Reference);
-            self.bld.store(temp);
-            self.bld.load(temp);
+        if types::is_enum(typ) {
+            self.bld.load(value);
             self.bld.get_object(0, Type::Integer);
+        } else if types::is_interface(typ) {
+            self.load_type_id(value);
+        } else {
+            self.bld.load(value);
+        }
+        switch_emitter.emit(&mut self.bld, default);
+    }
 
-            for case in s.cases.iter() {
-                match &case.pattern.kind {
-                    ast::PatternKind::EnumVariant { id: _, values } => {
-                        let block = self.bld.new_block();
-                        self.bld.switch_to_block(block);
-                        self.bld.push_scope();
-                        for (i, (name, typ)) in values.iter().enumerate() {
-                            let var = self.bld.create_var(name.clone(), typ.clone().into());
-                            self.bld.load(temp);
-                            self.bld.get_object(i + 1, typ.clone().into());
-                            self.bld.store(var);
-                        }
-                        did_return &= self.block_stmt(&case.block);
-                        self.bld.pop_scope();
-                        self.bld.br(finish_block);
-                        switch_emitter.set_entry(case.case_idx, block);
-                    }
-                    _ => {}
-                }
+    fn switch_stmt(&mut self, s: &ast::SwitchStmt) -> bool {
+        self.switch(s, None)
+    }
+
+    fn switch_expr(&mut self, e: &ast::Expr, s: &ast::SwitchStmt) {
+        let result = self.bld.create_temp(e.typ.clone().into());
+        self.switch(s, Some(result));
+        self.bld.load(result);
+    }
+
+    // When the switch is an expression the value of each case is stored in `result`
+    fn switch(&mut self, s: &ast::SwitchStmt, result: Option<VariableRef>) -> bool {
+        self.emit_source_loc(s.loc);
+        self.expr(&s.value);
+        let value = self.bld.create_temp(s.value.typ.clone().into());
+        self.bld.store(value);
+        // the value can branch, so the dispatch goes in the block it ends in
+        let dispatch_block = self.bld.current_block();
+        let finish_block = self.bld.new_block();
+
+        // the checker made sure a switch over an enum covers every variant
+        let wildcard = ast::Pattern {
+            loc: s.loc,
+            kind: ast::PatternKind::CatchAll,
+            typ: s.value.typ.clone(),
+        };
+        let exhaustive = types::is_enum(&s.value.typ)
+            || s.cases.iter().any(|c| {
+                c.guard.is_none() && patterns::is_irrefutable(&c.pattern, &s.value.typ, &wildcard)
+            });
+        let no_match_block = if exhaustive {
+            let block = self.bld.new_block();
+            self.bld.switch_to_block(block);
+            self.bld.unreachable();
+            block
+        } else {
+            finish_block
+        };
+
+        // each case tests its pattern and guard, and falls through to the next
+        let tests = s
+            .cases
+            .iter()
+            .map(|_| self.bld.new_block())
+            .collect::<Vec<_>>();
+        let mut did_return = exhaustive;
+        for (i, case) in s.cases.iter().enumerate() {
+            let next = tests.get(i + 1).copied().unwrap_or(no_match_block);
+            self.bld.switch_to_block(tests[i]);
+            self.bld.push_scope();
+            let vars = patterns::bindings(&case.pattern)
+                .into_iter()
+                .map(|(name, typ)| {
+                    let var = self.bld.create_var(name.clone(), typ.into());
+                    (name, var)
+                })
+                .collect::<HashMap<_, _>>();
+            self.pattern_test(&case.pattern, value, next, &vars);
+
+            let body = self.bld.new_block();
+            if let Some(guard) = &case.guard {
+                self.expr(guard);
+                self.bld.condbr(body, next);
+            } else {
+                self.bld.br(body);
             }
-        } else if types::is_integer(&s.value.typ) {
-            for case in s.cases.iter() {
-                match &case.pattern.kind {
-                    ast::PatternKind::Integer(i) => {
-                        let block = self.bld.new_block();
-                        self.bld.switch_to_block(block);
-                        did_return &= self.block_stmt(&case.block);
-                        self.bld.br(finish_block);
-                        switch_emitter.set_entry(*i, block);
-                    }
-                    ast::PatternKind::IntegerRange(lo, hi) => {
-                        let block = self.bld.new_block();
-                        self.bld.switch_to_block(block);
-                        did_return &= self.block_stmt(&case.block);
-                        self.bld.br(finish_block);
-                        switch_emitter.set_range_entry(*lo, *hi, block);
-                    }
-                    _ => {}
-                }
+            self.bld.switch_to_block(body);
+            let returned = match result {
+                Some(result) => self.branch_block(&case.block, result),
+                None => self.block_stmt(&case.block),
+            };
+            if !returned {
+                self.bld.br(finish_block);
             }
-        } else {
-            unimplemented!();
+            did_return &= returned;
+            self.bld.pop_scope();
         }
 
-        self.bld.switch_to_block(prev_block);
-        switch_emitter.emit(&mut self.bld, default);
+        self.bld.switch_to_block(dispatch_block);
+        self.switch_dispatch(s, value, &tests, no_match_block);
         self.bld.switch_to_block(finish_block);
 
         did_return
@@ -922,6 +1944,8 @@ impl<'a> FuncGen<'a> {
             ast::Stmt::Block(b) => self.block_stmt(&b),
             ast::Stmt::ExprStmt(e) => self.expr_stmt(&e),
             ast::Stmt::For(f) => self.for_stmt(&f),
+            ast::Stmt::Break(b) => self.break_stmt(&b),
+            ast::Stmt::Continue(c) => self.continue_stmt(&c),
             ast::Stmt::If(i) => self.if_stmt(&i),
             ast::Stmt::Return(r) => self.return_stmt(&r),
             ast::Stmt::VarDecl(v) => self.var_decl_stmt(&v),
@@ -930,6 +1954,25 @@ impl<'a> FuncGen<'a> {
         }
     }
 
+    fn new(
+        symbol_name: String,
+        signature: ir::Signature,
+        ir_module: &'a mut ir::Module,
+        interned_file_name: StringRef,
+    ) -> Self {
+        Self {
+            str_map: &mut ir_module.string_map,
+            global_map: &mut ir_module.global_value_map,
+            bld: FuncBuilder::new(symbol_name.clone(), signature, &mut ir_module.source_locs),
+            symbol_name,
+            self_var: None,
+            loops: Vec::new(),
+            closures: 0,
+            pending: Vec::new(),
+            interned_file_name,
+        }
+    }
+
     fn generate(
         func: &Box<ast::Func>,
         ir_module: &'a mut ir::Module,
@@ -939,17 +1982,12 @@ impl<'a> FuncGen<'a> {
             ret_types: func.typ_.returns.iter().map(|t| t.clone().into()).collect(),
             parameters: func.typ_.params.iter().map(|t| t.clone().into()).collect(),
         };
-        let mut s = Self {
-            str_map: &mut ir_module.string_map,
-            global_map: &mut ir_module.global_value_map,
-            bld: FuncBuilder::new(
-                func.signature.symbol_name.clone(),
-                signature,
-                &mut ir_module.source_locs,
-            ),
-            self_var: None,
+        let mut s = Self::new(
+            func.signature.symbol_name.clone(),
+            signature,
+            ir_module,
             interned_file_name,
-        };
+        );
         s.bld.push_scope();
         // add params as variables for scope purposes
         for (p, sig_p) in func.signature.params.iter().zip(func.typ_.params.iter()) {
@@ -973,17 +2011,12 @@ impl<'a> FuncGen<'a> {
             parameters: func.typ_.params.iter().map(|t| t.clone().into()).collect(),
         };
         signature.parameters.insert(0, struct_type.clone().into());
-        let mut s = Self {
-            str_map: &mut ir_module.string_map,
-            global_map: &mut ir_module.global_value_map,
-            bld: FuncBuilder::new(
-                func.signature.symbol_name.clone(),
-                signature,
-                &mut ir_module.source_locs,
-            ),
-            self_var: None,
+        let mut s = Self::new(
+            func.signature.symbol_name.clone(),
+            signature,
+            ir_module,
             interned_file_name,
-        };
+        );
         s.bld.push_scope();
         // add params as variables for scope purposes
         s.self_var = Some(s.bld.create_var("".into(), struct_type.clone().into()));
@@ -997,40 +2030,338 @@ impl<'a> FuncGen<'a> {
         s
     }
 
+    // Calls a method on the receiver held in the closure object, the object is
+    // passed before the params like for closures
+    fn generate_bound_method(
+        symbol_name: &str,
+        receiver: &types::Type,
+        method: &str,
+        func_type: &types::FunctionType,
+        ir_module: &'a mut ir::Module,
+        interned_file_name: StringRef,
+    ) -> Self {
+        let mut signature = ir::Signature {
+            ret_types: func_type.returns.iter().map(|t| t.clone().into()).collect(),
+            parameters: func_type.params.iter().map(|t| t.clone().into()).collect(),
+        };
+        signature.parameters.insert(0, Type::Reference);
+        let parameters = signature.parameters.clone();
+        let mut s = Self::new(
+            format!("{}.bound", symbol_name),
+            signature.clone(),
+            ir_module,
+            interned_file_name,
+        );
+        let params = parameters
+            .into_iter()
+            .map(|typ| s.bld.create_temp(typ))
+            .collect::<Vec<_>>();
+        let object = params[0];
+        if types::is_interface(receiver) {
+            // the method is found through the vtable of the interface value
+            let interface = s.bld.create_temp(Type::Reference);
+            s.bld.load(object);
+            s.bld.get_object(1, Type::Reference);
+            s.bld.store(interface);
+            s.bld.load(interface);
+            s.bld.get_object(1, Type::Reference);
+            let idx = types::get_interface_func_index(receiver, method);
+            s.bld.get_object(idx + 1, Type::Reference);
+            s.bld.load(interface);
+            s.bld.get_object(0, Type::Reference);
+            for param in params.iter().skip(1) {
+                s.bld.load(*param);
+            }
+            s.bld.indirect_call(signature);
+        } else {
+            s.bld.load(object);
+            s.bld.get_object(1, receiver.clone().into());
+            for param in params.iter().skip(1) {
+                s.bld.load(*param);
+            }
+            s.bld.call(symbol_name.into());
+        }
+        s.bld.ret();
+        s
+    }
+
+    // The code of a closure, the closure object is passed before the params
+    // and the captured variables are copied out of it into locals
+    fn generate_closure(
+        symbol_name: String,
+        closure: &ast::Closure,
+        ir_module: &'a mut ir::Module,
+        interned_file_name: StringRef,
+    ) -> Self {
+        let mut signature = ir::Signature {
+            ret_types: closure.typ_.returns.iter().map(|t| t.clone().into()).collect(),
+            parameters: closure.typ_.params.iter().map(|t| t.clone().into()).collect(),
+        };
+        signature.parameters.insert(0, Type::Reference);
+        let mut s = Self::new(symbol_name, signature, ir_module, interned_file_name);
+        s.bld.push_scope();
+        let object = s.bld.create_temp(Type::Reference);
+        for (p, sig_p) in closure.params.iter().zip(closure.typ_.params.iter()) {
+            s.bld.create_var(p.id.clone(), sig_p.clone().into());
+        }
+        for (i, (id, typ)) in closure.captures.iter().enumerate() {
+            s.bld.load(object);
+            s.bld.get_object(i + 1, typ.clone().into());
+            let var_id = s.bld.create_var(id.clone(), typ.clone().into());
+            s.bld.store(var_id);
+            if id.is_empty() {
+                s.self_var = Some(var_id);
+            }
+        }
+        if !s.block_stmt(&closure.body) {
+            s.bld.ret();
+        }
+        s.bld.pop_scope();
+        s
+    }
+
+    // Lets a named function be called like a closure, the closure object is
+    // ignored and the params are passed straight through
+    fn generate_function_value(
+        symbol_name: &str,
+        func_type: &types::FunctionType,
+        ir_module: &'a mut ir::Module,
+        interned_file_name: StringRef,
+    ) -> Self {
+        let mut signature = ir::Signature {
+            ret_types: func_type.returns.iter().map(|t| t.clone().into()).collect(),
+            parameters: func_type.params.iter().map(|t| t.clone().into()).collect(),
+        };
+        signature.parameters.insert(0, Type::Reference);
+        let parameters = signature.parameters.clone();
+        let mut s = Self::new(
+            format!("{}.value", symbol_name),
+            signature,
+            ir_module,
+            interned_file_name,
+        );
+        let params = parameters
+            .into_iter()
+            .map(|typ| s.bld.create_temp(typ))
+            .collect::<Vec<_>>();
+        for param in params.iter().skip(1) {
+            s.bld.load(*param);
+        }
+        s.bld.call(symbol_name.into());
+        s.bld.ret();
+        s
+    }
+
+    // Stores the value of each global of a file in order
+    fn generate_globals(
+        package_id: &str,
+        file: &ast::File,
+        ir_module: &'a mut ir::Module,
+        interned_file_name: StringRef,
+    ) -> Self {
+        let signature = ir::Signature {
+            ret_types: vec![],
+            parameters: vec![],
+        };
+        let mut s = Self::new(
+            mangle::mangle_init_name(package_id, &file.id),
+            signature,
+            ir_module,
+            interned_file_name,
+        );
+        s.bld.push_scope();
+        for global in file.globals.iter() {
+            s.emit_source_loc(global.loc);
+            s.expr(&global.value);
+            let symbol_name = mangle::mangle_name(&types::NameSpecification {
+                package: package_id.into(),
+                file: file.id.clone(),
+                name: global.id.clone(),
+            });
+            s.bld.store_static(symbol_name.clone(), global.value.typ.clone().into());
+            s.bld.load_const_bool(true);
+            s.bld.store_static(mangle::mangle_initialised_name(&symbol_name), Type::Bool);
+        }
+        s.bld.ret();
+        s.bld.pop_scope();
+        s
+    }
+
     fn finish(self) -> Box<ir::Function> {
         self.bld.finish()
     }
 }
 
+// Generates the closures and function value wrappers used by the generated
+// functions, closures can create more closures, so keep going until none are left
+fn generate_pending(
+    mut pending: Vec<PendingFunc>,
+    wrapped: &mut HashSet<String>,
+    ir_module: &mut ir::Module,
+    interned_file_name: StringRef,
+) {
+    while let Some(pending_func) = pending.pop() {
+        let mut func_gen = match &pending_func {
+            PendingFunc::Wrapper(symbol_name, func_type) => {
+                if !wrapped.insert(symbol_name.clone()) {
+                    continue;
+                }
+                FuncGen::generate_function_value(
+                    symbol_name,
+                    func_type,
+                    ir_module,
+                    interned_file_name,
+                )
+            }
+            PendingFunc::BoundMethod(symbol_name, receiver, method, func_type) => {
+                if !wrapped.insert(format!("{}.bound", symbol_name)) {
+                    continue;
+                }
+                FuncGen::generate_bound_method(
+                    symbol_name,
+                    receiver,
+                    method,
+                    func_type,
+                    ir_module,
+                    interned_file_name,
+                )
+            }
+            PendingFunc::Closure(symbol_name, closure) => FuncGen::generate_closure(
+                symbol_name.clone(),
+                closure,
+                ir_module,
+                interned_file_name,
+            ),
+        };
+        pending.append(&mut func_gen.pending);
+        let ir_func = func_gen.finish();
+        ir_module.funcs.push(*ir_func);
+    }
+}
+
 pub fn emit_program(program: &ast::Program) -> Box<ir::Module> {
     let mut ir_module = ir::Module {
         string_map: StringMap::new(),
         funcs: vec![],
+        statics: vec![],
         source_locs: Default::default(),
         global_value_map: GlobalValueMap::new(),
     };
 
+    // named functions used as values only need one wrapper
+    let mut wrapped = HashSet::new();
+
     for package in program.packages.iter() {
         for file in package.files.iter() {
             let interned_file_name = ir_module.string_map.intern(&file.id);
-            for func in file.functions.iter() {
-                let ir_func = FuncGen::generate(func, &mut ir_module, interned_file_name).finish();
+            let mut pending = Vec::new();
+            for func in file.functions.iter().filter(|f| f.signature.type_params.is_empty()) {
+                let mut func_gen = FuncGen::generate(func, &mut ir_module, interned_file_name);
+                pending.append(&mut func_gen.pending);
+                let ir_func = func_gen.finish();
                 ir_module.funcs.push(*ir_func);
             }
-            for _struct in file.structs.iter() {
+            for _struct in file.structs.iter().filter(|s| s.type_params.is_empty()) {
                 for func in _struct.functions.iter() {
-                    let ir_func = FuncGen::generate_struct_func(
+                    let mut func_gen = FuncGen::generate_struct_func(
                         func,
                         &mut ir_module,
                         _struct.typ.clone(),
                         interned_file_name,
-                    )
-                    .finish();
+                    );
+                    pending.append(&mut func_gen.pending);
+                    let ir_func = func_gen.finish();
+                    ir_module.funcs.push(*ir_func);
+                }
+            }
+            for enum_ in file.enums.iter().filter(|e| e.type_params.is_empty()) {
+                for func in enum_.functions.iter() {
+                    let mut func_gen = FuncGen::generate_struct_func(
+                        func,
+                        &mut ir_module,
+                        enum_.typ.clone(),
+                        interned_file_name,
+                    );
+                    pending.append(&mut func_gen.pending);
+                    let ir_func = func_gen.finish();
+                    ir_module.funcs.push(*ir_func);
+                }
+            }
+            for extension in file.extensions.iter() {
+                for func in extension.functions.iter() {
+                    let mut func_gen = FuncGen::generate_struct_func(
+                        func,
+                        &mut ir_module,
+                        extension.typ.clone(),
+                        interned_file_name,
+                    );
+                    pending.append(&mut func_gen.pending);
+                    let ir_func = func_gen.finish();
                     ir_module.funcs.push(*ir_func);
                 }
             }
+            generate_pending(pending, &mut wrapped, &mut ir_module, interned_file_name);
+        }
+    }
+
+    // the globals of each file are initialised after the files it imports
+    let mut inits = Vec::new();
+    for (package_id, file_id) in program.init_order.iter() {
+        let package = program.packages.iter().find(|p| &p.id == package_id).unwrap();
+        let file = package.files.iter().find(|f| &f.id == file_id).unwrap();
+        if !file.globals.is_empty() {
+            for global in file.globals.iter() {
+                let id = mangle::mangle_name(&types::NameSpecification {
+                    package: package.id.clone(),
+                    file: file.id.clone(),
+                    name: global.id.clone(),
+                });
+                ir_module.statics.push(ir::Static {
+                    id: mangle::mangle_initialised_name(&id),
+                    typ: Type::Bool,
+                });
+                ir_module.statics.push(ir::Static {
+                    id,
+                    typ: global.value.typ.clone().into(),
+                });
+            }
+            let interned_file_name = ir_module.string_map.intern(&file.id);
+            let mut func_gen =
+                FuncGen::generate_globals(&package.id, file, &mut ir_module, interned_file_name);
+            let pending = std::mem::take(&mut func_gen.pending);
+            let ir_func = func_gen.finish();
+            inits.push(ir_func.id.clone());
+            ir_module.funcs.push(*ir_func);
+            generate_pending(pending, &mut wrapped, &mut ir_module, interned_file_name);
         }
     }
+    let signature = ir::Signature {
+        ret_types: vec![],
+        parameters: vec![],
+    };
+    let mut bld = FuncBuilder::new(mangle::INIT_NAME.into(), signature, &mut ir_module.source_locs);
+    for init in inits {
+        bld.call(init);
+    }
+    bld.ret();
+    ir_module.funcs.push(*bld.finish());
+
+    for instance in program.instances.iter() {
+        let interned_file_name = ir_module.string_map.intern(&instance.file);
+        let mut func_gen = match &instance.self_type {
+            Some(self_type) => FuncGen::generate_struct_func(
+                &instance.func,
+                &mut ir_module,
+                self_type.clone(),
+                interned_file_name,
+            ),
+            None => FuncGen::generate(&instance.func, &mut ir_module, interned_file_name),
+        };
+        let pending = std::mem::take(&mut func_gen.pending);
+        let ir_func = func_gen.finish();
+        ir_module.funcs.push(*ir_func);
+        generate_pending(pending, &mut wrapped, &mut ir_module, interned_file_name);
+    }
 
     //println!("Generated IR: {:#?}", ir_module);
 
@@ -0,0 +1,257 @@
+use std::collections::HashMap;
+
+use crate::compiler::ast;
+use crate::types::Type;
+
+// Generics are monomorphised, each instance is a copy of the generic
+// definition with its type parameters replaced by the type arguments. The
+// copy is then checked and generated like any other definition.
+
+pub type TypeArgs = HashMap<String, Type>;
+
+pub fn substitute_type(t: &mut ast::Type, args: &TypeArgs) {
+    match t {
+        ast::Type::Identifier(id) => {
+            if let Some(typ) = args.get(id) {
+                *t = ast::Type::Resolved(typ.clone());
+            }
+        }
+        ast::Type::Array(element_type) => substitute_type(element_type, args),
+        ast::Type::Map(key_type, value_type) => {
+            substitute_type(key_type, args);
+            substitute_type(value_type, args);
+        }
+        ast::Type::Tuple(types) => {
+            for typ in types.iter_mut() {
+                substitute_type(typ, args);
+            }
+        }
+        ast::Type::Function {
+            params,
+            return_type,
+        } => {
+            for param in params.iter_mut() {
+                substitute_type(param, args);
+            }
+            if let Some(return_type) = return_type {
+                substitute_type(return_type, args);
+            }
+        }
+        ast::Type::Generic(_, type_args) => {
+            for type_arg in type_args.iter_mut() {
+                substitute_type(type_arg, args);
+            }
+        }
+        // the name belongs to the other file, only its arguments can be type parameters
+        ast::Type::Qualified(_, typ) if matches!(**typ, ast::Type::Generic(..)) => {
+            substitute_type(typ, args)
+        }
+        _ => {}
+    }
+}
+
+pub fn substitute_signature(signature: &mut ast::FuncSignature, args: &TypeArgs) {
+    for param in signature.params.iter_mut() {
+        substitute_type(&mut param.type_annotation, args);
+    }
+    if let Some(return_type) = &mut signature.return_type {
+        substitute_type(return_type, args);
+    }
+}
+
+pub fn substitute_func(func: &mut ast::Func, args: &TypeArgs) {
+    substitute_signature(&mut func.signature, args);
+    substitute_block(&mut func.body, args);
+}
+
+fn substitute_block(b: &mut ast::BlockStmt, args: &TypeArgs) {
+    for s in b.stmts.iter_mut() {
+        substitute_stmt(s, args);
+    }
+}
+
+fn substitute_stmt(s: &mut ast::Stmt, args: &TypeArgs) {
+    match s {
+        ast::Stmt::If(i) => substitute_if(i, args),
+        ast::Stmt::Return(r) => {
+            if let Some(value) = &mut r.value {
+                substitute_expr(value, args);
+            }
+        }
+        ast::Stmt::VarDecl(v) => {
+            if let Some(annotation) = &mut v.type_annotation {
+                substitute_type(annotation, args);
+            }
+            substitute_expr(&mut v.value, args);
+        }
+        ast::Stmt::While(w) => {
+            substitute_expr(&mut w.condition, args);
+            substitute_stmt(&mut w.consequent, args);
+        }
+        ast::Stmt::Switch(s) => substitute_switch(s, args),
+        ast::Stmt::For(f) => {
+            substitute_expr(&mut f.iterator, args);
+            substitute_stmt(&mut f.consequent, args);
+        }
+        ast::Stmt::Block(b) => substitute_block(b, args),
+        ast::Stmt::ExprStmt(e) => substitute_expr(&mut e.expr, args),
+        ast::Stmt::Break(_) | ast::Stmt::Continue(_) => {}
+    }
+}
+
+fn substitute_if(i: &mut ast::IfStmt, args: &TypeArgs) {
+    substitute_expr(&mut i.test, args);
+    substitute_stmt(&mut i.consequent, args);
+    if let Some(alternate) = &mut i.alternate {
+        substitute_stmt(alternate, args);
+    }
+}
+
+fn substitute_switch(s: &mut ast::SwitchStmt, args: &TypeArgs) {
+    substitute_expr(&mut s.value, args);
+    for case in s.cases.iter_mut() {
+        substitute_pattern(&mut case.pattern, args);
+        if let Some(guard) = &mut case.guard {
+            substitute_expr(guard, args);
+        }
+        substitute_block(&mut case.block, args);
+    }
+}
+
+fn substitute_pattern(p: &mut ast::Pattern, args: &TypeArgs) {
+    match &mut p.kind {
+        ast::PatternKind::Type { annotation, .. } => substitute_type(annotation, args),
+        ast::PatternKind::Struct { annotation, fields } => {
+            substitute_type(annotation, args);
+            for field in fields.iter_mut() {
+                substitute_pattern(&mut field.pattern, args);
+            }
+        }
+        ast::PatternKind::EnumVariant { values, .. } => {
+            for value in values.iter_mut() {
+                substitute_pattern(value, args);
+            }
+        }
+        ast::PatternKind::Or(alternatives) => {
+            for alternative in alternatives.iter_mut() {
+                substitute_pattern(alternative, args);
+            }
+        }
+        _ => {}
+    }
+}
+
+pub fn substitute_expr(e: &mut ast::Expr, args: &TypeArgs) {
+    match &mut e.kind {
+        ast::ExprKind::BinaryExpr(b) => {
+            substitute_expr(&mut b.lhs, args);
+            substitute_expr(&mut b.rhs, args);
+        }
+        ast::ExprKind::UnaryExpr(u) => substitute_expr(&mut u.value, args),
+        ast::ExprKind::Assign(a) => {
+            substitute_expr(&mut a.destination, args);
+            substitute_expr(&mut a.value, args);
+        }
+        ast::ExprKind::CompoundAssign(a) => {
+            substitute_expr(&mut a.destination, args);
+            substitute_expr(&mut a.value, args);
+        }
+        ast::ExprKind::Call(c) => {
+            substitute_expr(&mut c.function, args);
+            for param in c.parameters.iter_mut() {
+                substitute_expr(param, args);
+            }
+        }
+        ast::ExprKind::Closure(c) => {
+            for param in c.params.iter_mut() {
+                substitute_type(&mut param.type_annotation, args);
+            }
+            if let Some(return_type) = &mut c.return_type {
+                substitute_type(return_type, args);
+            }
+            substitute_block(&mut c.body, args);
+        }
+        ast::ExprKind::Subscript(s) => {
+            substitute_expr(&mut s.value, args);
+            if let Some(index) = &mut s.index {
+                substitute_expr(index, args);
+            }
+            if let Some(index_end) = &mut s.index_end {
+                substitute_expr(index_end, args);
+            }
+        }
+        ast::ExprKind::Selector(s) => substitute_expr(&mut s.value, args),
+        ast::ExprKind::ArrayLiteral(a) => {
+            for literal in a.literals.iter_mut() {
+                substitute_expr(literal, args);
+            }
+        }
+        ast::ExprKind::TupleLiteral(t) => {
+            for value in t.values.iter_mut() {
+                substitute_expr(value, args);
+            }
+        }
+        ast::ExprKind::MapLiteral(m) => {
+            for (key, value) in m.entries.iter_mut() {
+                substitute_expr(key, args);
+                substitute_expr(value, args);
+            }
+        }
+        ast::ExprKind::ObjectLiteral(o) => {
+            for field in o.fields.iter_mut() {
+                substitute_expr(&mut field.value, args);
+            }
+        }
+        ast::ExprKind::Cast(c) => {
+            if let Some(annotation) = &mut c.annotation {
+                substitute_type(annotation, args);
+            }
+            substitute_expr(&mut c.value, args);
+        }
+        ast::ExprKind::Try(t) => substitute_expr(&mut t.value, args),
+        ast::ExprKind::If(i) => substitute_if(i, args),
+        ast::ExprKind::Switch(s) => substitute_switch(s, args),
+        ast::ExprKind::Template(t) => {
+            for expression in t.expressions.iter_mut() {
+                substitute_expr(expression, args);
+            }
+        }
+        ast::ExprKind::Integer(_)
+        | ast::ExprKind::Number(_)
+        | ast::ExprKind::StringLiteral(_)
+        | ast::ExprKind::Boolean(_)
+        | ast::ExprKind::Identifier(_)
+        | ast::ExprKind::FunctionRef(_)
+        | ast::ExprKind::MethodRef(_)
+        | ast::ExprKind::GlobalRef(_)
+        | ast::ExprKind::_Self => {}
+    }
+}
+
+pub fn mentions_type_params(t: &ast::Type, type_params: &[String]) -> bool {
+    match t {
+        ast::Type::Identifier(id) => type_params.contains(id),
+        ast::Type::Array(element_type) => mentions_type_params(element_type, type_params),
+        ast::Type::Map(key_type, value_type) => {
+            mentions_type_params(key_type, type_params)
+                || mentions_type_params(value_type, type_params)
+        }
+        ast::Type::Tuple(types) => types.iter().any(|t| mentions_type_params(t, type_params)),
+        ast::Type::Function {
+            params,
+            return_type,
+        } => {
+            params.iter().any(|p| mentions_type_params(p, type_params))
+                || return_type
+                    .iter()
+                    .any(|r| mentions_type_params(r, type_params))
+        }
+        ast::Type::Generic(_, type_args) => type_args
+            .iter()
+            .any(|a| mentions_type_params(a, type_params)),
+        ast::Type::Qualified(_, typ) if matches!(**typ, ast::Type::Generic(..)) => {
+            mentions_type_params(typ, type_params)
+        }
+        _ => false,
+    }
+}
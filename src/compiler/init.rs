@@ -0,0 +1,241 @@
+use std::collections::HashSet;
+
+use crate::compiler::{SourceLoc, ast};
+use crate::types;
+
+// `init` gets an object with only the defaults set, so the fields without a
+// zero value or a default have to be set on every path through it before the
+// object is used or returned. Paths through branches are joined by keeping
+// the fields set on all of them, loop bodies may not run so they never count.
+
+// How `init` used its object before every field was set, with the field
+pub enum Violation {
+    Return(String),
+    Use(String),
+    Missing(String),
+}
+
+type InitResult = Result<State, (Violation, SourceLoc)>;
+
+#[derive(Clone)]
+struct State {
+    assigned: HashSet<String>,
+    // the path left through return, break or continue, so it does not reach
+    // the statements after it
+    terminated: bool,
+}
+
+impl State {
+    fn join(self, other: State) -> State {
+        if self.terminated {
+            return other;
+        }
+        if other.terminated {
+            return self;
+        }
+        State {
+            assigned: self.assigned.intersection(&other.assigned).cloned().collect(),
+            terminated: false,
+        }
+    }
+}
+
+struct InitChecker<'a> {
+    fields: &'a [String],
+    required: &'a [String],
+}
+
+/// Checks that `init` sets the required fields on every path, `fields` are
+/// all the fields of the struct
+pub fn check(body: &ast::BlockStmt, fields: &[String], required: &[String]) -> Result<(), (Violation, SourceLoc)> {
+    let checker = InitChecker { fields, required };
+    let state = State {
+        assigned: HashSet::new(),
+        terminated: false,
+    };
+    let state = checker.block(body, state)?;
+    match checker.missing(&state) {
+        Some(field) if !state.terminated => Err((Violation::Missing(field), body.loc)),
+        _ => Ok(()),
+    }
+}
+
+impl<'a> InitChecker<'a> {
+    // The first required field which is not set yet
+    fn missing(&self, state: &State) -> Option<String> {
+        self.required
+            .iter()
+            .find(|f| !state.assigned.contains(*f))
+            .cloned()
+    }
+
+    fn use_self(&self, state: State, loc: SourceLoc) -> InitResult {
+        match self.missing(&state) {
+            Some(field) => Err((Violation::Use(field), loc)),
+            None => Ok(state),
+        }
+    }
+
+    fn block(&self, b: &ast::BlockStmt, mut state: State) -> InitResult {
+        for s in b.stmts.iter() {
+            if state.terminated {
+                break;
+            }
+            state = self.stmt(s, state)?;
+        }
+        Ok(state)
+    }
+
+    fn stmt(&self, s: &ast::Stmt, state: State) -> InitResult {
+        match s {
+            ast::Stmt::ExprStmt(e) => self.expr(&e.expr, state),
+            ast::Stmt::VarDecl(v) => self.expr(&v.value, state),
+            ast::Stmt::Return(r) => {
+                let mut state = match &r.value {
+                    Some(value) => self.expr(value, state)?,
+                    None => state,
+                };
+                if let Some(field) = self.missing(&state) {
+                    return Err((Violation::Return(field), r.loc));
+                }
+                state.terminated = true;
+                Ok(state)
+            }
+            ast::Stmt::Break(_) | ast::Stmt::Continue(_) => Ok(State {
+                terminated: true,
+                ..state
+            }),
+            ast::Stmt::Block(b) => self.block(b, state),
+            ast::Stmt::If(i) => self.if_stmt(i, state),
+            ast::Stmt::While(w) => {
+                let state = self.expr(&w.condition, state)?;
+                self.stmt(&w.consequent, state.clone())?;
+                Ok(state)
+            }
+            ast::Stmt::For(f) => {
+                let state = self.expr(&f.iterator, state)?;
+                self.stmt(&f.consequent, state.clone())?;
+                Ok(state)
+            }
+            ast::Stmt::Switch(s) => self.switch(s, state),
+        }
+    }
+
+    fn if_stmt(&self, i: &ast::IfStmt, state: State) -> InitResult {
+        let state = self.expr(&i.test, state)?;
+        let consequent = self.stmt(&i.consequent, state.clone())?;
+        let alternate = match &i.alternate {
+            Some(alternate) => self.stmt(alternate, state)?,
+            None => state,
+        };
+        Ok(consequent.join(alternate))
+    }
+
+    fn switch(&self, s: &ast::SwitchStmt, state: State) -> InitResult {
+        let state = self.expr(&s.value, state)?;
+        // the checker makes enum switches cover every variant
+        let exhaustive = types::is_enum(&s.value.typ)
+            || s.cases.iter().any(|c| {
+                c.guard.is_none()
+                    && matches!(c.pattern.kind, ast::PatternKind::CatchAll | ast::PatternKind::Binding(_))
+            });
+        let mut joined: Option<State> = None;
+        for case in s.cases.iter() {
+            let mut case_state = state.clone();
+            if let Some(guard) = &case.guard {
+                case_state = self.expr(guard, case_state)?;
+            }
+            let case_state = self.block(&case.block, case_state)?;
+            joined = Some(match joined {
+                Some(joined) => joined.join(case_state),
+                None => case_state,
+            });
+        }
+        match joined {
+            Some(joined) if exhaustive => Ok(joined),
+            Some(joined) => Ok(joined.join(state)),
+            None => Ok(state),
+        }
+    }
+
+    fn exprs<'e>(&self, exprs: impl Iterator<Item = &'e ast::Expr>, mut state: State) -> InitResult {
+        for e in exprs {
+            state = self.expr(e, state)?;
+        }
+        Ok(state)
+    }
+
+    fn expr(&self, e: &ast::Expr, state: State) -> InitResult {
+        match &e.kind {
+            ast::ExprKind::_Self => self.use_self(state, e.loc),
+            ast::ExprKind::Selector(s) if matches!(s.value.kind, ast::ExprKind::_Self) => {
+                let id = &s.selector.id;
+                if !self.fields.contains(id) {
+                    // a method, which can use any field
+                    return self.use_self(state, e.loc);
+                }
+                if self.required.contains(id) && !state.assigned.contains(id) {
+                    return Err((Violation::Use(id.clone()), e.loc));
+                }
+                Ok(state)
+            }
+            ast::ExprKind::Selector(s) => self.expr(&s.value, state),
+            ast::ExprKind::MethodRef(m) => self.expr(&m.value, state),
+            ast::ExprKind::Assign(a) => {
+                let mut state = self.expr(&a.value, state)?;
+                match &a.destination.kind {
+                    ast::ExprKind::Selector(s) if matches!(s.value.kind, ast::ExprKind::_Self) => {
+                        state.assigned.insert(s.selector.id.clone());
+                        Ok(state)
+                    }
+                    _ => self.expr(&a.destination, state),
+                }
+            }
+            ast::ExprKind::CompoundAssign(a) => {
+                let state = self.expr(&a.destination, state)?;
+                self.expr(&a.value, state)
+            }
+            ast::ExprKind::BinaryExpr(b) => {
+                let state = self.expr(&b.lhs, state)?;
+                self.expr(&b.rhs, state)
+            }
+            ast::ExprKind::UnaryExpr(u) => self.expr(&u.value, state),
+            ast::ExprKind::Call(c) => {
+                let mut state = self.expr(&c.function, state)?;
+                if let Some(init) = &c.init {
+                    state = self.expr(init, state)?;
+                }
+                self.exprs(c.parameters.iter(), state)
+            }
+            ast::ExprKind::Closure(c) => {
+                // the closure can run at any time, so capturing self uses it
+                if c.captures.iter().any(|(id, _)| id.is_empty()) {
+                    return self.use_self(state, e.loc);
+                }
+                Ok(state)
+            }
+            ast::ExprKind::Subscript(s) => {
+                let state = self.expr(&s.value, state)?;
+                self.exprs(s.index.iter().chain(s.index_end.iter()), state)
+            }
+            ast::ExprKind::ArrayLiteral(a) => self.exprs(a.literals.iter(), state),
+            ast::ExprKind::TupleLiteral(t) => self.exprs(t.values.iter(), state),
+            ast::ExprKind::MapLiteral(m) => {
+                self.exprs(m.entries.iter().flat_map(|(key, value)| [key, value]), state)
+            }
+            ast::ExprKind::ObjectLiteral(o) => self.exprs(o.fields.iter().map(|f| &f.value), state),
+            ast::ExprKind::Cast(c) => self.expr(&c.value, state),
+            ast::ExprKind::Try(t) => self.expr(&t.value, state),
+            ast::ExprKind::Template(t) => self.exprs(t.expressions.iter(), state),
+            ast::ExprKind::If(i) => self.if_stmt(i, state),
+            ast::ExprKind::Switch(s) => self.switch(s, state),
+            ast::ExprKind::Integer(_)
+            | ast::ExprKind::Number(_)
+            | ast::ExprKind::StringLiteral(_)
+            | ast::ExprKind::Boolean(_)
+            | ast::ExprKind::Identifier(_)
+            | ast::ExprKind::FunctionRef(_)
+            | ast::ExprKind::GlobalRef(_) => Ok(state),
+        }
+    }
+}
@@ -22,7 +22,41 @@ pub fn mangle_name(name_spec: &NameSpecification) -> String {
     )
 }
 
+// Instances of a generic function are told apart by their type arguments
+pub fn mangle_generic_name(name_spec: &NameSpecification, type_args: &[Type]) -> String {
+    format!(
+        "{}<{}>",
+        mangle_name(name_spec),
+        type_args
+            .iter()
+            .map(types::name)
+            .collect::<Vec<_>>()
+            .join(", ")
+    )
+}
+
+// Runs the initialisers of every global in the program, called before main
+pub const INIT_NAME: &str = "_L.init";
+
+// Initialises the globals of one file
+pub fn mangle_init_name(package: &str, file: &str) -> String {
+    format!(
+        "{}.init",
+        mangle_name(&NameSpecification {
+            package: package.into(),
+            file: file.into(),
+            name: String::new(),
+        })
+    )
+}
+
+// Set once the initialiser of a global has stored its value
+pub fn mangle_initialised_name(global: &str) -> String {
+    format!("{}.initialised", global)
+}
+
 pub fn mangle_method_name(id: &str, typ: &Type) -> String {
-    // For now, we will just mangle by prefixing with the package name, but in the future we will need to mangle generics and stuff too
+    // The type name includes the type arguments of generic instances, so each
+    // instance gets its own methods
     format!("_L_ms_{}_{}", types::name(typ), id)
 }
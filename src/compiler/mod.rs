@@ -9,8 +9,11 @@ use crate::builtins::Builtins;
 pub mod ast;
 pub mod checker;
 pub mod emit;
+pub mod generics;
+pub mod init;
 pub mod mangle;
 pub mod parser;
+pub mod patterns;
 pub mod source;
 pub mod token;
 pub mod tokeniser;
@@ -146,15 +149,13 @@ pub fn add_root_file(compiler: Arc<Mutex<Compiler>>, filename: &str) -> String {
 }
 
 pub fn add_std_package(compiler: Arc<Mutex<Compiler>>) {
-    let base_path = std::env::current_dir()
-        .unwrap()
-        .join("lib/std");
+    let base_path = std::env::current_dir().unwrap().join("lib/std");
     {
         let mut compiler_guard = compiler.lock().unwrap();
         let package = ensure_package(&mut compiler_guard.program, "std");
         package.base_path = Some(base_path);
     }
-}    
+}
 
 pub fn run_compiler(compiler: Arc<Mutex<Compiler>>, builtins: &Builtins) -> Box<ir::Module> {
     let compiler = Arc::clone(&compiler);
@@ -173,7 +174,10 @@ pub fn run_compiler(compiler: Arc<Mutex<Compiler>>, builtins: &Builtins) -> Box<
     // By this point we should be ok to maintain a mutable reference
     let mut compiler = compiler.lock().unwrap();
 
-    checker::check_program(&mut compiler.program, builtins).unwrap();
+    let warnings = checker::check_program(&mut compiler.program, builtins).unwrap();
+    for warning in warnings {
+        eprintln!("{}", warning);
+    }
 
     emit::emit_program(&compiler.program)
 }
@@ -16,6 +16,7 @@ pub enum ParserErrorReason {
     ExpectedExpression,
     ExpectedPattern,
     ExpectedTemplate,
+    ExpectedLoop,
 }
 
 #[derive(Debug)]
@@ -31,6 +32,8 @@ pub struct Parser<'a> {
     tokeniser: Tokeniser<'a>,
     mode: TokeniserMode,
     nest_level: i32,
+    // set when a `>>` closed two lists of type arguments at once
+    split_right_angle: bool,
 }
 
 impl<'a> Parser<'a> {
@@ -40,6 +43,7 @@ impl<'a> Parser<'a> {
             tokeniser: Tokeniser::new(contents),
             mode: TokeniserMode::Regex,
             nest_level: 0,
+            split_right_angle: false,
         }
     }
 
@@ -93,6 +97,40 @@ impl<'a> Parser<'a> {
         }
     }
 
+    // looks two tokens ahead for `identifier :`, which starts a labelled loop
+    fn test_label(&mut self) -> bool {
+        let mut tokeniser = self.tokeniser.clone();
+        let is_identifier = matches!(
+            tokeniser.next(self.mode),
+            Some(Token {
+                kind: TokenKind::Identifier,
+                ..
+            })
+        );
+        is_identifier
+            && matches!(
+                tokeniser.peek(self.mode),
+                Some(Token {
+                    kind: TokenKind::Punctuation(Punctuation::Colon),
+                    ..
+                })
+            )
+    }
+
+    // looks three tokens ahead for `. identifier {`, which makes the identifier
+    // before it the alias of a qualified object literal
+    fn test_qualified_object(&mut self) -> bool {
+        let mut tokeniser = self.tokeniser.clone();
+        let expected = [
+            TokenKind::Punctuation(Punctuation::Dot),
+            TokenKind::Identifier,
+            TokenKind::Punctuation(Punctuation::LeftBrace),
+        ];
+        expected
+            .into_iter()
+            .all(|kind| tokeniser.next(self.mode).is_some_and(|t| t.kind == kind))
+    }
+
     fn skip(&mut self) {
         _ = self.tokeniser.next(self.mode);
     }
@@ -108,47 +146,85 @@ impl<'a> Parser<'a> {
     pub fn parse_file(&mut self) -> ParserResult<Box<File>> {
         let mut file = Box::new(File::default());
 
-        while let Some(next) = self.tokeniser.peek(TokeniserMode::Regex) {
+        loop {
+            let exported = self.parse_export()?;
+            let Some(next) = self.tokeniser.peek(TokeniserMode::Regex) else {
+                if exported {
+                    return self.error(ParserErrorReason::UnexpectedEOF);
+                }
+                break;
+            };
             match next.kind {
-                TokenKind::Keyword(Keywords::Import) => {
+                TokenKind::Keyword(Keywords::Import) if !exported => {
                     file.imports.push(self.parse_import()?);
                 }
                 TokenKind::Keyword(Keywords::Func) => {
-                    let func = self.parse_function()?;
+                    let mut func = self.parse_function()?;
+                    func.exported = exported;
                     file.functions.push(func);
                 }
                 TokenKind::Keyword(Keywords::Struct) => {
-                    let struct_ = self.parse_struct()?;
+                    let mut struct_ = self.parse_struct()?;
+                    struct_.exported = exported;
                     file.structs.push(struct_);
                 }
                 TokenKind::Keyword(Keywords::Enum) => {
-                    let enum_ = self.parse_enum()?;
+                    let mut enum_ = self.parse_enum()?;
+                    enum_.exported = exported;
                     file.enums.push(enum_);
                 }
                 TokenKind::Keyword(Keywords::Interface) => {
-                    let interface = self.parse_interface()?;
+                    let mut interface = self.parse_interface()?;
+                    interface.exported = exported;
                     file.interfaces.push(interface);
                 }
+                TokenKind::Keyword(Keywords::Extension) if !exported => {
+                    let extension = self.parse_extension()?;
+                    file.extensions.push(extension);
+                }
+                TokenKind::Keyword(Keywords::Const) | TokenKind::Keyword(Keywords::Let) => {
+                    let mut global = self.parse_var_decl_statement()?;
+                    global.exported = exported;
+                    file.globals.push(global);
+                }
                 _ => return self.error(ParserErrorReason::ExpectedTopLevelDefinition),
             }
         }
         Ok(file)
     }
 
+    // `export` in front of a definition or a struct member makes it visible to
+    // other packages
+    fn parse_export(&mut self) -> ParserResult<bool> {
+        if self.test(TokenKind::Keyword(Keywords::Export)) {
+            self.next()?;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
     fn parse_import(&mut self) -> ParserResult<Import> {
         self.expect(TokenKind::Keyword(Keywords::Import))?;
         let path = self.expect(TokenKind::StringLiteral)?.get_string();
+        let alias = if self.test(TokenKind::Keyword(Keywords::As)) {
+            self.next()?;
+            Some(self.expect(TokenKind::Identifier)?.get_string())
+        } else {
+            None
+        };
         self.expect(TokenKind::Punctuation(Punctuation::SemiColon))?;
 
         if let Some((package, file)) = path.split_once(':') {
             Ok(Import {
                 package: package.to_string(),
                 file: file.to_string(),
+                alias,
             })
         } else {
             Ok(Import {
                 package: self.package.into(),
                 file: path,
+                alias,
             })
         }
     }
@@ -157,9 +233,12 @@ impl<'a> Parser<'a> {
         let loc = self.source_loc();
         self.expect(TokenKind::Keyword(Keywords::Interface))?;
         let id = self.expect(TokenKind::Identifier)?;
+        let type_params = self.parse_type_params()?;
         let mut interface = Box::new(Interface {
             loc,
+            exported: false,
             id: id.get_string(),
+            type_params,
             methods: Vec::new(),
             typ: types::Type::default(),
         });
@@ -177,14 +256,25 @@ impl<'a> Parser<'a> {
         let loc = self.source_loc();
         self.expect(TokenKind::Keyword(Keywords::Enum))?;
         let id = self.expect(TokenKind::Identifier)?;
+        let type_params = self.parse_type_params()?;
         let mut enum_ = Box::new(Enum {
             loc,
+            exported: false,
             id: id.get_string(),
+            type_params,
             variants: Vec::new(),
+            functions: Vec::new(),
             typ: types::Type::default(),
         });
         self.expect(TokenKind::Punctuation(Punctuation::LeftBrace))?;
         while !self.test(TokenKind::Punctuation(Punctuation::RightBrace)) {
+            if self.test(TokenKind::Keyword(Keywords::Export)) || self.test(TokenKind::Keyword(Keywords::Func)) {
+                let exported = self.parse_export()?;
+                let mut func = self.parse_function()?;
+                func.exported = exported;
+                enum_.functions.push(func);
+                continue;
+            }
             let variant_loc = self.source_loc();
             let variant_id_token = self.expect(TokenKind::Identifier)?;
             let variant_id = variant_id_token.get_string();
@@ -221,9 +311,12 @@ impl<'a> Parser<'a> {
         let loc = self.source_loc();
         self.expect(TokenKind::Keyword(Keywords::Struct))?;
         let id = self.expect(TokenKind::Identifier)?;
+        let type_params = self.parse_type_params()?;
         let mut struct_ = Box::new(Struct {
             loc,
+            exported: false,
             id: id.get_string(),
+            type_params,
             fields: Vec::new(),
             functions: Vec::new(),
             typ: types::Type::default(),
@@ -231,18 +324,28 @@ impl<'a> Parser<'a> {
         self.expect(TokenKind::Punctuation(Punctuation::LeftBrace))?;
         while !self.test(TokenKind::Punctuation(Punctuation::RightBrace)) {
             let loc = self.source_loc();
+            let exported = self.parse_export()?;
             if self.test(TokenKind::Keyword(Keywords::Func)) {
-                let func = self.parse_function()?;
+                let mut func = self.parse_function()?;
+                func.exported = exported;
                 struct_.functions.push(func);
             } else {
                 let field_id_token = self.expect(TokenKind::Identifier)?;
                 let field_id = field_id_token.get_string();
                 self.expect(TokenKind::Punctuation(Punctuation::Colon))?;
                 let field_type = self.parse_type()?;
+                let default = if self.test(TokenKind::Punctuation(Punctuation::Equals)) {
+                    self.next()?;
+                    Some(self.parse_expression()?)
+                } else {
+                    None
+                };
                 struct_.fields.push(StructField {
                     loc,
+                    exported,
                     id: field_id,
                     type_annotation: field_type,
+                    default,
                 });
                 if self.test(TokenKind::Punctuation(Punctuation::Comma)) {
                     self.expect(TokenKind::Punctuation(Punctuation::Comma))?;
@@ -255,19 +358,70 @@ impl<'a> Parser<'a> {
         Ok(struct_)
     }
 
+    pub fn parse_extension(&mut self) -> ParserResult<Box<Extension>> {
+        let loc = self.source_loc();
+        self.expect(TokenKind::Keyword(Keywords::Extension))?;
+        let annotation = self.parse_type()?;
+        let mut extension = Box::new(Extension {
+            loc,
+            annotation,
+            functions: Vec::new(),
+            typ: types::Type::default(),
+        });
+        self.expect(TokenKind::Punctuation(Punctuation::LeftBrace))?;
+        while !self.test(TokenKind::Punctuation(Punctuation::RightBrace)) {
+            let exported = self.parse_export()?;
+            let mut func = self.parse_function()?;
+            func.exported = exported;
+            extension.functions.push(func);
+        }
+        self.expect(TokenKind::Punctuation(Punctuation::RightBrace))?;
+        Ok(extension)
+    }
+
     pub fn parse_function_signature(&mut self) -> ParserResult<FuncSignature> {
         let mut signature = FuncSignature::default();
 
         self.expect(TokenKind::Keyword(Keywords::Func))?;
         let id = self.expect(TokenKind::Identifier)?;
         signature.id = id.get_string();
+        signature.type_params = self.parse_type_params()?;
+        let (params, return_type) = self.parse_function_params()?;
+        signature.params = params;
+        signature.return_type = return_type;
+        Ok(signature)
+    }
+
+    // Parses `<T, U>` after the name of a generic definition
+    fn parse_type_params(&mut self) -> ParserResult<Vec<String>> {
+        let mut type_params = Vec::new();
+        if !self.test(TokenKind::Punctuation(Punctuation::LeftAngle)) {
+            return Ok(type_params);
+        }
+        self.next()?;
+        while !self.test(TokenKind::Punctuation(Punctuation::RightAngle)) {
+            let id = self.expect(TokenKind::Identifier)?;
+            type_params.push(id.get_string());
+            if self.test(TokenKind::Punctuation(Punctuation::Comma)) {
+                self.expect(TokenKind::Punctuation(Punctuation::Comma))?;
+            } else {
+                break;
+            }
+        }
+        self.expect(TokenKind::Punctuation(Punctuation::RightAngle))?;
+        Ok(type_params)
+    }
+
+    // Parses `(a: int, b: int): int`, shared by functions and closures
+    fn parse_function_params(&mut self) -> ParserResult<(Vec<Param>, Option<Box<Type>>)> {
+        let mut params = Vec::new();
         self.expect(TokenKind::Punctuation(Punctuation::LeftParenthesis))?;
         while !self.test(TokenKind::Punctuation(Punctuation::RightParenthesis)) {
             let param_id = self.expect(TokenKind::Identifier)?;
             let param_id = param_id.get_string();
             self.expect(TokenKind::Punctuation(Punctuation::Colon))?;
             let param_type = self.parse_type()?;
-            signature.params.push(Param {
+            params.push(Param {
                 id: param_id,
                 type_annotation: param_type,
             });
@@ -278,12 +432,12 @@ impl<'a> Parser<'a> {
             }
         }
         self.expect(TokenKind::Punctuation(Punctuation::RightParenthesis))?;
+        let mut return_type = None;
         if self.test(TokenKind::Punctuation(Punctuation::Colon)) {
             self.expect(TokenKind::Punctuation(Punctuation::Colon))?;
-            let return_type = self.parse_type()?;
-            signature.return_type = Some(return_type);
+            return_type = Some(self.parse_type()?);
         }
-        Ok(signature)
+        Ok((params, return_type))
     }
 
     pub fn parse_function(&mut self) -> ParserResult<Box<Func>> {
@@ -309,20 +463,32 @@ impl<'a> Parser<'a> {
             let return_stmt = self.parse_return_statement()?;
             return Ok(Stmt::Return(return_stmt));
         } else if self.test(TokenKind::Keyword(Keywords::For)) {
-            todo!()
+            let for_ = self.parse_for()?;
+            return Ok(Stmt::For(for_));
         } else if self.test(TokenKind::Keyword(Keywords::While)) {
             let while_ = self.parse_while()?;
             return Ok(Stmt::While(while_));
         } else if self.test(TokenKind::Keyword(Keywords::Switch)) {
             let switch = self.parse_switch()?;
             return Ok(Stmt::Switch(switch));
+        } else if self.test(TokenKind::Keyword(Keywords::Break)) {
+            let break_ = self.parse_break()?;
+            return Ok(Stmt::Break(break_));
+        } else if self.test(TokenKind::Keyword(Keywords::Continue)) {
+            let continue_ = self.parse_continue()?;
+            return Ok(Stmt::Continue(continue_));
+        } else if self.test_label() {
+            return self.parse_labelled_loop();
         } else if self.test(TokenKind::Punctuation(Punctuation::LeftBrace)) {
             let block = self.parse_block_statement()?;
             return Ok(Stmt::Block(block));
         } else {
             let loc = self.source_loc();
             let expr = self.parse_expression()?;
-            self.expect(TokenKind::Punctuation(Punctuation::SemiColon))?;
+            // the expression a block ends with can leave out the semicolon
+            if !self.test(TokenKind::Punctuation(Punctuation::RightBrace)) {
+                self.expect(TokenKind::Punctuation(Punctuation::SemiColon))?;
+            }
             return Ok(Stmt::ExprStmt(Box::new(ExprStmt { loc, expr })));
         }
     }
@@ -365,11 +531,73 @@ impl<'a> Parser<'a> {
         let consequent = self.parse_statement()?;
         Ok(Box::new(WhileStmt {
             loc,
+            label: None,
             condition,
             consequent,
         }))
     }
 
+    fn parse_labelled_loop(&mut self) -> ParserResult<Stmt> {
+        let label = self.expect(TokenKind::Identifier)?.get_string();
+        self.expect(TokenKind::Punctuation(Punctuation::Colon))?;
+        if self.test(TokenKind::Keyword(Keywords::For)) {
+            let mut for_ = self.parse_for()?;
+            for_.label = Some(label);
+            Ok(Stmt::For(for_))
+        } else if self.test(TokenKind::Keyword(Keywords::While)) {
+            let mut while_ = self.parse_while()?;
+            while_.label = Some(label);
+            Ok(Stmt::While(while_))
+        } else {
+            self.error(ParserErrorReason::ExpectedLoop)
+        }
+    }
+
+    fn parse_break(&mut self) -> ParserResult<Box<BreakStmt>> {
+        let loc = self.source_loc();
+        self.expect(TokenKind::Keyword(Keywords::Break))?;
+        let label = if self.test(TokenKind::Identifier) {
+            Some(self.expect(TokenKind::Identifier)?.get_string())
+        } else {
+            None
+        };
+        self.expect(TokenKind::Punctuation(Punctuation::SemiColon))?;
+        Ok(Box::new(BreakStmt { loc, label }))
+    }
+
+    fn parse_continue(&mut self) -> ParserResult<Box<ContinueStmt>> {
+        let loc = self.source_loc();
+        self.expect(TokenKind::Keyword(Keywords::Continue))?;
+        let label = if self.test(TokenKind::Identifier) {
+            Some(self.expect(TokenKind::Identifier)?.get_string())
+        } else {
+            None
+        };
+        self.expect(TokenKind::Punctuation(Punctuation::SemiColon))?;
+        Ok(Box::new(ContinueStmt { loc, label }))
+    }
+
+    fn parse_for(&mut self) -> ParserResult<Box<ForStmt>> {
+        let loc = self.source_loc();
+        self.expect(TokenKind::Keyword(Keywords::For))?;
+        let id = self.expect(TokenKind::Identifier)?.get_string();
+        self.expect(TokenKind::Keyword(Keywords::In))?;
+        let old_nest_level = self.nest_level;
+        self.nest_level = -1;
+        let iterator = self.parse_expression()?;
+        self.nest_level = old_nest_level;
+        let consequent = self.parse_statement()?;
+        Ok(Box::new(ForStmt {
+            loc,
+            label: None,
+            id,
+            iterator,
+            consequent,
+            next_call: None,
+            some_idx: 0,
+        }))
+    }
+
     fn parse_switch(&mut self) -> ParserResult<Box<SwitchStmt>> {
         let loc = self.source_loc();
         self.expect(TokenKind::Keyword(Keywords::Switch))?;
@@ -382,6 +610,12 @@ impl<'a> Parser<'a> {
         self.expect(TokenKind::Punctuation(Punctuation::LeftBrace))?;
         while !self.test(TokenKind::Punctuation(Punctuation::RightBrace)) {
             let pattern = self.parse_pattern()?;
+            let guard = if self.test(TokenKind::Keyword(Keywords::If)) {
+                self.next()?;
+                Some(self.parse_expression()?)
+            } else {
+                None
+            };
             self.expect(TokenKind::Punctuation(Punctuation::Colon))?;
             let block = self.parse_block_statement()?;
             if self.test(TokenKind::Punctuation(Punctuation::Comma)) {
@@ -389,8 +623,8 @@ impl<'a> Parser<'a> {
             }
             cases.push(CaseStmt {
                 pattern,
+                guard,
                 block,
-                case_idx: 0,
             });
         }
         self.expect(TokenKind::Punctuation(Punctuation::RightBrace))?;
@@ -419,8 +653,24 @@ impl<'a> Parser<'a> {
             self.expect(TokenKind::Keyword(Keywords::Let))?;
             false
         };
-        let id_token = self.expect(TokenKind::Identifier)?;
-        let id = id_token.get_string();
+        let mut destructure = None;
+        let id = if self.test(TokenKind::Punctuation(Punctuation::LeftParenthesis)) {
+            self.next()?;
+            let mut ids = Vec::new();
+            loop {
+                ids.push(self.expect(TokenKind::Identifier)?.get_string());
+                if self.test(TokenKind::Punctuation(Punctuation::Comma)) {
+                    self.expect(TokenKind::Punctuation(Punctuation::Comma))?;
+                } else {
+                    break;
+                }
+            }
+            self.expect(TokenKind::Punctuation(Punctuation::RightParenthesis))?;
+            destructure = Some(ids);
+            String::new()
+        } else {
+            self.expect(TokenKind::Identifier)?.get_string()
+        };
         let type_annotation = if self.test(TokenKind::Punctuation(Punctuation::Colon)) {
             self.expect(TokenKind::Punctuation(Punctuation::Colon))?;
             Some(self.parse_type()?)
@@ -433,7 +683,9 @@ impl<'a> Parser<'a> {
         Ok(Box::new(VarDeclStmt {
             loc,
             is_const,
+            exported: false,
             id,
+            destructure,
             type_annotation,
             value,
         }))
@@ -466,9 +718,15 @@ impl<'a> Parser<'a> {
             | TokenKind::Punctuation(Punctuation::LeftAngleEquals)
             | TokenKind::Punctuation(Punctuation::RightAngleEquals) => 2,
             TokenKind::Punctuation(Punctuation::Plus)
-            | TokenKind::Punctuation(Punctuation::Minus) => 3,
+            | TokenKind::Punctuation(Punctuation::Minus)
+            | TokenKind::Punctuation(Punctuation::Bar)
+            | TokenKind::Punctuation(Punctuation::Caret) => 3,
             TokenKind::Punctuation(Punctuation::Multiply)
-            | TokenKind::Punctuation(Punctuation::ForwardSlash) => 4,
+            | TokenKind::Punctuation(Punctuation::ForwardSlash)
+            | TokenKind::Punctuation(Punctuation::Percentage)
+            | TokenKind::Punctuation(Punctuation::And)
+            | TokenKind::Punctuation(Punctuation::LeftAngleLeftAngle)
+            | TokenKind::Punctuation(Punctuation::RightAngleRightAngle) => 4,
             _ => 0,
         }
     }
@@ -479,6 +737,16 @@ impl<'a> Parser<'a> {
             TokenKind::Punctuation(Punctuation::Minus) => Ok(BinaryExprKind::Subtract),
             TokenKind::Punctuation(Punctuation::Multiply) => Ok(BinaryExprKind::Multiply),
             TokenKind::Punctuation(Punctuation::ForwardSlash) => Ok(BinaryExprKind::Divide),
+            TokenKind::Punctuation(Punctuation::Percentage) => Ok(BinaryExprKind::Modulo),
+            TokenKind::Punctuation(Punctuation::And) => Ok(BinaryExprKind::BitwiseAnd),
+            TokenKind::Punctuation(Punctuation::Bar) => Ok(BinaryExprKind::BitwiseOr),
+            TokenKind::Punctuation(Punctuation::Caret) => Ok(BinaryExprKind::BitwiseXor),
+            TokenKind::Punctuation(Punctuation::LeftAngleLeftAngle) => {
+                Ok(BinaryExprKind::ShiftLeft)
+            }
+            TokenKind::Punctuation(Punctuation::RightAngleRightAngle) => {
+                Ok(BinaryExprKind::ShiftRight)
+            }
             TokenKind::Punctuation(Punctuation::EqualsEquals) => Ok(BinaryExprKind::Equal),
             TokenKind::Punctuation(Punctuation::ExclamationEquals) => Ok(BinaryExprKind::NotEqual),
             TokenKind::Punctuation(Punctuation::LeftAngle) => Ok(BinaryExprKind::LessThan),
@@ -495,8 +763,87 @@ impl<'a> Parser<'a> {
         }
     }
 
+    fn parse_compound_assign_kind(token: &Token) -> Option<BinaryExprKind> {
+        match token.kind {
+            TokenKind::Punctuation(Punctuation::PlusEquals) => Some(BinaryExprKind::Add),
+            TokenKind::Punctuation(Punctuation::MinusEquals) => Some(BinaryExprKind::Subtract),
+            TokenKind::Punctuation(Punctuation::MultiplyEquals) => Some(BinaryExprKind::Multiply),
+            TokenKind::Punctuation(Punctuation::ForwardSlashEquals) => Some(BinaryExprKind::Divide),
+            TokenKind::Punctuation(Punctuation::PercentageEquals) => Some(BinaryExprKind::Modulo),
+            TokenKind::Punctuation(Punctuation::AndEquals) => Some(BinaryExprKind::BitwiseAnd),
+            TokenKind::Punctuation(Punctuation::BarEquals) => Some(BinaryExprKind::BitwiseOr),
+            TokenKind::Punctuation(Punctuation::CaretEquals) => Some(BinaryExprKind::BitwiseXor),
+            TokenKind::Punctuation(Punctuation::LeftAngleLeftAngleEquals) => {
+                Some(BinaryExprKind::ShiftLeft)
+            }
+            TokenKind::Punctuation(Punctuation::RightAngleRightAngleEquals) => {
+                Some(BinaryExprKind::ShiftRight)
+            }
+            _ => None,
+        }
+    }
+
+    fn parse_unary_op_kind(token: &Token) -> Option<UnaryExprKind> {
+        match token.kind {
+            TokenKind::Punctuation(Punctuation::Minus) => Some(UnaryExprKind::Negate),
+            TokenKind::Punctuation(Punctuation::Exclamation) => Some(UnaryExprKind::LogicalNot),
+            TokenKind::Punctuation(Punctuation::Tilde) => Some(UnaryExprKind::BitwiseNot),
+            _ => None,
+        }
+    }
+
+    fn parse_unary_expr(&mut self) -> ParserResult<Expr> {
+        let kind = match self.tokeniser.peek(self.mode) {
+            Some(token) => Self::parse_unary_op_kind(&token),
+            None => None,
+        };
+        if let Some(kind) = kind {
+            let token = self.next()?;
+            let value = self.parse_unary_expr()?;
+            let expr = ExprKind::UnaryExpr(Box::new(UnaryExpr { value, kind }));
+            Ok(self.expr(expr, token.loc))
+        } else {
+            self.parse_left_hand_side_expr()
+        }
+    }
+
+    // `value as type`, binds tighter than any binary operator
+    fn parse_cast_expr(&mut self) -> ParserResult<Expr> {
+        let mut expr = self.parse_unary_expr()?;
+        while self.test(TokenKind::Keyword(Keywords::As)) {
+            let token = self.next()?;
+            let optional = self.test(TokenKind::Punctuation(Punctuation::QuestionMark));
+            if optional {
+                self.skip();
+            }
+            let wrapping = match self.tokeniser.peek(self.mode) {
+                Some(next) => {
+                    !optional
+                        && next.kind == TokenKind::Identifier
+                        && next.get_string() == "wrapping"
+                }
+                None => false,
+            };
+            if wrapping {
+                self.skip();
+            }
+            let annotation = self.parse_type()?;
+            expr = self.expr(
+                ExprKind::Cast(Box::new(Cast {
+                    value: expr,
+                    target_type: types::Type::default(),
+                    annotation: Some(annotation),
+                    wrapping,
+                    optional,
+                })),
+                token.loc,
+            );
+        }
+        Ok(expr)
+    }
+
     fn parse_bin_expr(&mut self, prec: u8) -> ParserResult<Expr> {
-        let mut lhs = self.parse_left_hand_side_expr()?;
+        let mut lhs = self.parse_cast_expr()?;
         loop {
             let token = match self.tokeniser.peek(self.mode) {
                 Some(token) => token,
@@ -523,13 +870,19 @@ impl<'a> Parser<'a> {
                 self.next()?;
                 if self.test(TokenKind::Punctuation(Punctuation::Colon)) {
                     self.next()?;
-                    let index_end = if self.test(TokenKind::Punctuation(Punctuation::RightBracket)) {
+                    let index_end = if self.test(TokenKind::Punctuation(Punctuation::RightBracket))
+                    {
                         None
                     } else {
                         Some(self.parse_expression()?)
                     };
                     expr = self.expr(
-                        ExprKind::Subscript(Box::new(Subscript { value: expr, index: None, is_slice: true, index_end })),
+                        ExprKind::Subscript(Box::new(Subscript {
+                            value: expr,
+                            index: None,
+                            is_slice: true,
+                            index_end,
+                        })),
                         loc,
                     );
                     self.expect(TokenKind::Punctuation(Punctuation::RightBracket))?;
@@ -541,14 +894,20 @@ impl<'a> Parser<'a> {
                     if self.test(TokenKind::Punctuation(Punctuation::Colon)) {
                         self.next()?;
                         is_slice = true;
-                        index_end = if self.test(TokenKind::Punctuation(Punctuation::RightBracket)) {
+                        index_end = if self.test(TokenKind::Punctuation(Punctuation::RightBracket))
+                        {
                             None
                         } else {
                             Some(self.parse_expression()?)
                         };
                     }
                     expr = self.expr(
-                        ExprKind::Subscript(Box::new(Subscript { value: expr, index: Some(index), is_slice, index_end })),
+                        ExprKind::Subscript(Box::new(Subscript {
+                            value: expr,
+                            index: Some(index),
+                            is_slice,
+                            index_end,
+                        })),
                         loc,
                     );
                 }
@@ -563,10 +922,63 @@ impl<'a> Parser<'a> {
                     })),
                     loc,
                 );
+            } else if let Some(kind) = self
+                .tokeniser
+                .peek(self.mode)
+                .and_then(|token| Self::parse_compound_assign_kind(&token))
+            {
+                self.next()?;
+                let value = self.parse_expression()?;
+                expr = self.expr(
+                    ExprKind::CompoundAssign(Box::new(CompoundAssign {
+                        destination: expr,
+                        value,
+                        kind,
+                        postfix: false,
+                    })),
+                    loc,
+                );
+            } else if self.test(TokenKind::Punctuation(Punctuation::PlusPlus))
+                || self.test(TokenKind::Punctuation(Punctuation::MinusMinus))
+            {
+                let token = self.next()?;
+                let kind = if token.kind == TokenKind::Punctuation(Punctuation::PlusPlus) {
+                    BinaryExprKind::Add
+                } else {
+                    BinaryExprKind::Subtract
+                };
+                let value = self.expr(ExprKind::Integer(Box::new(Integer { value: 1 })), token.loc);
+                expr = self.expr(
+                    ExprKind::CompoundAssign(Box::new(CompoundAssign {
+                        destination: expr,
+                        value,
+                        kind,
+                        postfix: true,
+                    })),
+                    loc,
+                );
+            } else if self.test(TokenKind::Punctuation(Punctuation::QuestionMark)) {
+                self.next()?;
+                expr = self.expr(
+                    ExprKind::Try(Box::new(Try {
+                        value: expr,
+                        success_idx: 0,
+                    })),
+                    loc,
+                );
             } else if self.test(TokenKind::Punctuation(Punctuation::Dot)) {
                 self.next()?;
-                let id_token = self.expect(TokenKind::Identifier)?;
-                let id = id_token.get_string();
+                // `pair.0` selects a value of a tuple, the index is read on its
+                // own so `pair.0.1` is not read as a number
+                let old_mode = self.mode;
+                self.mode = TokeniserMode::TupleIndex;
+                let id = if self.test(TokenKind::IntegerLiteral) {
+                    self.next().map(|t| t.get_int().to_string())
+                } else {
+                    self.expect(TokenKind::Identifier).map(|t| t.get_string())
+                };
+                self.mode = old_mode;
+                let id = id?;
                 expr = self.expr(
                     ExprKind::Selector(Box::new(Selector {
                         value: expr,
@@ -595,6 +1007,9 @@ impl<'a> Parser<'a> {
                         parameters,
                         symbol_name: None,
                         enum_idx: None,
+                        init: None,
+                        array_method: None,
+                        map_method: None,
                     })),
                     loc,
                 );
@@ -606,9 +1021,21 @@ impl<'a> Parser<'a> {
 
     fn parse_primary_expr(&mut self) -> ParserResult<Expr> {
         if self.test(TokenKind::Punctuation(Punctuation::LeftParenthesis)) {
+            let loc = self.source_loc();
             self.next()?;
             self.nest_level += 1;
             let expr = self.parse_expression()?;
+            // a comma after the first value makes this a tuple
+            if self.test(TokenKind::Punctuation(Punctuation::Comma)) {
+                let mut values = vec![expr];
+                while self.test(TokenKind::Punctuation(Punctuation::Comma)) {
+                    self.expect(TokenKind::Punctuation(Punctuation::Comma))?;
+                    values.push(self.parse_expression()?);
+                }
+                self.expect(TokenKind::Punctuation(Punctuation::RightParenthesis))?;
+                self.nest_level -= 1;
+                return Ok(self.expr(ExprKind::TupleLiteral(Box::new(TupleLiteral { values })), loc));
+            }
             self.expect(TokenKind::Punctuation(Punctuation::RightParenthesis))?;
             self.nest_level -= 1;
             return Ok(expr);
@@ -675,35 +1102,16 @@ impl<'a> Parser<'a> {
             let token = self.next()?;
             let id = token.get_string();
 
+            // test for object literal of an imported type, `alias.Type { ... }`
+            if self.nest_level >= 0 && self.test_qualified_object() {
+                self.expect(TokenKind::Punctuation(Punctuation::Dot))?;
+                let type_id = self.expect(TokenKind::Identifier)?.get_string();
+                return self.parse_object_literal(Some(id), type_id, token.loc);
+            }
+
             // test for object literal
             if self.nest_level >= 0 && self.test(TokenKind::Punctuation(Punctuation::LeftBrace)) {
-                self.next()?;
-                let mut fields = Vec::new();
-                while !self.test(TokenKind::Punctuation(Punctuation::RightBrace)) {
-                    let field_loc = self.source_loc();
-                    let field_id_token = self.expect(TokenKind::Identifier)?;
-                    let field_id = field_id_token.get_string();
-                    self.expect(TokenKind::Punctuation(Punctuation::Colon))?;
-                    let field_value = self.parse_expression()?;
-                    fields.push(ObjectLiteralField {
-                        loc: field_loc,
-                        id: field_id,
-                        value: field_value,
-                    });
-                    if self.test(TokenKind::Punctuation(Punctuation::Comma)) {
-                        self.expect(TokenKind::Punctuation(Punctuation::Comma))?;
-                    } else {
-                        break;
-                    }
-                }
-                self.expect(TokenKind::Punctuation(Punctuation::RightBrace))?;
-                return Ok(self.expr(
-                    ExprKind::ObjectLiteral(Box::new(ObjectLiteral {
-                        id: Some(Identifier { id }),
-                        fields,
-                    })),
-                    token.loc,
-                ));
+                return self.parse_object_literal(None, id, token.loc);
             } else {
                 return Ok(self.expr(ExprKind::Identifier(Box::new(Identifier { id })), token.loc));
             }
@@ -728,11 +1136,57 @@ impl<'a> Parser<'a> {
                 loc: token.loc,
                 typ: types::bool(),
             });
+        } else if self.test(TokenKind::Keyword(Keywords::Func)) {
+            let token = self.next()?;
+            let (params, return_type) = self.parse_function_params()?;
+            // the body is a new statement context, object literals are allowed
+            // again even when the closure is inside an if condition
+            let old_nest_level = self.nest_level;
+            self.nest_level = 0;
+            let body = self.parse_block_statement()?;
+            self.nest_level = old_nest_level;
+            return Ok(self.expr(
+                ExprKind::Closure(Box::new(Closure {
+                    loc: token.loc,
+                    params,
+                    return_type,
+                    body,
+                    typ_: types::FunctionType::default(),
+                    captures: Vec::new(),
+                })),
+                token.loc,
+            ));
+        } else if self.test(TokenKind::Keyword(Keywords::If)) {
+            let loc = self.source_loc();
+            let old_nest_level = self.nest_level;
+            self.nest_level = 0;
+            let if_ = self.parse_if()?;
+            self.nest_level = old_nest_level;
+            return Ok(self.expr(ExprKind::If(if_), loc));
+        } else if self.test(TokenKind::Keyword(Keywords::Switch)) {
+            let loc = self.source_loc();
+            let old_nest_level = self.nest_level;
+            self.nest_level = 0;
+            let switch = self.parse_switch()?;
+            self.nest_level = old_nest_level;
+            return Ok(self.expr(ExprKind::Switch(switch), loc));
         } else if self.test(TokenKind::Punctuation(Punctuation::LeftBracket)) {
             let token = self.next()?;
+            if self.test(TokenKind::Punctuation(Punctuation::Colon)) {
+                self.next()?;
+                self.expect(TokenKind::Punctuation(Punctuation::RightBracket))?;
+                return Ok(self.expr(
+                    ExprKind::MapLiteral(Box::new(MapLiteral { entries: Vec::new() })),
+                    token.loc,
+                ));
+            }
             let mut literals = Vec::new();
             while !self.test(TokenKind::Punctuation(Punctuation::RightBracket)) {
                 let literal = self.parse_expression()?;
+                // the first entry decides if this is a map literal
+                if literals.is_empty() && self.test(TokenKind::Punctuation(Punctuation::Colon)) {
+                    return self.parse_map_literal(literal, token.loc);
+                }
                 literals.push(literal);
                 if self.test(TokenKind::Punctuation(Punctuation::Comma)) {
                     self.expect(TokenKind::Punctuation(Punctuation::Comma))?;
@@ -795,6 +1249,25 @@ impl<'a> Parser<'a> {
 
     fn parse_pattern(&mut self) -> ParserResult<Box<Pattern>> {
         let loc = self.source_loc();
+        let pattern = self.parse_single_pattern()?;
+        if !self.test(TokenKind::Punctuation(Punctuation::Bar)) {
+            return Ok(pattern);
+        }
+
+        let mut alternatives = vec![*pattern];
+        while self.test(TokenKind::Punctuation(Punctuation::Bar)) {
+            self.next()?;
+            alternatives.push(*self.parse_single_pattern()?);
+        }
+        Ok(Box::new(Pattern {
+            loc,
+            kind: PatternKind::Or(alternatives),
+            typ: types::bad(),
+        }))
+    }
+
+    fn parse_single_pattern(&mut self) -> ParserResult<Box<Pattern>> {
+        let loc = self.source_loc();
 
         let kind = if self.test(TokenKind::Punctuation(Punctuation::Underscore)) {
             self.next()?;
@@ -814,6 +1287,19 @@ impl<'a> Parser<'a> {
             let token = self.next()?;
             let value = token.get_string();
             PatternKind::String(value)
+        } else if self.test(TokenKind::Keyword(Keywords::Case)) {
+            self.next()?;
+            let annotation = self.parse_type()?;
+            let binding = if self.test(TokenKind::Identifier) {
+                Some(self.next()?.get_string())
+            } else {
+                None
+            };
+            PatternKind::Type {
+                annotation,
+                binding,
+                typ: types::bad(),
+            }
         } else if self.test(TokenKind::Punctuation(Punctuation::Dot)) {
             self.expect(TokenKind::Punctuation(Punctuation::Dot))?;
             let id_token = self.expect(TokenKind::Identifier)?;
@@ -823,8 +1309,7 @@ impl<'a> Parser<'a> {
             if self.test(TokenKind::Punctuation(Punctuation::LeftParenthesis)) {
                 self.next()?;
                 while !self.test(TokenKind::Punctuation(Punctuation::RightParenthesis)) {
-                    let value_token = self.expect(TokenKind::Identifier)?;
-                    values.push((value_token.get_string(), types::bad()));
+                    values.push(*self.parse_pattern()?);
                     if self.test(TokenKind::Punctuation(Punctuation::Comma)) {
                         self.next()?;
                     } else {
@@ -834,12 +1319,64 @@ impl<'a> Parser<'a> {
                 self.expect(TokenKind::Punctuation(Punctuation::RightParenthesis))?;
             }
 
-            PatternKind::EnumVariant { id, values }
+            PatternKind::EnumVariant { id, values, idx: 0 }
+        } else if self.test(TokenKind::Identifier) {
+            let id = self.next()?.get_string();
+            // a name on its own binds the value, followed by braces it is a struct
+            if self.test(TokenKind::Punctuation(Punctuation::LeftAngle)) {
+                PatternKind::Struct {
+                    annotation: self.parse_type_args(id)?,
+                    fields: self.parse_field_patterns()?,
+                }
+            } else if self.test(TokenKind::Punctuation(Punctuation::LeftBrace)) {
+                PatternKind::Struct {
+                    annotation: Box::new(Type::Identifier(id)),
+                    fields: self.parse_field_patterns()?,
+                }
+            } else {
+                PatternKind::Binding(id)
+            }
         } else {
             return self.error(ParserErrorReason::ExpectedPattern);
         };
 
-        Ok(Box::new(Pattern { loc, kind }))
+        Ok(Box::new(Pattern {
+            loc,
+            kind,
+            typ: types::bad(),
+        }))
+    }
+
+    fn parse_field_patterns(&mut self) -> ParserResult<Vec<FieldPattern>> {
+        let mut fields = Vec::new();
+        self.expect(TokenKind::Punctuation(Punctuation::LeftBrace))?;
+        while !self.test(TokenKind::Punctuation(Punctuation::RightBrace)) {
+            let loc = self.source_loc();
+            let id = self.expect(TokenKind::Identifier)?.get_string();
+            // `{ x }` is short for `{ x: x }`
+            let pattern = if self.test(TokenKind::Punctuation(Punctuation::Colon)) {
+                self.next()?;
+                *self.parse_pattern()?
+            } else {
+                Pattern {
+                    loc,
+                    kind: PatternKind::Binding(id.clone()),
+                    typ: types::bad(),
+                }
+            };
+            fields.push(FieldPattern {
+                id,
+                idx: 0,
+                pattern,
+            });
+            if self.test(TokenKind::Punctuation(Punctuation::Comma)) {
+                self.next()?;
+            } else {
+                break;
+            }
+        }
+        self.expect(TokenKind::Punctuation(Punctuation::RightBrace))?;
+        Ok(fields)
     }
 
     /////////////////////////////
@@ -847,6 +1384,46 @@ impl<'a> Parser<'a> {
     /////////////////////////////
 
     fn parse_type(&mut self) -> ParserResult<Box<Type>> {
+        if self.test(TokenKind::Keyword(Keywords::Func)) {
+            self.tokeniser.next(self.mode);
+            self.expect(TokenKind::Punctuation(Punctuation::LeftParenthesis))?;
+            let mut params = Vec::new();
+            while !self.test(TokenKind::Punctuation(Punctuation::RightParenthesis)) {
+                params.push(*self.parse_type()?);
+                if self.test(TokenKind::Punctuation(Punctuation::Comma)) {
+                    self.expect(TokenKind::Punctuation(Punctuation::Comma))?;
+                } else {
+                    break;
+                }
+            }
+            self.expect(TokenKind::Punctuation(Punctuation::RightParenthesis))?;
+            let return_type = if self.test(TokenKind::Punctuation(Punctuation::Colon)) {
+                self.expect(TokenKind::Punctuation(Punctuation::Colon))?;
+                Some(self.parse_type()?)
+            } else {
+                None
+            };
+            return Ok(Box::new(Type::Function {
+                params,
+                return_type,
+            }));
+        }
+
+        // `(int, string)` is a tuple, a single type in parentheses is just that type
+        if self.test(TokenKind::Punctuation(Punctuation::LeftParenthesis)) {
+            self.next()?;
+            let mut types = vec![*self.parse_type()?];
+            while self.test(TokenKind::Punctuation(Punctuation::Comma)) {
+                self.expect(TokenKind::Punctuation(Punctuation::Comma))?;
+                types.push(*self.parse_type()?);
+            }
+            self.expect(TokenKind::Punctuation(Punctuation::RightParenthesis))?;
+            if types.len() == 1 {
+                return Ok(Box::new(types.remove(0)));
+            }
+            return Ok(Box::new(Type::Tuple(types)));
+        }
+
         if self.test(TokenKind::Punctuation(Punctuation::LeftBracket)) {
             self.tokeniser.next(self.mode);
             self.expect(TokenKind::Punctuation(Punctuation::RightBracket))?;
@@ -866,10 +1443,105 @@ impl<'a> Parser<'a> {
             return Ok(Box::new(Type::Byte));
         } else if string == "number" {
             return Ok(Box::new(Type::Number));
+        } else if string == "map" && self.test(TokenKind::Punctuation(Punctuation::LeftBracket)) {
+            self.next()?;
+            let key_type = self.parse_type()?;
+            self.expect(TokenKind::Punctuation(Punctuation::RightBracket))?;
+            let value_type = self.parse_type()?;
+            return Ok(Box::new(Type::Map(key_type, value_type)));
+        } else if self.test(TokenKind::Punctuation(Punctuation::LeftAngle)) {
+            return self.parse_type_args(string);
+        } else if self.test(TokenKind::Punctuation(Punctuation::Dot)) {
+            self.next()?;
+            let id = self.expect(TokenKind::Identifier)?.get_string();
+            let typ = if self.test(TokenKind::Punctuation(Punctuation::LeftAngle)) {
+                self.parse_type_args(id)?
+            } else {
+                Box::new(Type::Identifier(id))
+            };
+            return Ok(Box::new(Type::Qualified(string, typ)));
         } else {
             return Ok(Box::new(Type::Identifier(string)));
         }
     }
+
+    // Parses the rest of a map literal after its first key
+    fn parse_map_literal(&mut self, first_key: Expr, loc: SourceLoc) -> ParserResult<Expr> {
+        let mut entries = Vec::new();
+        let mut key = first_key;
+        loop {
+            self.expect(TokenKind::Punctuation(Punctuation::Colon))?;
+            let value = self.parse_expression()?;
+            entries.push((key, value));
+            if self.test(TokenKind::Punctuation(Punctuation::Comma)) {
+                self.expect(TokenKind::Punctuation(Punctuation::Comma))?;
+            } else {
+                break;
+            }
+            if self.test(TokenKind::Punctuation(Punctuation::RightBracket)) {
+                break;
+            }
+            key = self.parse_expression()?;
+        }
+        self.expect(TokenKind::Punctuation(Punctuation::RightBracket))?;
+        Ok(self.expr(ExprKind::MapLiteral(Box::new(MapLiteral { entries })), loc))
+    }
+
+    // Parses the fields of an object literal after the name of its type, the
+    // alias is set for `alias.Type { ... }`
+    fn parse_object_literal(&mut self, alias: Option<String>, id: String, loc: SourceLoc) -> ParserResult<Expr> {
+        self.expect(TokenKind::Punctuation(Punctuation::LeftBrace))?;
+        let mut fields = Vec::new();
+        while !self.test(TokenKind::Punctuation(Punctuation::RightBrace)) {
+            let field_loc = self.source_loc();
+            let field_id_token = self.expect(TokenKind::Identifier)?;
+            let field_id = field_id_token.get_string();
+            self.expect(TokenKind::Punctuation(Punctuation::Colon))?;
+            let field_value = self.parse_expression()?;
+            fields.push(ObjectLiteralField {
+                loc: field_loc,
+                id: field_id,
+                value: field_value,
+            });
+            if self.test(TokenKind::Punctuation(Punctuation::Comma)) {
+                self.expect(TokenKind::Punctuation(Punctuation::Comma))?;
+            } else {
+                break;
+            }
+        }
+        self.expect(TokenKind::Punctuation(Punctuation::RightBrace))?;
+        Ok(self.expr(
+            ExprKind::ObjectLiteral(Box::new(ObjectLiteral {
+                alias,
+                id: Some(Identifier { id }),
+                fields,
+            })),
+            loc,
+        ))
+    }
+
+    fn parse_type_args(&mut self, id: String) -> ParserResult<Box<Type>> {
+        self.expect(TokenKind::Punctuation(Punctuation::LeftAngle))?;
+        let mut args = Vec::new();
+        loop {
+            args.push(*self.parse_type()?);
+            if self.test(TokenKind::Punctuation(Punctuation::Comma)) {
+                self.expect(TokenKind::Punctuation(Punctuation::Comma))?;
+            } else {
+                break;
+            }
+        }
+        // `Option<Option<int>>` ends with a single `>>` token closing both lists
+        if self.split_right_angle {
+            self.split_right_angle = false;
+        } else if self.test(TokenKind::Punctuation(Punctuation::RightAngleRightAngle)) {
+            self.next()?;
+            self.split_right_angle = true;
+        } else {
+            self.expect(TokenKind::Punctuation(Punctuation::RightAngle))?;
+        }
+        Ok(Box::new(Type::Generic(id, args)))
+    }
 }
 
 mod tests {
@@ -885,20 +1557,59 @@ mod tests {
         assert_eq!(file.structs[0].id, "MyStruct");
     }
 
+    #[test]
+    fn test_parse_globals() {
+        use crate::compiler::parser::Parser;
+
+        let mut parser = Parser::new("testing", "const PORT = 8080; let count: int = 0; func main() {}");
+        let file = parser.parse_file().unwrap();
+        assert_eq!(file.globals.len(), 2);
+        assert_eq!(file.globals[0].id, "PORT");
+        assert!(file.globals[0].is_const);
+        assert_eq!(file.globals[1].id, "count");
+        assert!(!file.globals[1].is_const);
+        assert!(file.globals[1].type_annotation.is_some());
+        assert_eq!(file.functions.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_export() {
+        use crate::compiler::parser::Parser;
+
+        let mut parser = Parser::new(
+            "testing",
+            "export struct Point { export x: int, y: int, export func length(): int { return 0; } func hidden() {} } export const ORIGIN = 0; func helper() {}",
+        );
+        let file = parser.parse_file().unwrap();
+        let point = &file.structs[0];
+        assert!(point.exported);
+        assert!(point.fields[0].exported);
+        assert!(!point.fields[1].exported);
+        assert!(point.functions[0].exported);
+        assert!(!point.functions[1].exported);
+        assert!(file.globals[0].exported);
+        assert!(!file.functions[0].exported);
+
+        let mut parser = Parser::new("testing", "export import \"utils.luna\";");
+        assert!(parser.parse_file().is_err());
+    }
+
     #[test]
     fn test_parse_imports() {
         use crate::compiler::parser::Parser;
 
         let mut parser = Parser::new(
             "testing",
-            "import \"std:math/core.luna\"; import \"utils.luna\"; func main() {}",
+            "import \"std:math/core.luna\"; import \"utils.luna\" as utils; func main() {}",
         );
         let file = parser.parse_file().unwrap();
         assert_eq!(file.imports.len(), 2);
         assert_eq!(file.imports[0].package, "std");
         assert_eq!(file.imports[0].file, "math/core.luna");
+        assert_eq!(file.imports[0].alias, None);
         assert_eq!(file.imports[1].package, "testing");
         assert_eq!(file.imports[1].file, "utils.luna");
+        assert_eq!(file.imports[1].alias.as_deref(), Some("utils"));
     }
 
     #[test]
@@ -943,6 +1654,17 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_struct_field_default() {
+        use crate::compiler::parser::Parser;
+
+        let mut parser = Parser::new("testing", "struct MyStruct { field1: int = 1, field2: int }");
+        let struct_ = parser.parse_struct().unwrap();
+        assert_eq!(struct_.fields.len(), 2);
+        assert!(struct_.fields[0].default.is_some());
+        assert!(struct_.fields[1].default.is_none());
+    }
+
     #[test]
     fn test_parse_struct_with_function() {
         use crate::compiler::ast;
@@ -983,6 +1705,20 @@ mod tests {
         assert_eq!(enum_.variants[1].variant_types.len(), 1);
     }
 
+    #[test]
+    fn test_parse_enum_with_function() {
+        use crate::compiler::parser::Parser;
+
+        let mut parser = Parser::new(
+            "testing",
+            "enum MyEnum { Variant1, Variant2(int), func method(): int { return 1; } }",
+        );
+        let enum_ = parser.parse_enum().unwrap();
+        assert_eq!(enum_.variants.len(), 2);
+        assert_eq!(enum_.functions.len(), 1);
+        assert_eq!(enum_.functions[0].signature.id, "method");
+    }
+
     #[test]
     fn test_parse_interface() {
         use crate::compiler::parser::Parser;
@@ -1003,6 +1739,26 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_extension() {
+        use crate::compiler::parser::Parser;
+
+        let mut parser = Parser::new(
+            "testing",
+            "extension []byte { func first(): byte { return self[0]; } func empty(): bool { return self.length == 0; } }",
+        );
+        let extension = parser.parse_extension().unwrap();
+        assert_eq!(
+            extension.annotation,
+            Box::new(crate::compiler::ast::Type::Array(Box::new(
+                crate::compiler::ast::Type::Byte
+            )))
+        );
+        assert_eq!(extension.functions.len(), 2);
+        assert_eq!(extension.functions[0].signature.id, "first");
+        assert_eq!(extension.functions[1].signature.id, "empty");
+    }
+
     #[test]
     fn test_parse_if() {
         use crate::compiler::parser::Parser;
@@ -1033,6 +1789,208 @@ mod tests {
         //assert_eq!(while_.consequent.stmts.len(), 0);
     }
 
+    #[test]
+    fn test_parse_for() {
+        use crate::compiler::parser::Parser;
+        let mut parser = Parser::new("testing", "for x in values {}");
+        let for_ = parser.parse_for().unwrap();
+        assert_eq!(for_.id, "x");
+        if let crate::compiler::ast::ExprKind::Identifier(i) = &for_.iterator.kind {
+            assert_eq!(i.id, "values");
+        } else {
+            panic!("Expected identifier");
+        }
+    }
+
+    #[test]
+    fn test_parse_labelled_loop() {
+        use crate::compiler::ast::Stmt;
+        use crate::compiler::parser::Parser;
+        let mut parser = Parser::new("testing", "outer: while true { break outer; }");
+        let stmt = parser.parse_statement().unwrap();
+        if let Stmt::While(w) = &stmt {
+            assert_eq!(w.label.as_deref(), Some("outer"));
+            if let Stmt::Block(b) = &w.consequent
+                && let Stmt::Break(b) = &b.stmts[0]
+            {
+                assert_eq!(b.label.as_deref(), Some("outer"));
+            } else {
+                panic!("Expected break");
+            }
+        } else {
+            panic!("Expected while");
+        }
+    }
+
+    #[test]
+    fn test_parse_unary() {
+        use crate::compiler::ast::{BinaryExprKind, ExprKind, UnaryExprKind};
+        use crate::compiler::parser::Parser;
+        // unary operators bind tighter than binary ones
+        let mut parser = Parser::new("testing", "-a + !b");
+        let expr = parser.parse_expression().unwrap();
+        if let ExprKind::BinaryExpr(b) = &expr.kind {
+            assert!(matches!(b.kind, BinaryExprKind::Add));
+            assert!(
+                matches!(&b.lhs.kind, ExprKind::UnaryExpr(u) if matches!(u.kind, UnaryExprKind::Negate))
+            );
+            assert!(
+                matches!(&b.rhs.kind, ExprKind::UnaryExpr(u) if matches!(u.kind, UnaryExprKind::LogicalNot))
+            );
+        } else {
+            panic!("Expected binary expression");
+        }
+    }
+
+    #[test]
+    fn test_parse_closure() {
+        use crate::compiler::ast::{ExprKind, Type};
+        use crate::compiler::parser::Parser;
+        let mut parser = Parser::new("testing", "func(x: int): int { return x + y; }");
+        let expr = parser.parse_expression().unwrap();
+        if let ExprKind::Closure(c) = &expr.kind {
+            assert_eq!(c.params.len(), 1);
+            assert_eq!(c.params[0].id, "x");
+            assert_eq!(c.return_type.as_deref(), Some(&Type::Integer));
+            assert_eq!(c.body.stmts.len(), 1);
+        } else {
+            panic!("Expected closure");
+        }
+    }
+
+    #[test]
+    fn test_parse_cast() {
+        use crate::compiler::ast::{BinaryExprKind, ExprKind, Type};
+        use crate::compiler::parser::Parser;
+        let mut parser = Parser::new("testing", "a + b as wrapping byte");
+        let expr = parser.parse_expression().unwrap();
+        if let ExprKind::BinaryExpr(b) = &expr.kind {
+            assert!(matches!(b.kind, BinaryExprKind::Add));
+            if let ExprKind::Cast(c) = &b.rhs.kind {
+                assert_eq!(c.annotation.as_deref(), Some(&Type::Byte));
+                assert!(c.wrapping);
+            } else {
+                panic!("Expected cast");
+            }
+        } else {
+            panic!("Expected binary expression");
+        }
+
+        let mut parser = Parser::new("testing", "-a as number");
+        let expr = parser.parse_expression().unwrap();
+        assert!(
+            matches!(&expr.kind, ExprKind::Cast(c) if !c.wrapping && matches!(c.value.kind, ExprKind::UnaryExpr(_)))
+        );
+
+        let mut parser = Parser::new("testing", "shape as? Circle");
+        let expr = parser.parse_expression().unwrap();
+        assert!(
+            matches!(&expr.kind, ExprKind::Cast(c) if c.optional && c.annotation.as_deref() == Some(&Type::Identifier("Circle".into())))
+        );
+    }
+
+    #[test]
+    fn test_parse_try() {
+        use crate::compiler::ast::{BinaryExprKind, ExprKind};
+        use crate::compiler::parser::Parser;
+        let mut parser = Parser::new("testing", "parse(a)? + 1");
+        let expr = parser.parse_expression().unwrap();
+        if let ExprKind::BinaryExpr(b) = &expr.kind {
+            assert!(matches!(b.kind, BinaryExprKind::Add));
+            assert!(
+                matches!(&b.lhs.kind, ExprKind::Try(t) if matches!(t.value.kind, ExprKind::Call(_)))
+            );
+        } else {
+            panic!("Expected binary expression");
+        }
+    }
+
+    #[test]
+    fn test_parse_map_literal() {
+        use crate::compiler::ast::ExprKind;
+        use crate::compiler::parser::Parser;
+        let mut parser = Parser::new("testing", "[\"a\": 1, \"b\": 2,]");
+        let expr = parser.parse_expression().unwrap();
+        if let ExprKind::MapLiteral(m) = &expr.kind {
+            assert_eq!(m.entries.len(), 2);
+            assert!(matches!(m.entries[1].0.kind, ExprKind::StringLiteral(_)));
+            assert!(matches!(&m.entries[1].1.kind, ExprKind::Integer(i) if i.value == 2));
+        } else {
+            panic!("Expected map literal");
+        }
+
+        let mut parser = Parser::new("testing", "[:]");
+        let expr = parser.parse_expression().unwrap();
+        assert!(matches!(&expr.kind, ExprKind::MapLiteral(m) if m.entries.is_empty()));
+
+        let mut parser = Parser::new("testing", "[1, 2]");
+        let expr = parser.parse_expression().unwrap();
+        assert!(matches!(&expr.kind, ExprKind::ArrayLiteral(a) if a.literals.len() == 2));
+    }
+
+    #[test]
+    fn test_parse_qualified_object_literal() {
+        use crate::compiler::ast::ExprKind;
+        use crate::compiler::parser::Parser;
+        let mut parser = Parser::new("testing", "net.Address { port: 80 }");
+        let expr = parser.parse_expression().unwrap();
+        if let ExprKind::ObjectLiteral(o) = &expr.kind {
+            assert_eq!(o.alias.as_deref(), Some("net"));
+            assert_eq!(o.id.as_ref().unwrap().id, "Address");
+            assert_eq!(o.fields.len(), 1);
+        } else {
+            panic!("Expected object literal");
+        }
+
+        // a selector which is not followed by a brace stays a selector
+        let mut parser = Parser::new("testing", "net.port");
+        let expr = parser.parse_expression().unwrap();
+        assert!(matches!(&expr.kind, ExprKind::Selector(_)));
+    }
+
+    #[test]
+    fn test_parse_chained_tuple_index() {
+        use crate::compiler::ast::ExprKind;
+        use crate::compiler::parser::Parser;
+        let mut parser = Parser::new("testing", "t.0.1");
+        let expr = parser.parse_expression().unwrap();
+        let ExprKind::Selector(outer) = &expr.kind else {
+            panic!("Expected selector");
+        };
+        assert_eq!(outer.selector.id, "1");
+        assert!(matches!(&outer.value.kind, ExprKind::Selector(inner) if inner.selector.id == "0"));
+
+        let mut parser = Parser::new("testing", "t.0.show()");
+        let expr = parser.parse_expression().unwrap();
+        assert!(matches!(&expr.kind, ExprKind::Call(_)));
+
+        // numbers elsewhere keep their decimals
+        let mut parser = Parser::new("testing", "t + 0.5");
+        let expr = parser.parse_expression().unwrap();
+        assert!(matches!(&expr.kind, ExprKind::BinaryExpr(b) if matches!(b.rhs.kind, ExprKind::Number(_))));
+    }
+
+    #[test]
+    fn test_parse_compound_assign() {
+        use crate::compiler::ast::{BinaryExprKind, ExprKind};
+        use crate::compiler::parser::Parser;
+        let mut parser = Parser::new("testing", "a <<= 2");
+        let expr = parser.parse_expression().unwrap();
+        assert!(
+            matches!(&expr.kind, ExprKind::CompoundAssign(a) if matches!(a.kind, BinaryExprKind::ShiftLeft))
+        );
+
+        let mut parser = Parser::new("testing", "a.b--");
+        let expr = parser.parse_expression().unwrap();
+        if let ExprKind::CompoundAssign(a) = &expr.kind {
+            assert!(matches!(a.kind, BinaryExprKind::Subtract));
+            assert!(matches!(a.destination.kind, ExprKind::Selector(_)));
+            assert!(matches!(&a.value.kind, ExprKind::Integer(i) if i.value == 1));
+        } else {
+            panic!("Expected compound assignment");
+        }
+    }
+
     #[test]
     fn test_parse_pattern() {
         use crate::compiler::parser::Parser;
@@ -1070,18 +2028,74 @@ mod tests {
             switch.cases[0].pattern.kind,
             crate::compiler::ast::PatternKind::EnumVariant {
                 id: "Variant1".into(),
-                values: vec![]
+                values: vec![],
+                idx: 0
+            }
+        );
+        if let crate::compiler::ast::PatternKind::EnumVariant { id, values, .. } =
+            &switch.cases[1].pattern.kind
+        {
+            assert_eq!(id, "Variant2");
+            assert_eq!(values.len(), 1);
+            assert_eq!(
+                values[0].kind,
+                crate::compiler::ast::PatternKind::Binding("value".into())
+            );
+        } else {
+            panic!("Expected enum variant pattern");
+        }
+
+        let mut parser = Parser::new("testing", "switch x { case Circle c: {} case int: {} }");
+        let switch = parser.parse_switch().unwrap();
+        assert_eq!(
+            switch.cases[0].pattern.kind,
+            crate::compiler::ast::PatternKind::Type {
+                annotation: Box::new(crate::compiler::ast::Type::Identifier("Circle".into())),
+                binding: Some("c".into()),
+                typ: types::bad()
             }
         );
         assert_eq!(
             switch.cases[1].pattern.kind,
-            crate::compiler::ast::PatternKind::EnumVariant {
-                id: "Variant2".into(),
-                values: vec![("value".into(), types::bad())]
+            crate::compiler::ast::PatternKind::Type {
+                annotation: Box::new(crate::compiler::ast::Type::Integer),
+                binding: None,
+                typ: types::bad()
             }
         );
     }
 
+    #[test]
+    fn test_parse_nested_patterns() {
+        use crate::compiler::ast::PatternKind;
+        use crate::compiler::parser::Parser;
+
+        let mut parser = Parser::new(
+            "testing",
+            "switch x { .A | .B: {} .Some(.Ok(v)) if v > 3: {} Point { x: 0, y }: {} }",
+        );
+        let switch = parser.parse_switch().unwrap();
+        assert_eq!(switch.cases.len(), 3);
+        assert!(matches!(&switch.cases[0].pattern.kind, PatternKind::Or(a) if a.len() == 2));
+        assert!(switch.cases[0].guard.is_none());
+
+        if let PatternKind::EnumVariant { id, values, .. } = &switch.cases[1].pattern.kind {
+            assert_eq!(id, "Some");
+            assert!(matches!(&values[0].kind, PatternKind::EnumVariant { id, .. } if id == "Ok"));
+        } else {
+            panic!("Expected enum variant pattern");
+        }
+        assert!(switch.cases[1].guard.is_some());
+
+        if let PatternKind::Struct { fields, .. } = &switch.cases[2].pattern.kind {
+            assert_eq!(fields.len(), 2);
+            assert_eq!(fields[0].pattern.kind, PatternKind::Integer(0));
+            assert_eq!(fields[1].pattern.kind, PatternKind::Binding("y".into()));
+        } else {
+            panic!("Expected struct pattern");
+        }
+    }
+
     #[test]
     fn test_parse_block() {
         use crate::compiler::parser::Parser;
@@ -1096,6 +2110,33 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_if_switch_expr() {
+        use crate::compiler::ast;
+        use crate::compiler::parser::Parser;
+
+        let mut parser = Parser::new("testing", "let x = if y { 1 } else { 2 };");
+        let var_decl = parser.parse_var_decl_statement().unwrap();
+        if let ast::ExprKind::If(if_) = &var_decl.value.kind {
+            assert!(if_.alternate.is_some());
+            if let ast::Stmt::Block(block) = &if_.consequent {
+                assert!(matches!(block.stmts[0], ast::Stmt::ExprStmt(_)));
+            } else {
+                panic!("Expected block");
+            }
+        } else {
+            panic!("Expected if expression");
+        }
+
+        let mut parser = Parser::new("testing", "let x = switch y { .A: { 1 } _: { 2 } };");
+        let var_decl = parser.parse_var_decl_statement().unwrap();
+        if let ast::ExprKind::Switch(switch) = &var_decl.value.kind {
+            assert_eq!(switch.cases.len(), 2);
+        } else {
+            panic!("Expected switch expression");
+        }
+    }
+
     #[test]
     fn test_parse_var_decl() {
         use crate::compiler::ast;
@@ -1115,6 +2156,31 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_tuple() {
+        use crate::compiler::ast;
+        use crate::compiler::parser::Parser;
+
+        let mut parser = Parser::new("testing", "let (q, r): (int, int) = (7 / 2, 7 % 2);");
+        let var_decl = parser.parse_var_decl_statement().unwrap();
+        assert_eq!(var_decl.destructure, Some(vec!["q".to_string(), "r".to_string()]));
+        assert_eq!(
+            var_decl.type_annotation.unwrap(),
+            Box::new(ast::Type::Tuple(vec![ast::Type::Integer, ast::Type::Integer]))
+        );
+        assert!(matches!(&var_decl.value.kind, ast::ExprKind::TupleLiteral(t) if t.values.len() == 2));
+
+        // a single value in parentheses is not a tuple
+        let mut parser = Parser::new("testing", "(int) (a)");
+        assert_eq!(parser.parse_type().unwrap(), Box::new(ast::Type::Integer));
+        let expr = parser.parse_expression().unwrap();
+        assert!(matches!(expr.kind, ast::ExprKind::Identifier(_)));
+
+        let mut parser = Parser::new("testing", "pair.1");
+        let expr = parser.parse_expression().unwrap();
+        assert!(matches!(&expr.kind, ast::ExprKind::Selector(s) if s.selector.id == "1"));
+    }
+
     #[test]
     fn test_parse_return() {
         use crate::compiler::parser::Parser;
@@ -1150,6 +2216,81 @@ mod tests {
         assert_eq!(ty, Box::new(ast::Type::Array(Box::new(ast::Type::String))));
         let ty = parser.parse_type().unwrap();
         assert_eq!(ty, Box::new(ast::Type::Identifier("myStruct".into())));
+
+        let mut parser = Parser::new("testing", "map[string][]int map");
+        let ty = parser.parse_type().unwrap();
+        assert_eq!(
+            ty,
+            Box::new(ast::Type::Map(
+                Box::new(ast::Type::String),
+                Box::new(ast::Type::Array(Box::new(ast::Type::Integer)))
+            ))
+        );
+        // without the brackets it is just a name
+        let ty = parser.parse_type().unwrap();
+        assert_eq!(ty, Box::new(ast::Type::Identifier("map".into())));
+
+        let mut parser = Parser::new("testing", "net.Listener result.Option<int>");
+        let ty = parser.parse_type().unwrap();
+        assert_eq!(
+            ty,
+            Box::new(ast::Type::Qualified(
+                "net".into(),
+                Box::new(ast::Type::Identifier("Listener".into()))
+            ))
+        );
+        let ty = parser.parse_type().unwrap();
+        assert_eq!(
+            ty,
+            Box::new(ast::Type::Qualified(
+                "result".into(),
+                Box::new(ast::Type::Generic("Option".into(), vec![ast::Type::Integer]))
+            ))
+        );
+
+        let mut parser = Parser::new("testing", "func(int, string): bool func()");
+        let ty = parser.parse_type().unwrap();
+        assert_eq!(
+            ty,
+            Box::new(ast::Type::Function {
+                params: vec![ast::Type::Integer, ast::Type::String],
+                return_type: Some(Box::new(ast::Type::Bool)),
+            })
+        );
+        let ty = parser.parse_type().unwrap();
+        assert_eq!(
+            ty,
+            Box::new(ast::Type::Function {
+                params: vec![],
+                return_type: None,
+            })
+        );
+
+        let mut parser = Parser::new("testing", "Pair<int, []T> Option<Option<int>> bool");
+        let ty = parser.parse_type().unwrap();
+        assert_eq!(
+            ty,
+            Box::new(ast::Type::Generic(
+                "Pair".into(),
+                vec![
+                    ast::Type::Integer,
+                    ast::Type::Array(Box::new(ast::Type::Identifier("T".into())))
+                ]
+            ))
+        );
+        let ty = parser.parse_type().unwrap();
+        assert_eq!(
+            ty,
+            Box::new(ast::Type::Generic(
+                "Option".into(),
+                vec![ast::Type::Generic(
+                    "Option".into(),
+                    vec![ast::Type::Integer]
+                )]
+            ))
+        );
+        let ty = parser.parse_type().unwrap();
+        assert_eq!(ty, Box::new(ast::Type::Bool));
     }
 
     #[test]
@@ -0,0 +1,259 @@
+use crate::compiler::ast::{Pattern, PatternKind};
+use crate::types::{self, Type, TypeKind};
+
+// Reachability and exhaustiveness of switch cases, a pattern is useful when
+// there is a value it matches which none of the patterns before it do. This is
+// the usefulness algorithm from "Warnings for pattern matching" (Maranget),
+// each row is a list of patterns matched against the columns' values.
+
+type Row<'a> = Vec<&'a Pattern>;
+
+// What a pattern checks the value for, wildcards have no constructor
+#[derive(PartialEq)]
+enum Constructor {
+    Variant(usize),
+    Struct,
+    // an inclusive range, a single integer is a range of one
+    Integer(i64, i64),
+    String(String),
+    Type(String),
+}
+
+fn constructor(p: &Pattern) -> Option<Constructor> {
+    match &p.kind {
+        PatternKind::CatchAll | PatternKind::Binding(_) => None,
+        PatternKind::EnumVariant { idx, .. } => Some(Constructor::Variant(*idx)),
+        PatternKind::Struct { .. } => Some(Constructor::Struct),
+        PatternKind::Integer(i) => Some(Constructor::Integer(*i, *i)),
+        PatternKind::IntegerRange(lo, hi) => Some(Constructor::Integer(*lo, *hi)),
+        PatternKind::String(s) => Some(Constructor::String(s.clone())),
+        PatternKind::Type { typ, .. } => Some(Constructor::Type(types::name(typ))),
+        PatternKind::Or(_) => unreachable!("or patterns are expanded before this"),
+    }
+}
+
+// Does a pattern for `row` match everything a pattern for `c` does, ranges
+// which only overlap are left alone so the result errs on the side of useful
+fn covers(row: &Constructor, c: &Constructor) -> bool {
+    match (row, c) {
+        (Constructor::Integer(lo, hi), Constructor::Integer(c_lo, c_hi)) => {
+            lo <= c_lo && c_hi <= hi
+        }
+        _ => row == c,
+    }
+}
+
+// The types of the values inside a constructor, they become new columns
+fn sub_types(typ: &Type, c: &Constructor) -> Vec<Type> {
+    match (typ.kind(), c) {
+        (TypeKind::Enum(enum_type), Constructor::Variant(idx)) => {
+            enum_type.variants.read().unwrap()[*idx].1.clone()
+        }
+        (TypeKind::Struct(struct_type), Constructor::Struct) => struct_type
+            .fields
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(_, t)| t.clone())
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+// The patterns inside a constructor pattern, struct fields which are left out
+// match anything
+fn sub_patterns<'a>(p: &'a Pattern, field_count: usize, wildcard: &'a Pattern) -> Row<'a> {
+    match &p.kind {
+        PatternKind::EnumVariant { values, .. } => values.iter().collect(),
+        PatternKind::Struct { fields, .. } => (0..field_count)
+            .map(|i| {
+                fields
+                    .iter()
+                    .find(|f| f.idx == i)
+                    .map(|f| &f.pattern)
+                    .unwrap_or(wildcard)
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+// Splits the rows starting with an or pattern into a row for each alternative
+fn expand_or<'a>(rows: &[Row<'a>]) -> Vec<Row<'a>> {
+    let mut expanded = Vec::new();
+    for row in rows {
+        match &row[0].kind {
+            PatternKind::Or(alternatives) => {
+                let alternatives = alternatives
+                    .iter()
+                    .map(|a| {
+                        let mut r = vec![a];
+                        r.extend_from_slice(&row[1..]);
+                        r
+                    })
+                    .collect::<Vec<_>>();
+                expanded.extend(expand_or(&alternatives));
+            }
+            _ => expanded.push(row.clone()),
+        }
+    }
+    expanded
+}
+
+// The rows which can match a value built by `c`, with its inner patterns in
+// place of the first column
+fn specialize<'a>(
+    rows: &[Row<'a>],
+    c: &Constructor,
+    typ: &Type,
+    wildcard: &'a Pattern,
+) -> Vec<Row<'a>> {
+    let field_count = sub_types(typ, c).len();
+    let mut specialized = Vec::new();
+    for row in expand_or(rows) {
+        let mut sub = match constructor(row[0]) {
+            None => vec![wildcard; field_count],
+            Some(head) if covers(&head, c) => sub_patterns(row[0], field_count, wildcard),
+            Some(_) => continue,
+        };
+        sub.extend_from_slice(&row[1..]);
+        specialized.push(sub);
+    }
+    specialized
+}
+
+// The rows which match whatever is in the first column
+fn default<'a>(rows: &[Row<'a>]) -> Vec<Row<'a>> {
+    expand_or(rows)
+        .into_iter()
+        .filter(|row| constructor(row[0]).is_none())
+        .map(|row| row[1..].to_vec())
+        .collect()
+}
+
+fn specialized_is_useful<'a>(
+    rows: &[Row<'a>],
+    c: Constructor,
+    row: Row<'a>,
+    types: &[Type],
+    wildcard: &'a Pattern,
+) -> bool {
+    let mut sub_types = sub_types(&types[0], &c);
+    sub_types.extend_from_slice(&types[1..]);
+    let specialized = specialize(rows, &c, &types[0], wildcard);
+    is_useful(&specialized, &row, &sub_types, wildcard)
+}
+
+pub fn is_useful<'a>(
+    rows: &[Row<'a>],
+    row: &Row<'a>,
+    types: &[Type],
+    wildcard: &'a Pattern,
+) -> bool {
+    let Some((head, rest)) = row.split_first() else {
+        return rows.is_empty();
+    };
+
+    if let PatternKind::Or(alternatives) = &head.kind {
+        return alternatives.iter().any(|a| {
+            let mut r = vec![a];
+            r.extend_from_slice(rest);
+            is_useful(rows, &r, types, wildcard)
+        });
+    }
+
+    let typ = &types[0];
+    match constructor(head) {
+        Some(c) => {
+            let mut sub = sub_patterns(head, sub_types(typ, &c).len(), wildcard);
+            sub.extend_from_slice(rest);
+            specialized_is_useful(rows, c, sub, types, wildcard)
+        }
+        // when every constructor of the type is in the column a wildcard is
+        // useful if it is for one of them, otherwise it is useful if it is for
+        // the rows which match anything
+        None => {
+            let heads = expand_or(rows)
+                .iter()
+                .filter_map(|r| constructor(r[0]))
+                .collect::<Vec<_>>();
+            match typ.kind() {
+                TypeKind::Enum(enum_type) => {
+                    let variant_count = enum_type.variants.read().unwrap().len();
+                    if (0..variant_count).all(|i| heads.contains(&Constructor::Variant(i))) {
+                        return (0..variant_count).any(|i| {
+                            let c = Constructor::Variant(i);
+                            let mut sub = vec![wildcard; sub_types(typ, &c).len()];
+                            sub.extend_from_slice(rest);
+                            specialized_is_useful(rows, c, sub, types, wildcard)
+                        });
+                    }
+                }
+                TypeKind::Struct(_) if heads.contains(&Constructor::Struct) => {
+                    let mut sub = vec![wildcard; sub_types(typ, &Constructor::Struct).len()];
+                    sub.extend_from_slice(rest);
+                    return specialized_is_useful(rows, Constructor::Struct, sub, types, wildcard);
+                }
+                _ => {}
+            }
+            is_useful(&default(rows), &rest.to_vec(), &types[1..], wildcard)
+        }
+    }
+}
+
+/// Does the pattern match every value of its type
+pub fn is_irrefutable<'a>(p: &'a Pattern, typ: &Type, wildcard: &'a Pattern) -> bool {
+    !is_useful(
+        &[vec![p]],
+        &vec![wildcard],
+        std::slice::from_ref(typ),
+        wildcard,
+    )
+}
+
+/// The variants of an enum which none of the patterns fully match
+pub fn missing_variants<'a>(
+    patterns: &[&'a Pattern],
+    typ: &Type,
+    wildcard: &'a Pattern,
+) -> Vec<String> {
+    let TypeKind::Enum(enum_type) = typ.kind() else {
+        return Vec::new();
+    };
+    let rows = patterns.iter().map(|p| vec![*p]).collect::<Vec<_>>();
+    let variants = enum_type.variants.read().unwrap().clone();
+    variants
+        .into_iter()
+        .enumerate()
+        .filter(|(i, (_, values))| {
+            let sub = vec![wildcard; values.len()];
+            specialized_is_useful(
+                &rows,
+                Constructor::Variant(*i),
+                sub,
+                std::slice::from_ref(typ),
+                wildcard,
+            )
+        })
+        .map(|(_, (id, _))| id)
+        .collect()
+}
+
+/// The variables a pattern binds with their types, every alternative of an
+/// or pattern binds the same ones
+pub fn bindings(p: &Pattern) -> Vec<(String, Type)> {
+    match &p.kind {
+        PatternKind::Binding(name) => vec![(name.clone(), p.typ.clone())],
+        PatternKind::Type {
+            binding: Some(binding),
+            typ,
+            ..
+        } => vec![(binding.clone(), typ.clone())],
+        PatternKind::EnumVariant { values, .. } => values.iter().flat_map(bindings).collect(),
+        PatternKind::Struct { fields, .. } => {
+            fields.iter().flat_map(|f| bindings(&f.pattern)).collect()
+        }
+        PatternKind::Or(alternatives) => bindings(&alternatives[0]),
+        _ => Vec::new(),
+    }
+}
@@ -2,6 +2,7 @@ use crate::compiler::SourceLoc;
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Keywords {
+    As,
     Await,
     Break,
     Case,
@@ -17,6 +18,7 @@ pub enum Keywords {
     Enum,
     Export,
     Extends,
+    Extension,
     False,
     Finally,
     For,
@@ -47,6 +49,7 @@ pub enum Keywords {
 }
 
 pub const KEYWORDS_MAP: &'static [(&'static str, Keywords)] = &[
+    ("as", Keywords::As),
     ("await", Keywords::Await),
     ("break", Keywords::Break),
     ("case", Keywords::Case),
@@ -62,6 +65,7 @@ pub const KEYWORDS_MAP: &'static [(&'static str, Keywords)] = &[
     ("enum", Keywords::Enum),
     ("export", Keywords::Export),
     ("extends", Keywords::Extends),
+    ("extension", Keywords::Extension),
     ("false", Keywords::False),
     ("finally", Keywords::Finally),
     ("for", Keywords::For),
@@ -21,6 +21,8 @@ pub enum TokeniserMode {
     Regex,
     RegexOrTemplateTail,
     TemplateTail,
+    // after the dot of `pair.0`, where digits are an index rather than a number
+    TupleIndex,
 }
 
 #[derive(Clone)]
@@ -135,7 +137,10 @@ impl<'a> Tokeniser<'a> {
                 ))
             } else {
                 let int_str = self.source.accum(|c, _| c.is_numeric());
-                if self.source.peek_char() == Some('.') && !self.source.peek_str("..") {
+                if mode != TokeniserMode::TupleIndex
+                    && self.source.peek_char() == Some('.')
+                    && !self.source.peek_str("..")
+                {
                     self.source.next();
                     let dec_str = self.source.accum(|c, _| c.is_numeric());
                     let float_str = format!("{}.{}", int_str, dec_str);
@@ -154,7 +159,10 @@ impl<'a> Tokeniser<'a> {
             }
         } else if c.is_numeric() {
             let int_str = self.source.accum(|c, _| c.is_numeric());
-            if self.source.peek_char() == Some('.') && !self.source.peek_str("..") {
+            if mode != TokeniserMode::TupleIndex
+                && self.source.peek_char() == Some('.')
+                && !self.source.peek_str("..")
+            {
                 self.source.next();
                 let dec_str = self.source.accum(|c, _| c.is_numeric());
                 let float_str = format!("{}.{}", int_str, dec_str);
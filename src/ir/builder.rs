@@ -134,6 +134,42 @@ impl<'a> FuncBuilder<'a> {
         self.append_inst(super::Inst::ModInt);
     }
 
+    pub fn neg_int(&mut self) {
+        self.append_inst(super::Inst::NegInt);
+    }
+
+    pub fn and_int(&mut self) {
+        self.append_inst(super::Inst::AndInt);
+    }
+
+    pub fn or_int(&mut self) {
+        self.append_inst(super::Inst::OrInt);
+    }
+
+    pub fn xor_int(&mut self) {
+        self.append_inst(super::Inst::XorInt);
+    }
+
+    pub fn shl_int(&mut self) {
+        self.append_inst(super::Inst::ShlInt);
+    }
+
+    pub fn shr_int(&mut self) {
+        self.append_inst(super::Inst::ShrInt);
+    }
+
+    pub fn div_byte(&mut self) {
+        self.append_inst(super::Inst::DivByte);
+    }
+
+    pub fn mod_byte(&mut self) {
+        self.append_inst(super::Inst::ModByte);
+    }
+
+    pub fn shr_byte(&mut self) {
+        self.append_inst(super::Inst::ShrByte);
+    }
+
     pub fn eq_int(&mut self) {
         self.append_inst(super::Inst::EquInt);
     }
@@ -174,6 +210,10 @@ impl<'a> FuncBuilder<'a> {
         self.append_inst(super::Inst::DivNumber);
     }
 
+    pub fn neg_number(&mut self) {
+        self.append_inst(super::Inst::NegNumber);
+    }
+
     pub fn eq_number(&mut self) {
         self.append_inst(super::Inst::EquNumber);
     }
@@ -214,6 +254,14 @@ impl<'a> FuncBuilder<'a> {
         self.append_inst(super::Inst::Or);
     }
 
+    pub fn not(&mut self) {
+        self.append_inst(super::Inst::Not);
+    }
+
+    pub fn bit_not(&mut self) {
+        self.append_inst(super::Inst::BitNot);
+    }
+
     pub fn load_const_int(&mut self, i: i64) {
         self.append_inst(super::Inst::LoadConstInt(i));
     }
@@ -238,6 +286,18 @@ impl<'a> FuncBuilder<'a> {
         self.append_inst(super::Inst::LoadGlobal(g));
     }
 
+    pub fn load_static(&mut self, id: String, typ: Type) {
+        self.append_inst(super::Inst::LoadStatic(id, typ));
+    }
+
+    pub fn store_static(&mut self, id: String, typ: Type) {
+        self.append_inst(super::Inst::StoreStatic(id, typ));
+    }
+
+    pub fn check_initialised(&mut self) {
+        self.append_inst(super::Inst::CheckInitialised);
+    }
+
     pub fn truncate(&mut self) {
         self.append_inst(super::Inst::Truncate);
     }
@@ -246,6 +306,14 @@ impl<'a> FuncBuilder<'a> {
         self.append_inst(super::Inst::Promote);
     }
 
+    pub fn extend(&mut self) {
+        self.append_inst(super::Inst::Extend);
+    }
+
+    pub fn narrow(&mut self, wrapping: bool) {
+        self.append_inst(super::Inst::Narrow(wrapping));
+    }
+
     pub fn load(&mut self, v: VariableRef) {
         self.append_inst(super::Inst::Load(v));
     }
@@ -282,6 +350,10 @@ impl<'a> FuncBuilder<'a> {
         self.append_inst(super::Inst::Call(id));
     }
 
+    pub fn load_function(&mut self, id: String) {
+        self.append_inst(super::Inst::LoadFunction(id));
+    }
+
     pub fn indirect_call(&mut self, signature: Signature) {
         self.append_inst(super::Inst::IndirectCall(signature));
     }
@@ -306,6 +378,46 @@ impl<'a> FuncBuilder<'a> {
         self.append_inst(super::Inst::ArrayLen);
     }
 
+    pub fn array_insert(&mut self, typ: Type) {
+        self.append_inst(super::Inst::ArrayInsert(typ));
+    }
+
+    pub fn array_remove(&mut self, typ: Type) {
+        self.append_inst(super::Inst::ArrayRemove(typ));
+    }
+
+    pub fn array_clear(&mut self) {
+        self.append_inst(super::Inst::ArrayClear);
+    }
+
+    pub fn new_map(&mut self, key: Type, value: Type) {
+        self.append_inst(super::Inst::NewMap(key, value));
+    }
+
+    pub fn load_map(&mut self, key: Type, value: Type) {
+        self.append_inst(super::Inst::LoadMap(key, value));
+    }
+
+    pub fn store_map(&mut self, key: Type, value: Type) {
+        self.append_inst(super::Inst::StoreMap(key, value));
+    }
+
+    pub fn map_contains(&mut self, key: Type) {
+        self.append_inst(super::Inst::MapContains(key));
+    }
+
+    pub fn map_remove(&mut self, key: Type) {
+        self.append_inst(super::Inst::MapRemove(key));
+    }
+
+    pub fn map_len(&mut self) {
+        self.append_inst(super::Inst::MapLen);
+    }
+
+    pub fn map_keys(&mut self, key: Type) {
+        self.append_inst(super::Inst::MapKeys(key));
+    }
+
     pub fn new_object(&mut self, size: usize) {
         self.append_inst(super::Inst::NewObject(size));
     }
@@ -326,6 +438,10 @@ impl<'a> FuncBuilder<'a> {
         self.append_inst(super::Inst::Assert);
     }
 
+    pub fn unreachable(&mut self) {
+        self.append_inst(super::Inst::Unreachable);
+    }
+
     pub fn finish(self) -> Box<super::Function> {
         return Box::new(self.func);
     }
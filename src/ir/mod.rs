@@ -14,7 +14,7 @@ pub enum Type {
     Bool,
     String,
     Reference,
-    Array
+    Array,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -26,6 +26,15 @@ pub enum Inst {
     MulInt,
     DivInt,
     ModInt,
+    NegInt,
+    AndInt,
+    OrInt,
+    XorInt,
+    ShlInt,
+    ShrInt,  // Arithmetic shift, keeps the sign
+    DivByte, // Bytes are unsigned, so division and right shift differ from ints
+    ModByte,
+    ShrByte,
     EquInt,
     NeqInt,
     LtInt,
@@ -36,6 +45,7 @@ pub enum Inst {
     SubNumber,
     MulNumber,
     DivNumber,
+    NegNumber,
     EquNumber,
     NeqNumber,
     LtNumber,
@@ -46,14 +56,22 @@ pub enum Inst {
     NeqString,
     And,
     Or,
+    Not,    // Logical not of a bool
+    BitNot, // Flips every bit of an int or byte
     LoadConstInt(i64),
     LoadConstByte(u8),
     LoadConstNumber(f64),
     LoadConstBool(bool),
     LoadConstString(StringRef),
     LoadGlobal(GlobalRef),
-    Truncate, // Convert number to integer
-    Promote,  // Convert integer to number
+    LoadStatic(String, Type), // Pushes the value of a module-level variable
+    StoreStatic(String, Type),
+    CheckInitialised, // Pops the initialised flag of a module-level variable, panics when it is not set
+    LoadFunction(String), // Pushes the address of a function
+    Truncate,             // Convert number to integer, saturating at the limits of an integer
+    Promote,              // Convert integer to number
+    Extend,               // Convert byte or bool to integer
+    Narrow(bool),         // Convert integer to byte, panics when it does not fit unless wrapping
     Load(VariableRef),
     Store(VariableRef),
     Tee(VariableRef),
@@ -72,6 +90,17 @@ pub enum Inst {
     StoreArray(Type), // Pops the value, index, and array
     CreateSlice(Type),
     ArrayLen,
+    ArrayInsert(Type), // Pops the value, index, and array, moving the elements after the index up
+    ArrayRemove(Type), // Pops the index and array, pushes the removed element
+    ArrayClear,
+
+    NewMap(Type, Type), // The key and value types
+    LoadMap(Type, Type),  // Pops the key and map, panics when the key is missing
+    StoreMap(Type, Type), // Pops the value, key, and map
+    MapContains(Type),    // Pops the key and map
+    MapRemove(Type),      // Pops the key and map, pushes whether the key was there
+    MapLen,
+    MapKeys(Type),  // Pops the map, pushes a new array of its keys
 
     NewObject(usize),
     GetObject(usize, Type),
@@ -82,6 +111,8 @@ pub enum Inst {
     CheckYield,
 
     Assert,
+    /// Panics, for code the compiler knows cannot be reached
+    Unreachable,
 }
 
 #[derive(Debug, Clone, Default, PartialEq)]
@@ -131,9 +162,17 @@ pub struct Function {
     pub blocks: Vec<Block>,
 }
 
+// A module-level variable, one slot of memory shared by every function
+#[derive(Debug, Clone)]
+pub struct Static {
+    pub id: String,
+    pub typ: Type,
+}
+
 #[derive(Debug, Clone)]
 pub struct Module {
     pub funcs: Vec<Function>,
+    pub statics: Vec<Static>,
     pub string_map: StringMap,
     pub global_value_map: GlobalValueMap,
     pub source_locs: SourceLocs,
@@ -164,7 +203,9 @@ impl StringMap {
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum GlobalValue {
-    VirtualTable(Vec<String>), // method names in order
+    // The first slot holds the runtime identity of the implementing type,
+    // the interned name of the type, followed by the methods in order
+    VirtualTable(StringRef, Vec<String>),
 }
 
 #[derive(Debug, Clone)]
@@ -6,7 +6,16 @@ use std::sync::Arc;
 fn main() {
     let mut args = std::env::args();
     args.next();
-    let name = args.next().expect("Expected file");
+    let mut overflow_checks = false;
+    let mut name = None;
+    for arg in args {
+        if arg == "--overflow-checks" {
+            overflow_checks = true;
+        } else {
+            name = Some(arg);
+        }
+    }
+    let name = name.expect("Expected file");
 
     let builtins = builtins::default_builtins();
 
@@ -16,6 +25,7 @@ fn main() {
     let module = compiler::run_compiler(Arc::clone(&compiler), &builtins);
 
     let mut jit = runtime::JitContext::new(builtins);
+    jit.set_overflow_checks(overflow_checks);
     //print!("compiling... ");  std::io::stdout().flush().unwrap();
     jit.compile_ir_module(&module);
     //println!("done.");
@@ -25,6 +35,6 @@ fn main() {
         file: main_file_name,
         name: "main".into(),
     });
-    jit.call_function_no_params_no_return(&main_symbol);
+    jit.call_functions_no_params_no_return(&[compiler::mangle::INIT_NAME, &main_symbol]);
     //println!("Returned {}", returned);
 }
@@ -1,34 +1,71 @@
 use std::collections::HashSet;
 
-use libc::{free, malloc};
+use libc::{free, malloc, realloc};
+
+use super::map::MapObject;
+
+// An array value points at its header, the elements live in a separate block
+// so the array can grow without moving
+#[repr(C)]
+pub struct ArrayHeader {
+    pub length: i64,
+    pub capacity: i64,
+    pub data: *mut u8,
+}
+
+impl ArrayHeader {
+    pub const LENGTH_OFFSET: i32 = 0;
+    pub const DATA_OFFSET: i32 = 16;
+
+    // Makes room for `additional` more elements, at least doubling the
+    // capacity so pushing is amortised constant time
+    pub fn reserve(&mut self, additional: i64, elem_size: i64) {
+        let needed = self.length + additional;
+        if needed <= self.capacity {
+            return;
+        }
+        let capacity = needed.max(self.capacity * 2).max(4);
+        unsafe {
+            self.data = realloc(self.data as *mut libc::c_void, (capacity * elem_size) as usize) as *mut u8;
+        }
+        self.capacity = capacity;
+    }
+}
 
 #[derive(Debug, Clone, PartialEq, Hash, Eq)]
 enum Allocation {
     Array{
         ptr: usize, 
-        size: usize,
         elem_size: usize,
         scan_elements : bool, // whether we should scan the elements of this array for pointers to other allocations. This is false for arrays of primitive types.
     },
     Object(usize, usize),
+    Map{
+        ptr: usize,
+        scan_values: bool, // whether the values can point to other allocations, keys never do
+    },
 }
 
 impl Allocation {
     pub fn get_address(&self) -> usize {
         match self {
-            &Allocation::Array{ptr, size: _, elem_size: _, scan_elements:_} => ptr,
+            &Allocation::Array{ptr, elem_size: _, scan_elements:_} => ptr,
             &Allocation::Object(p, _) => p,
+            &Allocation::Map{ptr, scan_values: _} => ptr,
         }
     }
 
     pub fn address_in_range(&self, ptr: usize) -> bool {
         match self {
-            &Allocation::Array{ptr: p, size, elem_size, scan_elements:_} => {
-                ptr ==p || ptr >= p && ptr < (p + (size * elem_size))
+            &Allocation::Array{ptr: p, elem_size: _, scan_elements:_} => {
+                ptr == p
             }
             &Allocation::Object(p, _) => {
                 ptr == p
             }
+            &Allocation::Map{ptr: p, scan_values: _} => {
+                ptr == p
+            }
         }
     }
 }
@@ -61,12 +98,15 @@ impl GarbageCollector {
 
         // find all sub allocations and mark them
         match allocation {
-            &Allocation::Array{ptr: p, size: s, elem_size: e, scan_elements} => {
+            &Allocation::Array{ptr: p, elem_size: e, scan_elements} => {
                 if !scan_elements {
                     return;
                 }
-                for i in 0..(s/e) {
-                    let v = (p + 8 + (i * e)) as *mut usize;
+                // only the elements up to the length are live, the rest of the
+                // capacity may hold stale pointers
+                let header = unsafe { &*(p as *const ArrayHeader) };
+                for i in 0..header.length as usize {
+                    let v = (header.data as usize + (i * e)) as *mut usize;
                     let v = unsafe { *v };
                     if let Some(a) = self.find_allocation(v) {
                         self.mark_allocation(a, marks);
@@ -82,10 +122,21 @@ impl GarbageCollector {
                     }
                 }
             }
+            &Allocation::Map{ptr: p, scan_values} => {
+                if !scan_values {
+                    return;
+                }
+                let map = unsafe { &*(p as *const MapObject) };
+                for &v in map.values() {
+                    if let Some(a) = self.find_allocation(v as usize) {
+                        self.mark_allocation(a, marks);
+                    }
+                }
+            }
         }
     }
 
-    pub fn collect(&mut self, stack_roots: &Vec<usize>) {
+    pub fn collect(&mut self, stack_roots: &Vec<usize>, global_roots: &[usize]) {
         let mut marks = Vec::new();
         for &root in stack_roots.iter() {
             let alloc = self
@@ -93,6 +144,13 @@ impl GarbageCollector {
                 .unwrap_or_else(|| panic!("Stack root was not an allocation! {root} allocations: {:?}", self.allocations));
             self.mark_allocation(alloc, &mut marks);
         }
+        // globals are empty until they are initialised and constant strings
+        // are not allocated, so those are skipped
+        for &root in global_roots.iter() {
+            if let Some(alloc) = self.find_allocation(root) {
+                self.mark_allocation(alloc, &mut marks);
+            }
+        }
         let to_remove = self
             .allocations
             .iter()
@@ -108,19 +166,39 @@ impl GarbageCollector {
 
     fn free_allocation(a: Allocation) {
         unsafe {
+            match a {
+                Allocation::Array { ptr, .. } => {
+                    free((*(ptr as *const ArrayHeader)).data as *mut libc::c_void);
+                }
+                // the entries are owned by rust
+                Allocation::Map { ptr, .. } => std::ptr::drop_in_place(ptr as *mut MapObject),
+                Allocation::Object(..) => {}
+            }
             free(a.get_address() as *mut libc::c_void);
         }
     }
 
     pub fn create_array(&mut self, size: usize, elem_size: usize, scan_elements: bool) -> *const i64 {
-        // Placeholder implementation
-        // +8 for the size
         unsafe {
-            let ptr = malloc(size * elem_size + 8) as *mut i64;
-            *ptr = (size) as i64;
+            let ptr = malloc(std::mem::size_of::<ArrayHeader>()) as *mut ArrayHeader;
+            ptr.write(ArrayHeader {
+                length: size as i64,
+                capacity: size as i64,
+                data: malloc(size * elem_size) as *mut u8,
+            });
             self.allocations
-                .insert(Allocation::Array{ptr: ptr as usize, size, elem_size, scan_elements});
-            ptr
+                .insert(Allocation::Array{ptr: ptr as usize, elem_size, scan_elements});
+            ptr as *const i64
+        }
+    }
+
+    pub fn create_map(&mut self, string_keys: bool, scan_values: bool) -> *const i64 {
+        unsafe {
+            let ptr = malloc(std::mem::size_of::<MapObject>()) as *mut MapObject;
+            ptr.write(MapObject::new(string_keys));
+            self.allocations
+                .insert(Allocation::Map{ptr: ptr as usize, scan_values});
+            ptr as *const i64
         }
     }
 
@@ -140,7 +218,8 @@ impl GarbageCollector {
         // Placeholder implementation
         unsafe {
             let ptr = malloc(std::mem::size_of::<T>()) as *mut T;
-            *ptr = v;
+            // the memory is uninitialised, so there is no old value to drop
+            ptr.write(v);
             self.allocations
                 .insert(Allocation::Object(ptr as usize, 0)); // We can treat this as an object with 0 fields, as we will never look for sub-allocations of it.
             ptr
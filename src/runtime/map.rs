@@ -0,0 +1,109 @@
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+use super::string;
+
+// Keys and values are held in a single word, strings are compared by their
+// contents rather than their address
+#[derive(Debug, Clone, Copy)]
+struct MapKey {
+    value: i64,
+    is_string: bool,
+}
+
+impl MapKey {
+    fn as_str<'a>(&self) -> &'a str {
+        string::convert_from_internal_string(self.value as *const u8)
+    }
+}
+
+impl Hash for MapKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        if self.is_string {
+            self.as_str().hash(state);
+        } else {
+            self.value.hash(state);
+        }
+    }
+}
+
+impl PartialEq for MapKey {
+    fn eq(&self, other: &Self) -> bool {
+        if self.is_string {
+            self.as_str() == other.as_str()
+        } else {
+            self.value == other.value
+        }
+    }
+}
+
+impl Eq for MapKey {}
+
+// The entries are kept in a list so they can be iterated by position, removing
+// an entry moves the last one into its place
+pub struct MapObject {
+    string_keys: bool,
+    keys: Vec<i64>,
+    values: Vec<i64>,
+    positions: HashMap<MapKey, usize>,
+}
+
+impl MapObject {
+    pub fn new(string_keys: bool) -> Self {
+        Self {
+            string_keys,
+            keys: Vec::new(),
+            values: Vec::new(),
+            positions: HashMap::new(),
+        }
+    }
+
+    fn key(&self, value: i64) -> MapKey {
+        MapKey {
+            value,
+            is_string: self.string_keys,
+        }
+    }
+
+    pub fn find(&self, key: i64) -> Option<usize> {
+        self.positions.get(&self.key(key)).copied()
+    }
+
+    pub fn insert(&mut self, key: i64, value: i64) {
+        if let Some(i) = self.find(key) {
+            self.values[i] = value;
+            return;
+        }
+        self.positions.insert(self.key(key), self.keys.len());
+        self.keys.push(key);
+        self.values.push(value);
+    }
+
+    pub fn remove(&mut self, key: i64) -> bool {
+        let Some(i) = self.positions.remove(&self.key(key)) else {
+            return false;
+        };
+        self.keys.swap_remove(i);
+        self.values.swap_remove(i);
+        if let Some(&moved) = self.keys.get(i) {
+            self.positions.insert(self.key(moved), i);
+        }
+        true
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn keys(&self) -> &[i64] {
+        &self.keys
+    }
+
+    pub fn value_at(&self, i: usize) -> i64 {
+        self.values[i]
+    }
+
+    pub fn values(&self) -> &[i64] {
+        &self.values
+    }
+}
@@ -17,12 +17,17 @@ use crate::{
 
 mod cranelift;
 mod gc;
+mod map;
 mod stack_roots;
 pub mod string;
 mod translate;
 
+pub use gc::ArrayHeader;
+use map::MapObject;
+
 use cranelift::backend::{JITBuilder, JITModule};
-use cranelift::module::{FuncId, Module};
+use cranelift::data_context::DataDescription;
+use cranelift::module::{DataId, FuncId, Module};
 
 pub struct CompiledFunc {
     id: FuncId,
@@ -44,6 +49,8 @@ pub struct StackMaps {
 pub struct RuntimeContext {
     pub gc: GarbageCollector,
     pub stack_maps: StackMaps,
+    // addresses of the globals which can hold a reference, scanned as roots
+    pub global_roots: Vec<usize>,
 }
 
 impl RuntimeContext {
@@ -53,13 +60,15 @@ impl RuntimeContext {
             stack_maps: StackMaps {
                 lr_map: HashMap::new(),
             },
+            global_roots: Vec::new(),
         }
     }
 }
 
 struct Fiber {
     ctx: *mut RuntimeContext,
-    entry_point: *const u8,
+    // run one after another, globals are initialised before main
+    entry_points: Vec<*const u8>,
 }
 
 pub extern "C" fn panic(_: *mut RuntimeContext, message: *const u8) {
@@ -74,6 +83,91 @@ pub extern "C" fn create_array(ctx: *mut RuntimeContext, size: i64, elem_size: i
     array
 }
 
+// Opens a gap at index, the caller stores the new element into it.
+// Safety: only called by generated code, with a live array and an index it
+// has already checked against the length
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn array_insert(_: *mut RuntimeContext, array: *mut ArrayHeader, index: i64, elem_size: i64) {
+    let header = unsafe { &mut *array };
+    header.reserve(1, elem_size);
+    unsafe {
+        let at = header.data.add((index * elem_size) as usize);
+        std::ptr::copy(at, at.add(elem_size as usize), ((header.length - index) * elem_size) as usize);
+    }
+    header.length += 1;
+}
+
+// Closes the gap left by the element at index, the caller has already loaded it.
+// Safety: the same as array_insert
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn array_remove(_: *mut RuntimeContext, array: *mut ArrayHeader, index: i64, elem_size: i64) {
+    let header = unsafe { &mut *array };
+    unsafe {
+        let at = header.data.add((index * elem_size) as usize);
+        std::ptr::copy(at.add(elem_size as usize), at, ((header.length - index - 1) * elem_size) as usize);
+    }
+    header.length -= 1;
+}
+
+pub extern "C" fn create_map(ctx: *mut RuntimeContext, string_keys: bool, scan_values: bool) -> *const i64 {
+    let gc = unsafe { &mut (*ctx).gc };
+    gc.create_map(string_keys, scan_values)
+}
+
+// The position of the key, or -1 when the map does not contain it.
+// Safety: the map functions are only called by generated code with a live
+// map, and positions it has already checked against the length
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn map_find(_: *mut RuntimeContext, map: *const MapObject, key: i64) -> i64 {
+    let map = unsafe { &*map };
+    map.find(key).map_or(-1, |i| i as i64)
+}
+
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn map_insert(_: *mut RuntimeContext, map: *mut MapObject, key: i64, value: i64) {
+    let map = unsafe { &mut *map };
+    map.insert(key, value);
+}
+
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn map_remove(_: *mut RuntimeContext, map: *mut MapObject, key: i64) -> bool {
+    let map = unsafe { &mut *map };
+    map.remove(key)
+}
+
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn map_len(_: *mut RuntimeContext, map: *const MapObject) -> i64 {
+    let map = unsafe { &*map };
+    map.len() as i64
+}
+
+// A new array of the keys, loops over a map go through it so the body can add
+// and remove entries without any being skipped
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn map_keys(ctx: *mut RuntimeContext, map: *const MapObject, elem_size: i64) -> *const i64 {
+    let gc = unsafe { &mut (*ctx).gc };
+    let map = unsafe { &*map };
+    let array = gc.create_array(map.len(), elem_size as usize, false) as *mut ArrayHeader;
+    for (i, &key) in map.keys().iter().enumerate() {
+        unsafe {
+            let at = (*array).data.add(i * elem_size as usize);
+            // byte and bool keys take a single byte of the word
+            if elem_size == 1 {
+                at.write(key as u8);
+            } else {
+                (at as *mut i64).write_unaligned(key);
+            }
+        }
+    }
+    array as *const i64
+}
+
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn map_value_at(_: *mut RuntimeContext, map: *const MapObject, index: i64) -> i64 {
+    let map = unsafe { &*map };
+    map.value_at(index as usize)
+}
+
 pub extern "C" fn create_object(ctx: *mut RuntimeContext, size: i64) -> *const i64 {
     let gc = unsafe { &mut (*ctx).gc };
     let array = gc.create_object(size as usize);
@@ -109,7 +203,11 @@ fn check_yield_common(ctx: *mut RuntimeContext, fp: usize) {
     if gc.should_collect() {
         let stack_maps = unsafe { &mut (*ctx).stack_maps };
         let roots = stack_roots::collect_roots(stack_maps, fp);
-        gc.collect(&roots);
+        let global_roots = unsafe { &(*ctx).global_roots }
+            .iter()
+            .map(|&addr| unsafe { *(addr as *const usize) })
+            .collect::<Vec<_>>();
+        gc.collect(&roots, &global_roots);
     }
 
     // perform fiber switch if we need ?
@@ -119,9 +217,11 @@ fn check_yield_common(ctx: *mut RuntimeContext, fp: usize) {
 pub extern "C" fn fiber_entry(t: context::Transfer) -> ! {
     let fiber = unsafe { &mut *(t.data as *mut Fiber) };
 
-    unsafe {
-        let code_fn = core::mem::transmute::<_, fn(*mut RuntimeContext)>(fiber.entry_point);
-        code_fn(fiber.ctx);
+    for &entry_point in fiber.entry_points.iter() {
+        unsafe {
+            let code_fn = core::mem::transmute::<_, fn(*mut RuntimeContext)>(entry_point);
+            code_fn(fiber.ctx);
+        }
     }
 
     unsafe {
@@ -157,7 +257,11 @@ pub struct JitContext {
     module: JITModule,
     builtins: Builtins,
     compiled_funcs: Vec<CompiledFunc>,
+    // the memory of each global, by symbol
+    statics: HashMap<String, DataId>,
     runtime_ctx: *mut RuntimeContext,
+    // integer add, subtract and multiply panic on overflow instead of wrapping
+    overflow_checks: bool,
 }
 
 impl JitContext {
@@ -190,6 +294,15 @@ impl JitContext {
         builder.symbol("__panic", panic as *const u8);
         builder.symbol("__create_array", create_array as *const u8);
         builder.symbol("__create_object", create_object as *const u8);
+        builder.symbol("__array_insert", array_insert as *const u8);
+        builder.symbol("__array_remove", array_remove as *const u8);
+        builder.symbol("__create_map", create_map as *const u8);
+        builder.symbol("__map_find", map_find as *const u8);
+        builder.symbol("__map_insert", map_insert as *const u8);
+        builder.symbol("__map_remove", map_remove as *const u8);
+        builder.symbol("__map_len", map_len as *const u8);
+        builder.symbol("__map_keys", map_keys as *const u8);
+        builder.symbol("__map_value_at", map_value_at as *const u8);
         builder.symbol("__check_yield", check_yield as *const u8);
 
         let runtime_ctx = Box::new(RuntimeContext::new());
@@ -199,11 +312,17 @@ impl JitContext {
             isa,
             module: JITModule::new(builder),
             compiled_funcs: Vec::new(),
+            statics: HashMap::new(),
             builtins,
             runtime_ctx,
+            overflow_checks: false,
         }
     }
 
+    pub fn set_overflow_checks(&mut self, overflow_checks: bool) {
+        self.overflow_checks = overflow_checks;
+    }
+
     fn isa(&self) -> &dyn TargetIsa {
         &*self.isa
     }
@@ -274,6 +393,60 @@ impl JitContext {
                 ret_types: vec![ir::Type::Reference],
             },
         });
+        for id in ["__array_insert", "__array_remove"] {
+            signatures.push(TranslateSignature {
+                id: id.into(),
+                signature: Signature {
+                    parameters: vec![ir::Type::Array, ir::Type::Integer, ir::Type::Integer],
+                    ret_types: vec![],
+                },
+            });
+        }
+        // keys and values are passed as a single word
+        signatures.push(TranslateSignature {
+            id: "__create_map".into(),
+            signature: Signature {
+                parameters: vec![ir::Type::Bool, ir::Type::Bool],
+                ret_types: vec![ir::Type::Reference],
+            },
+        });
+        signatures.push(TranslateSignature {
+            id: "__map_insert".into(),
+            signature: Signature {
+                parameters: vec![ir::Type::Reference, ir::Type::Integer, ir::Type::Integer],
+                ret_types: vec![],
+            },
+        });
+        signatures.push(TranslateSignature {
+            id: "__map_remove".into(),
+            signature: Signature {
+                parameters: vec![ir::Type::Reference, ir::Type::Integer],
+                ret_types: vec![ir::Type::Bool],
+            },
+        });
+        signatures.push(TranslateSignature {
+            id: "__map_keys".into(),
+            signature: Signature {
+                parameters: vec![ir::Type::Reference, ir::Type::Integer],
+                ret_types: vec![ir::Type::Array],
+            },
+        });
+        signatures.push(TranslateSignature {
+            id: "__map_len".into(),
+            signature: Signature {
+                parameters: vec![ir::Type::Reference],
+                ret_types: vec![ir::Type::Integer],
+            },
+        });
+        for id in ["__map_find", "__map_value_at"] {
+            signatures.push(TranslateSignature {
+                id: id.into(),
+                signature: Signature {
+                    parameters: vec![ir::Type::Reference, ir::Type::Integer],
+                    ret_types: vec![ir::Type::Integer],
+                },
+            });
+        }
         signatures.push(TranslateSignature {
             id: "__check_yield".into(),
             signature: Signature {
@@ -282,6 +455,21 @@ impl JitContext {
             },
         });
 
+        // globals live in zeroed memory, their init function stores the values
+        let mut data_desc = DataDescription::new();
+        data_desc.define_zeroinit(8);
+        data_desc.set_align(8);
+        for s in module.statics.iter() {
+            let data_id = self
+                .module
+                .declare_data(&s.id, cranelift::module::Linkage::Local, true, false)
+                .expect("Failed to declare global");
+            self.module
+                .define_data(data_id, &data_desc)
+                .expect("Could not define global");
+            self.statics.insert(s.id.clone(), data_id);
+        }
+
         let translated = module
             .funcs
             .iter()
@@ -311,6 +499,15 @@ impl JitContext {
 
         self.module.finalize_definitions().unwrap();
 
+        for s in module.statics.iter() {
+            if matches!(s.typ, ir::Type::Reference | ir::Type::Array | ir::Type::String) {
+                let (addr, _) = self.module.get_finalized_data(self.statics[&s.id]);
+                unsafe {
+                    (*self.runtime_ctx).global_roots.push(addr as usize);
+                }
+            }
+        }
+
         for (id, name) in translated {
             let blob = self.module.get_finalized_function(id);
             self.compiled_funcs.push(CompiledFunc {
@@ -333,11 +530,15 @@ impl JitContext {
         }
     }
 
-    pub fn call_function_no_params_no_return(&self, name: &str) {
-        let compiled_func = self.compiled_funcs.iter().find(|c| c.name == name).unwrap();
+    // Runs the functions one after another in the same task
+    pub fn call_functions_no_params_no_return(&self, names: &[&str]) {
+        let entry_points = names
+            .iter()
+            .map(|name| self.compiled_funcs.iter().find(|c| c.name == *name).unwrap().code)
+            .collect();
         let fiber = Box::new(Fiber {
             ctx: self.runtime_ctx,
-            entry_point: compiled_func.code,
+            entry_points,
         });
         let fiber = Box::into_raw(fiber);
 
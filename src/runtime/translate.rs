@@ -5,7 +5,7 @@ use super::cranelift::data_context::DataDescription;
 use super::cranelift::module::{Linkage, Module};
 use crate::{
     ir::{self, GlobalValueMap, SourceLocs, StringMap},
-    runtime::string,
+    runtime::{ArrayHeader, string},
 };
 use cranelift_codegen::{
     Context,
@@ -13,7 +13,7 @@ use cranelift_codegen::{
     ir::{
         AbiParam, Block, BlockArg, InstBuilder, JumpTableData, MemFlags, Signature, TrapCode,
         condcodes::{FloatCC, IntCC},
-        types::{I8, I32, I64},
+        types::{F64, I8, I32, I64},
     },
     isa::CallConv,
     verify_function,
@@ -80,6 +80,184 @@ fn construct_panic_message(
     builder.ins().symbol_value(I64, local_data_id)
 }
 
+// Jumps to the panic block if the divisor is zero, leaving the builder in a
+// new block where the division can happen
+fn check_divisor(
+    ctx: &mut super::JitContext,
+    builder: &mut cranelift_frontend::FunctionBuilder,
+    source_locs: &SourceLocs,
+    source_loc: usize,
+    str_map: &StringMap,
+    panic_block: Block,
+    divisor: cranelift_codegen::ir::Value,
+) {
+    let continue_block = builder.create_block();
+    let panic_message = construct_panic_message(
+        ctx,
+        builder,
+        source_locs,
+        source_loc,
+        str_map,
+        "Division by zero.",
+    );
+    builder.ins().brif(
+        divisor,
+        continue_block,
+        &[],
+        panic_block,
+        &vec![BlockArg::Value(panic_message)],
+    );
+    builder.switch_to_block(continue_block);
+}
+
+// Signed division where MIN / -1 wraps around to MIN like the other operators
+// instead of trapping, dividing by -1 is the same as negating
+fn wrapping_sdiv(
+    builder: &mut cranelift_frontend::FunctionBuilder,
+    lhs: cranelift_codegen::ir::Value,
+    rhs: cranelift_codegen::ir::Value,
+) -> cranelift_codegen::ir::Value {
+    let by_minus_one = builder.ins().icmp_imm(IntCC::Equal, rhs, -1);
+    let typ = builder.func.dfg.value_type(rhs);
+    let one = builder.ins().iconst(typ, 1);
+    let divisor = builder.ins().select(by_minus_one, one, rhs);
+    let quotient = builder.ins().sdiv(lhs, divisor);
+    let negated = builder.ins().ineg(lhs);
+    builder.ins().select(by_minus_one, negated, quotient)
+}
+
+// Signed remainder, which is always zero for -1 so MIN % -1 cannot trap
+fn wrapping_srem(
+    builder: &mut cranelift_frontend::FunctionBuilder,
+    lhs: cranelift_codegen::ir::Value,
+    rhs: cranelift_codegen::ir::Value,
+) -> cranelift_codegen::ir::Value {
+    let by_minus_one = builder.ins().icmp_imm(IntCC::Equal, rhs, -1);
+    let typ = builder.func.dfg.value_type(rhs);
+    let one = builder.ins().iconst(typ, 1);
+    let divisor = builder.ins().select(by_minus_one, one, rhs);
+    builder.ins().srem(lhs, divisor)
+}
+
+// Jumps to the panic block if a checked operation overflowed, leaving the
+// builder in a new block where the result can be used
+fn check_overflow(
+    ctx: &mut super::JitContext,
+    builder: &mut cranelift_frontend::FunctionBuilder,
+    source_locs: &SourceLocs,
+    source_loc: usize,
+    str_map: &StringMap,
+    panic_block: Block,
+    overflow: cranelift_codegen::ir::Value,
+) {
+    let continue_block = builder.create_block();
+    let panic_message = construct_panic_message(
+        ctx,
+        builder,
+        source_locs,
+        source_loc,
+        str_map,
+        "Integer overflow.",
+    );
+    builder.ins().brif(
+        overflow,
+        panic_block,
+        &vec![BlockArg::Value(panic_message)],
+        continue_block,
+        &[],
+    );
+    builder.switch_to_block(continue_block);
+}
+
+// Panics unless 0 <= index < limit, or index <= limit when the limit itself is
+// a valid position, like inserting at the end of an array
+#[allow(clippy::too_many_arguments)]
+fn check_bounds(
+    ctx: &mut super::JitContext,
+    builder: &mut cranelift_frontend::FunctionBuilder,
+    source_locs: &SourceLocs,
+    source_loc: usize,
+    str_map: &StringMap,
+    panic_block: Block,
+    index: cranelift_codegen::ir::Value,
+    limit: cranelift_codegen::ir::Value,
+    inclusive: bool,
+) {
+    let index_ok_zero = builder.ins().icmp_imm(IntCC::SignedGreaterThanOrEqual, index, 0);
+    let cc = if inclusive { IntCC::SignedLessThanOrEqual } else { IntCC::SignedLessThan };
+    let index_ok_limit = builder.ins().icmp(cc, index, limit);
+    let index_ok = builder.ins().band(index_ok_limit, index_ok_zero);
+    panic_unless(ctx, builder, source_locs, source_loc, str_map, panic_block, index_ok, "Out of bounds.");
+}
+
+// Jumps to the panic block with the reason unless the condition holds,
+// leaving the builder in a new block
+#[allow(clippy::too_many_arguments)]
+fn panic_unless(
+    ctx: &mut super::JitContext,
+    builder: &mut cranelift_frontend::FunctionBuilder,
+    source_locs: &SourceLocs,
+    source_loc: usize,
+    str_map: &StringMap,
+    panic_block: Block,
+    condition: cranelift_codegen::ir::Value,
+    reason: &str,
+) {
+    let continue_block = builder.create_block();
+    let panic_message = construct_panic_message(
+        ctx,
+        builder,
+        source_locs,
+        source_loc,
+        str_map,
+        reason,
+    );
+    builder.ins().brif(
+        condition,
+        continue_block,
+        &[],
+        panic_block,
+        &vec![BlockArg::Value(panic_message)],
+    );
+    builder.switch_to_block(continue_block);
+}
+
+// Map keys and values are passed to the runtime as a single word
+fn to_word(
+    builder: &mut cranelift_frontend::FunctionBuilder,
+    value: cranelift_codegen::ir::Value,
+) -> cranelift_codegen::ir::Value {
+    match builder.func.dfg.value_type(value) {
+        I8 => builder.ins().uextend(I64, value),
+        F64 => builder.ins().bitcast(I64, MemFlags::new(), value),
+        _ => value,
+    }
+}
+
+fn from_word(
+    builder: &mut cranelift_frontend::FunctionBuilder,
+    word: cranelift_codegen::ir::Value,
+    typ: cranelift_codegen::ir::Type,
+) -> cranelift_codegen::ir::Value {
+    match typ {
+        I8 => builder.ins().ireduce(I8, word),
+        F64 => builder.ins().bitcast(F64, MemFlags::new(), word),
+        _ => word,
+    }
+}
+
+// The address of an element, the elements are behind the array header
+fn array_element(
+    builder: &mut cranelift_frontend::FunctionBuilder,
+    array: cranelift_codegen::ir::Value,
+    index: cranelift_codegen::ir::Value,
+    elem_size: i64,
+) -> cranelift_codegen::ir::Value {
+    let data = builder.ins().load(I64, MemFlags::new(), array, ArrayHeader::DATA_OFFSET);
+    let offset = builder.ins().imul_imm(index, elem_size);
+    builder.ins().iadd(data, offset)
+}
+
 struct ValueStack {
     stack: Vec<cranelift_codegen::ir::Value>,
 }
@@ -210,28 +388,192 @@ pub fn translate_function(
                 ir::Inst::AddInt => {
                     let rhs = stack.pop();
                     let lhs = stack.pop();
-                    let res = builder.ins().iadd(lhs, rhs);
+                    let res = if ctx.overflow_checks {
+                        // bytes are unsigned
+                        let (res, overflow) = if builder.func.dfg.value_type(lhs) == I8 {
+                            builder.ins().uadd_overflow(lhs, rhs)
+                        } else {
+                            builder.ins().sadd_overflow(lhs, rhs)
+                        };
+                        check_overflow(
+                            ctx,
+                            &mut builder,
+                            source_locs,
+                            source_loc.unwrap(),
+                            str_map,
+                            panic_block,
+                            overflow,
+                        );
+                        res
+                    } else {
+                        builder.ins().iadd(lhs, rhs)
+                    };
                     stack.push(res);
                 }
                 ir::Inst::SubInt => {
                     let rhs = stack.pop();
                     let lhs = stack.pop();
-                    let res = builder.ins().isub(lhs, rhs);
+                    let res = if ctx.overflow_checks {
+                        let (res, overflow) = if builder.func.dfg.value_type(lhs) == I8 {
+                            builder.ins().usub_overflow(lhs, rhs)
+                        } else {
+                            builder.ins().ssub_overflow(lhs, rhs)
+                        };
+                        check_overflow(
+                            ctx,
+                            &mut builder,
+                            source_locs,
+                            source_loc.unwrap(),
+                            str_map,
+                            panic_block,
+                            overflow,
+                        );
+                        res
+                    } else {
+                        builder.ins().isub(lhs, rhs)
+                    };
                     stack.push(res);
                 }
                 ir::Inst::MulInt => {
                     let rhs = stack.pop();
                     let lhs = stack.pop();
-                    let res = builder.ins().imul(lhs, rhs);
+                    let res = if ctx.overflow_checks {
+                        let (res, overflow) = if builder.func.dfg.value_type(lhs) == I8 {
+                            builder.ins().umul_overflow(lhs, rhs)
+                        } else {
+                            builder.ins().smul_overflow(lhs, rhs)
+                        };
+                        check_overflow(
+                            ctx,
+                            &mut builder,
+                            source_locs,
+                            source_loc.unwrap(),
+                            str_map,
+                            panic_block,
+                            overflow,
+                        );
+                        res
+                    } else {
+                        builder.ins().imul(lhs, rhs)
+                    };
                     stack.push(res);
                 }
                 ir::Inst::DivInt => {
                     let rhs = stack.pop();
                     let lhs = stack.pop();
-                    let res = builder.ins().sdiv(lhs, rhs);
+                    check_divisor(
+                        ctx,
+                        &mut builder,
+                        source_locs,
+                        source_loc.unwrap(),
+                        str_map,
+                        panic_block,
+                        rhs,
+                    );
+                    if ctx.overflow_checks {
+                        // MIN / -1 is the only division which overflows
+                        let min = builder.ins().icmp_imm(IntCC::Equal, lhs, i64::MIN);
+                        let minus_one = builder.ins().icmp_imm(IntCC::Equal, rhs, -1);
+                        let overflow = builder.ins().band(min, minus_one);
+                        check_overflow(
+                            ctx,
+                            &mut builder,
+                            source_locs,
+                            source_loc.unwrap(),
+                            str_map,
+                            panic_block,
+                            overflow,
+                        );
+                    }
+                    let res = wrapping_sdiv(&mut builder, lhs, rhs);
+                    stack.push(res);
+                }
+                ir::Inst::ModInt => {
+                    let rhs = stack.pop();
+                    let lhs = stack.pop();
+                    check_divisor(
+                        ctx,
+                        &mut builder,
+                        source_locs,
+                        source_loc.unwrap(),
+                        str_map,
+                        panic_block,
+                        rhs,
+                    );
+                    let res = wrapping_srem(&mut builder, lhs, rhs);
+                    stack.push(res);
+                }
+                ir::Inst::AndInt => {
+                    let rhs = stack.pop();
+                    let lhs = stack.pop();
+                    let res = builder.ins().band(lhs, rhs);
+                    stack.push(res);
+                }
+                ir::Inst::OrInt => {
+                    let rhs = stack.pop();
+                    let lhs = stack.pop();
+                    let res = builder.ins().bor(lhs, rhs);
+                    stack.push(res);
+                }
+                ir::Inst::XorInt => {
+                    let rhs = stack.pop();
+                    let lhs = stack.pop();
+                    let res = builder.ins().bxor(lhs, rhs);
+                    stack.push(res);
+                }
+                ir::Inst::ShlInt => {
+                    let rhs = stack.pop();
+                    let lhs = stack.pop();
+                    let res = builder.ins().ishl(lhs, rhs);
+                    stack.push(res);
+                }
+                ir::Inst::ShrInt => {
+                    let rhs = stack.pop();
+                    let lhs = stack.pop();
+                    let res = builder.ins().sshr(lhs, rhs);
+                    stack.push(res);
+                }
+                ir::Inst::DivByte => {
+                    let rhs = stack.pop();
+                    let lhs = stack.pop();
+                    check_divisor(
+                        ctx,
+                        &mut builder,
+                        source_locs,
+                        source_loc.unwrap(),
+                        str_map,
+                        panic_block,
+                        rhs,
+                    );
+                    let res = builder.ins().udiv(lhs, rhs);
+                    stack.push(res);
+                }
+                ir::Inst::ModByte => {
+                    let rhs = stack.pop();
+                    let lhs = stack.pop();
+                    check_divisor(
+                        ctx,
+                        &mut builder,
+                        source_locs,
+                        source_loc.unwrap(),
+                        str_map,
+                        panic_block,
+                        rhs,
+                    );
+                    let res = builder.ins().urem(lhs, rhs);
+                    stack.push(res);
+                }
+                ir::Inst::ShrByte => {
+                    let rhs = stack.pop();
+                    let lhs = stack.pop();
+                    let res = builder.ins().ushr(lhs, rhs);
+                    stack.push(res);
+                }
+                ir::Inst::NegInt => {
+                    let value = stack.pop();
+                    let res = builder.ins().ineg(value);
                     stack.push(res);
                 }
-                ir::Inst::ModInt => todo!(),
                 ir::Inst::EquInt => {
                     let rhs = stack.pop();
                     let lhs = stack.pop();
@@ -294,6 +636,11 @@ pub fn translate_function(
                     let res = builder.ins().fdiv(lhs, rhs);
                     stack.push(res);
                 }
+                ir::Inst::NegNumber => {
+                    let value = stack.pop();
+                    let res = builder.ins().fneg(value);
+                    stack.push(res);
+                }
                 ir::Inst::EquNumber => {
                     let rhs = stack.pop();
                     let lhs = stack.pop();
@@ -370,6 +717,17 @@ pub fn translate_function(
                     let res = builder.ins().bor(lhs, rhs);
                     stack.push(res);
                 }
+                ir::Inst::Not => {
+                    // bools are 0 or 1, so only flip the lowest bit
+                    let value = stack.pop();
+                    let res = builder.ins().bxor_imm(value, 1);
+                    stack.push(res);
+                }
+                ir::Inst::BitNot => {
+                    let value = stack.pop();
+                    let res = builder.ins().bnot(value);
+                    stack.push(res);
+                }
                 ir::Inst::LoadConstInt(value) => {
                     let val = builder.ins().iconst(I64, *value);
                     stack.push(val);
@@ -407,8 +765,10 @@ pub fn translate_function(
                         .expect("Failed to create anonymous data");
                     data_desc.clear();
                     match globals.get(*value) {
-                        ir::GlobalValue::VirtualTable(functions) => {
-                            data_desc.define_zeroinit(functions.len() * 8);
+                        ir::GlobalValue::VirtualTable(type_id, functions) => {
+                            let mut contents = vec![0u8; (functions.len() + 1) * 8];
+                            contents[..8].copy_from_slice(&(*type_id as i64).to_le_bytes());
+                            data_desc.define(contents.into_boxed_slice());
                             for (i, func) in functions.iter().enumerate() {
                                 let sig = signatures.iter().find(|s| s.id == *func).expect(
                                     format!("Could not find signature for {}", func).as_str(),
@@ -420,7 +780,7 @@ pub fn translate_function(
                                     .expect("Failed to declare function");
                                 let func_ref =
                                     ctx.module.declare_func_in_data(func_id, &mut data_desc);
-                                data_desc.write_function_addr((i * 8) as CodeOffset, func_ref);
+                                data_desc.write_function_addr(((i + 1) * 8) as CodeOffset, func_ref);
                             }
                         }
                     }
@@ -431,9 +791,55 @@ pub fn translate_function(
                     let addr = builder.ins().symbol_value(I64, local_data_id);
                     stack.push(addr);
                 }
+                ir::Inst::LoadStatic(id, typ) => {
+                    let local_data_id =
+                        ctx.module.declare_data_in_func(ctx.statics[id], builder.func);
+                    let addr = builder.ins().symbol_value(I64, local_data_id);
+                    let abi_type = ctx.translate_type(typ);
+                    let value = builder
+                        .ins()
+                        .load(abi_type.root, MemFlags::new().with_aligned(), addr, 0);
+                    stack.push(value);
+                }
+                ir::Inst::StoreStatic(id, _) => {
+                    let value = stack.pop();
+                    let local_data_id =
+                        ctx.module.declare_data_in_func(ctx.statics[id], builder.func);
+                    let addr = builder.ins().symbol_value(I64, local_data_id);
+                    builder
+                        .ins()
+                        .store(MemFlags::new().with_aligned(), value, addr, 0);
+                }
+                ir::Inst::CheckInitialised => {
+                    let initialised = stack.pop();
+                    panic_unless(
+                        ctx,
+                        &mut builder,
+                        source_locs,
+                        source_loc.unwrap(),
+                        str_map,
+                        panic_block,
+                        initialised,
+                        "Global used before initialisation.",
+                    );
+                }
+                ir::Inst::LoadFunction(id) => {
+                    let sig = signatures
+                        .iter()
+                        .find(|s| s.id == *id)
+                        .expect(format!("Could not find signature for {}", id).as_str());
+                    let signature = translate_signature(ctx, &sig.signature, call_conv);
+                    let func_id = ctx
+                        .module
+                        .declare_function(&sig.id, Linkage::Import, &signature)
+                        .expect("Failed to declare function");
+                    let func_ref = ctx.module.declare_func_in_func(func_id, builder.func);
+                    let addr = builder.ins().func_addr(I64, func_ref);
+                    stack.push(addr);
+                }
                 ir::Inst::Truncate => {
                     let val = stack.pop();
-                    let res = builder.ins().fcvt_to_sint(I64, val);
+                    let res = builder.ins().fcvt_to_sint_sat(I64, val);
                     stack.push(res);
                 }
                 ir::Inst::Promote => {
@@ -443,6 +849,37 @@ pub fn translate_function(
                         .fcvt_from_sint(cranelift_codegen::ir::types::F64, val);
                     stack.push(res);
                 }
+                ir::Inst::Extend => {
+                    let val = stack.pop();
+                    let res = builder.ins().uextend(I64, val);
+                    stack.push(res);
+                }
+                ir::Inst::Narrow(wrapping) => {
+                    let val = stack.pop();
+                    if !wrapping {
+                        let continue_block = builder.create_block();
+                        // negative values are huge when unsigned, so one compare checks both ends
+                        let fits = builder.ins().icmp_imm(IntCC::UnsignedLessThanOrEqual, val, 255);
+                        let panic_message = construct_panic_message(
+                            ctx,
+                            &mut builder,
+                            source_locs,
+                            source_loc.unwrap(),
+                            str_map,
+                            "Value does not fit in a byte.",
+                        );
+                        builder.ins().brif(
+                            fits,
+                            continue_block,
+                            &[],
+                            panic_block,
+                            &vec![BlockArg::Value(panic_message)],
+                        );
+                        builder.switch_to_block(continue_block);
+                    }
+                    let res = builder.ins().ireduce(I8, val);
+                    stack.push(res);
+                }
                 ir::Inst::Load(var) => {
                     let var = variables[*var];
                     stack.push(builder.use_var(var));
@@ -478,7 +915,8 @@ pub fn translate_function(
                     builder.ins().br_table(val, jt);
                 }
                 ir::Inst::Ret => {
-                    let ret_vals = stack.take(func.signature.ret_types.len());
+                    let mut ret_vals = stack.take(func.signature.ret_types.len());
+                    ret_vals.reverse();
                     builder.ins().return_(&ret_vals);
                 }
                 ir::Inst::Call(id) => {
@@ -504,48 +942,28 @@ pub fn translate_function(
                     stack.push(val);
                     let val = builder.ins().iconst(I64, clir_typ.bytes() as i64);
                     stack.push(val);
-                    let scan_elements = matches!(typ, ir::Type::Reference);
+                    let scan_elements = matches!(typ, ir::Type::Reference | ir::Type::Array);
                     stack.push(builder.ins().iconst(I8, if scan_elements { 1 } else { 0 }));
                     translate_call(ctx, &mut builder, &mut stack, "__create_array");
                 }
                 ir::Inst::LoadArray(typ) => {
                     let array = stack.pop();
                     let index = stack.pop();
-                    // create a new block that everything after this load goes into
-                    let continue_block = builder.create_block();
-                    // load the array size, which is directly at the array pointer
-                    let array_size = builder.ins().load(I64, MemFlags::new(), array, 0);
-                    // check the index is allg
-                    let index_ok_zero =
-                        builder
-                            .ins()
-                            .icmp_imm(IntCC::SignedGreaterThanOrEqual, array_size, 0);
-                    let index_ok_size =
-                        builder.ins().icmp(IntCC::SignedLessThan, index, array_size);
-                    let index_ok = builder.ins().band(index_ok_size, index_ok_zero);
-                    // jump to continue block if we are allg otherwise panic block
-                    let panic_message = construct_panic_message(
+                    let array_size = builder.ins().load(I64, MemFlags::new(), array, ArrayHeader::LENGTH_OFFSET);
+                    check_bounds(
                         ctx,
                         &mut builder,
                         source_locs,
                         source_loc.unwrap(),
                         str_map,
-                        "Out of bounds.",
-                    );
-                    builder.ins().brif(
-                        index_ok,
-                        continue_block,
-                        &[],
                         panic_block,
-                        &vec![BlockArg::Value(panic_message)],
+                        index,
+                        array_size,
+                        false,
                     );
-
-                    builder.switch_to_block(continue_block);
                     // load the value now we know the array index is ok
                     let clir_typ = ctx.translate_type(&typ);
-                    let offset = builder.ins().imul_imm(index, clir_typ.bytes() as i64);
-                    let array_begin = builder.ins().iadd_imm(array, 8);
-                    let pointer = builder.ins().iadd(array_begin, offset);
+                    let pointer = array_element(&mut builder, array, index, clir_typ.bytes() as i64);
                     let value =
                         builder
                             .ins()
@@ -556,52 +974,159 @@ pub fn translate_function(
                     let array = stack.pop();
                     let index = stack.pop();
                     let value = stack.pop();
-                    // create a new block that everything after this load goes into
-                    let continue_block = builder.create_block();
-                    // load the array size, which is directly at the array pointer
-                    let array_size = builder.ins().load(I64, MemFlags::new(), array, 0);
-                    // check the index is allg
-                    let index_ok_zero =
-                        builder
-                            .ins()
-                            .icmp_imm(IntCC::SignedGreaterThanOrEqual, array_size, 0);
-                    let index_ok_size =
-                        builder.ins().icmp(IntCC::SignedLessThan, index, array_size);
-                    let index_ok = builder.ins().band(index_ok_size, index_ok_zero);
-                    // jump to continue block if we are allg otherwise panic block
-                    let panic_message = construct_panic_message(
+                    let array_size = builder.ins().load(I64, MemFlags::new(), array, ArrayHeader::LENGTH_OFFSET);
+                    check_bounds(
                         ctx,
                         &mut builder,
                         source_locs,
                         source_loc.unwrap(),
                         str_map,
-                        "Out of bounds.",
-                    );
-                    builder.ins().brif(
-                        index_ok,
-                        continue_block,
-                        &[],
                         panic_block,
-                        &vec![BlockArg::Value(panic_message)],
+                        index,
+                        array_size,
+                        false,
                     );
-
-                    builder.switch_to_block(continue_block);
                     // store the value now we know the array index is ok
                     let clir_typ = ctx.translate_type(&typ);
-                    let offset = builder.ins().imul_imm(index, clir_typ.bytes() as i64);
-                    let array_begin = builder.ins().iadd_imm(array, 8);
-                    let pointer = builder.ins().iadd(array_begin, offset);
+                    let pointer = array_element(&mut builder, array, index, clir_typ.bytes() as i64);
+                    builder
+                        .ins()
+                        .store(MemFlags::new().with_aligned(), value, pointer, 0);
+                }
+                ir::Inst::ArrayInsert(typ) => {
+                    let array = stack.pop();
+                    let index = stack.pop();
+                    let value = stack.pop();
+                    let array_size = builder.ins().load(I64, MemFlags::new(), array, ArrayHeader::LENGTH_OFFSET);
+                    check_bounds(
+                        ctx,
+                        &mut builder,
+                        source_locs,
+                        source_loc.unwrap(),
+                        str_map,
+                        panic_block,
+                        index,
+                        array_size,
+                        true,
+                    );
+                    // grow the array and move the elements after the index up
+                    let clir_typ = ctx.translate_type(&typ);
+                    let elem_size = clir_typ.bytes() as i64;
+                    stack.push(array);
+                    stack.push(index);
+                    stack.push(builder.ins().iconst(I64, elem_size));
+                    translate_call(ctx, &mut builder, &mut stack, "__array_insert");
+                    let pointer = array_element(&mut builder, array, index, elem_size);
                     builder
                         .ins()
                         .store(MemFlags::new().with_aligned(), value, pointer, 0);
                 }
+                ir::Inst::ArrayRemove(typ) => {
+                    let array = stack.pop();
+                    let index = stack.pop();
+                    let array_size = builder.ins().load(I64, MemFlags::new(), array, ArrayHeader::LENGTH_OFFSET);
+                    check_bounds(
+                        ctx,
+                        &mut builder,
+                        source_locs,
+                        source_loc.unwrap(),
+                        str_map,
+                        panic_block,
+                        index,
+                        array_size,
+                        false,
+                    );
+                    let clir_typ = ctx.translate_type(&typ);
+                    let elem_size = clir_typ.bytes() as i64;
+                    let pointer = array_element(&mut builder, array, index, elem_size);
+                    let value =
+                        builder
+                            .ins()
+                            .load(clir_typ.root, MemFlags::new(), pointer, 0);
+                    // move the elements after the index down
+                    stack.push(array);
+                    stack.push(index);
+                    stack.push(builder.ins().iconst(I64, elem_size));
+                    translate_call(ctx, &mut builder, &mut stack, "__array_remove");
+                    stack.push(value);
+                }
+                ir::Inst::ArrayClear => {
+                    let array = stack.pop();
+                    let zero = builder.ins().iconst(I64, 0);
+                    builder.ins().store(MemFlags::new(), zero, array, ArrayHeader::LENGTH_OFFSET);
+                }
+                ir::Inst::NewMap(key, value) => {
+                    let string_keys = matches!(key, ir::Type::String);
+                    let scan_values = matches!(value, ir::Type::Reference | ir::Type::Array);
+                    stack.push(builder.ins().iconst(I8, string_keys as i64));
+                    stack.push(builder.ins().iconst(I8, scan_values as i64));
+                    translate_call(ctx, &mut builder, &mut stack, "__create_map");
+                }
+                ir::Inst::LoadMap(_, value) => {
+                    let map = stack.pop();
+                    let key = stack.pop();
+                    stack.push(map);
+                    stack.push(to_word(&mut builder, key));
+                    translate_call(ctx, &mut builder, &mut stack, "__map_find");
+                    let index = stack.pop();
+                    let found = builder.ins().icmp_imm(IntCC::SignedGreaterThanOrEqual, index, 0);
+                    panic_unless(
+                        ctx,
+                        &mut builder,
+                        source_locs,
+                        source_loc.unwrap(),
+                        str_map,
+                        panic_block,
+                        found,
+                        "Key not found.",
+                    );
+                    stack.push(map);
+                    stack.push(index);
+                    translate_call(ctx, &mut builder, &mut stack, "__map_value_at");
+                    let word = stack.pop();
+                    let clir_typ = ctx.translate_type(&value);
+                    stack.push(from_word(&mut builder, word, clir_typ.root));
+                }
+                ir::Inst::StoreMap(_, _) => {
+                    let map = stack.pop();
+                    let key = stack.pop();
+                    let value = stack.pop();
+                    stack.push(map);
+                    stack.push(to_word(&mut builder, key));
+                    stack.push(to_word(&mut builder, value));
+                    translate_call(ctx, &mut builder, &mut stack, "__map_insert");
+                }
+                ir::Inst::MapContains(_) => {
+                    let map = stack.pop();
+                    let key = stack.pop();
+                    stack.push(map);
+                    stack.push(to_word(&mut builder, key));
+                    translate_call(ctx, &mut builder, &mut stack, "__map_find");
+                    let index = stack.pop();
+                    let found = builder.ins().icmp_imm(IntCC::SignedGreaterThanOrEqual, index, 0);
+                    stack.push(found);
+                }
+                ir::Inst::MapRemove(_) => {
+                    let map = stack.pop();
+                    let key = stack.pop();
+                    stack.push(map);
+                    stack.push(to_word(&mut builder, key));
+                    translate_call(ctx, &mut builder, &mut stack, "__map_remove");
+                }
+                ir::Inst::MapLen => {
+                    translate_call(ctx, &mut builder, &mut stack, "__map_len");
+                }
+                ir::Inst::MapKeys(key) => {
+                    let clir_typ = ctx.translate_type(&key);
+                    stack.push(builder.ins().iconst(I64, clir_typ.bytes() as i64));
+                    translate_call(ctx, &mut builder, &mut stack, "__map_keys");
+                }
                 ir::Inst::CreateSlice(typ) => {
                     let array = stack.pop();
                     let end = stack.pop();
                     let start = stack.pop();
                     
-                    // load the array size, which is directly at the array pointer
-                    let array_size = builder.ins().load(I64, MemFlags::new(), array, 0);
+                    let array_size = builder.ins().load(I64, MemFlags::new(), array, ArrayHeader::LENGTH_OFFSET);
                     
                     // create a new block that everything after this load goes into
                     let continue_block = builder.create_block();
@@ -655,24 +1180,23 @@ pub fn translate_function(
                     stack.push(slice_size);
                     let val = builder.ins().iconst(I64, clir_typ.bytes() as i64);
                     stack.push(val);
-                    let scan_elements = matches!(typ, ir::Type::Reference);
+                    let scan_elements = matches!(typ, ir::Type::Reference | ir::Type::Array);
                     stack.push(builder.ins().iconst(I8, if scan_elements { 1 } else { 0 }));
                     translate_call(ctx, &mut builder, &mut stack, "__create_array");
 
                     // copy the contents
                     let slice = stack.pop();
                     let byte_size = builder.ins().imul_imm(slice_size, clir_typ.bytes() as i64);
-                    let offset = builder.ins().imul_imm(start, clir_typ.bytes() as i64);
-                    let src_pointer = builder.ins().iadd_imm(array, 8);
-                    let src_pointer = builder.ins().iadd(src_pointer, offset);
-                    let dest_pointer = builder.ins().iadd_imm(slice, 8);
+                    let src_pointer = array_element(&mut builder, array, start, clir_typ.bytes() as i64);
+                    let dest_pointer =
+                        builder.ins().load(I64, MemFlags::new(), slice, ArrayHeader::DATA_OFFSET);
                     builder.call_memcpy(frontend_config, dest_pointer, src_pointer, byte_size);
 
                     stack.push(slice);
                 }
                 ir::Inst::ArrayLen => {
                     let array = stack.pop();
-                    let array_size = builder.ins().load(I64, MemFlags::new(), array, 0);
+                    let array_size = builder.ins().load(I64, MemFlags::new(), array, ArrayHeader::LENGTH_OFFSET);
                     stack.push(array_size);
                 }   
                 ir::Inst::NewObject(size) => {
@@ -723,6 +1247,19 @@ pub fn translate_function(
                     );
                     builder.switch_to_block(continue_block);
                 }
+                ir::Inst::Unreachable => {
+                    let panic_message = construct_panic_message(
+                        ctx,
+                        &mut builder,
+                        source_locs,
+                        source_loc.unwrap(),
+                        str_map,
+                        "Unreachable code reached.",
+                    );
+                    builder
+                        .ins()
+                        .jump(panic_block, &vec![BlockArg::Value(panic_message)]);
+                }
             }
         }
     }
@@ -27,18 +27,22 @@ pub struct FunctionType {
 #[derive(Debug)]
 pub struct StructType {
     pub spec: NameSpecification,
+    // the arguments of a generic instance, empty for everything else
+    pub type_args: Vec<Type>,
     pub fields: RwLock<Vec<(String, Type)>>,
 }
 
 #[derive(Debug)]
 pub struct EnumType {
     pub spec: NameSpecification,
+    pub type_args: Vec<Type>,
     pub variants: RwLock<Vec<(String, Vec<Type>)>>,
 }
 
 #[derive(Debug)]
 pub struct InterfaceType {
     pub spec: NameSpecification,
+    pub type_args: Vec<Type>,
     pub methods: RwLock<Vec<(String, FunctionType)>>,
 }
 
@@ -50,8 +54,10 @@ pub enum TypeKind {
     Number,
     String,
     Bool,
-    UnknownReference, // An internal detail before generics is correctly implemented
+    UnknownReference, // An opaque reference owned by the runtime, like the template builder
     Array(Type),
+    Map(Type, Type),
+    Tuple(Vec<Type>),
     Struct(StructType),
     Enum(EnumType),
     Function(FunctionType),
@@ -161,6 +167,71 @@ pub fn interface_assignable(i: &InterfaceType, typ: &Type) -> bool {
     true
 }
 
+/// Finds what the `?` operator unwraps, an enum with `Ok(value)` and `Err(error)`
+/// variants or with `Some(value)` and `None` variants.
+/// Returns the value type and the index of the variant holding it.
+pub fn try_value_type(typ: &Type) -> Option<(Type, usize)> {
+    let TypeKind::Enum(enum_type) = typ.kind() else {
+        return None;
+    };
+
+    let variants = enum_type.variants.read().unwrap();
+    if variants.len() != 2 {
+        return None;
+    }
+    for (success, failure, failure_values) in [("Ok", "Err", 1), ("Some", "None", 0)] {
+        let Some(success_idx) = variants
+            .iter()
+            .position(|(id, values)| id == success && values.len() == 1)
+        else {
+            continue;
+        };
+        let (failure_id, values) = &variants[1 - success_idx];
+        if failure_id == failure && values.len() == failure_values {
+            return Some((variants[success_idx].1[0].clone(), success_idx));
+        }
+    }
+    None
+}
+
+/// The failure unwrapped by `?` is returned as it is, so the function has to
+/// return an enum with the same failure variant in the same place.
+pub fn try_failure_returnable(value: &Type, returns: &Type) -> bool {
+    let (Some((_, success_idx)), Some((_, return_success_idx))) =
+        (try_value_type(value), try_value_type(returns))
+    else {
+        return false;
+    };
+    if success_idx != return_success_idx {
+        return false;
+    }
+    let (TypeKind::Enum(value_enum), TypeKind::Enum(return_enum)) = (value.kind(), returns.kind())
+    else {
+        return false;
+    };
+
+    let value_variants = value_enum.variants.read().unwrap();
+    let return_variants = return_enum.variants.read().unwrap();
+    let (failure_id, values) = &value_variants[1 - success_idx];
+    let (return_failure_id, return_values) = &return_variants[1 - success_idx];
+    failure_id == return_failure_id
+        && values
+            .iter()
+            .zip(return_values.iter())
+            .all(|(a, b)| compare(a, b) == ComparisonResult::Same)
+}
+
+fn function_types_equal(a: &FunctionType, b: &FunctionType) -> bool {
+    let types_equal = |a: &Vec<Type>, b: &Vec<Type>| {
+        a.len() == b.len()
+            && a
+                .iter()
+                .zip(b.iter())
+                .all(|(a, b)| compare(a, b) == ComparisonResult::Same)
+    };
+    types_equal(&a.params, &b.params) && types_equal(&a.returns, &b.returns)
+}
+
 /// Compares two types
 /// If one can be an interface, the interface should be `a`
 pub fn compare(a: &Type, b: &Type) -> ComparisonResult {
@@ -177,6 +248,34 @@ pub fn compare(a: &Type, b: &Type) -> ComparisonResult {
         }
     }
 
+    if let (TypeKind::Map(a_key, a_value), TypeKind::Map(b_key, b_value)) = (a.kind(), b.kind()) {
+        if compare(a_key, b_key) == ComparisonResult::Same
+            && compare(a_value, b_value) == ComparisonResult::Same
+        {
+            return ComparisonResult::Same;
+        }
+    }
+
+    if let (TypeKind::Tuple(a_types), TypeKind::Tuple(b_types)) = (a.kind(), b.kind()) {
+        if a_types.len() == b_types.len()
+            && a_types
+                .iter()
+                .zip(b_types.iter())
+                .all(|(a, b)| compare(a, b) == ComparisonResult::Same)
+        {
+            return ComparisonResult::Same;
+        }
+    }
+
+    // function types are structural, any function with the same signature will do
+    if let TypeKind::Function(a_func) = a.kind() {
+        if let TypeKind::Function(b_func) = b.kind() {
+            if function_types_equal(a_func, b_func) {
+                return ComparisonResult::Same;
+            }
+        }
+    }
+
     if let TypeKind::Interface(i) = a.kind() {
         if interface_assignable(i, b) {
             return ComparisonResult::Upcastable;
@@ -215,6 +314,22 @@ pub fn is_array(ty: &Type) -> bool {
     matches!(ty.inner.kind, TypeKind::Array(_))
 }
 
+pub fn is_map(ty: &Type) -> bool {
+    matches!(ty.inner.kind, TypeKind::Map(..))
+}
+
+/// Map keys are hashed by value, so only these types can be used
+pub fn is_map_key(ty: &Type) -> bool {
+    matches!(
+        ty.inner.kind,
+        TypeKind::Integer | TypeKind::String | TypeKind::Byte | TypeKind::Bool
+    )
+}
+
+pub fn is_tuple(ty: &Type) -> bool {
+    matches!(ty.inner.kind, TypeKind::Tuple(_))
+}
+
 pub fn is_bool(ty: &Type) -> bool {
     matches!(ty.inner.kind, TypeKind::Bool)
 }
@@ -241,6 +356,8 @@ pub fn is_reference(ty: &Type) -> bool {
         TypeKind::UnknownReference
             | TypeKind::Struct(_)
             | TypeKind::Array(_)
+            | TypeKind::Map(..)
+            | TypeKind::Tuple(_)
             | TypeKind::Interface(_)
     )
 }
@@ -297,6 +414,40 @@ pub fn get_inner_array_type(ty: &Type) -> Type {
     }
 }
 
+pub fn get_map_types(ty: &Type) -> (Type, Type) {
+    if let TypeKind::Map(key_type, value_type) = &ty.inner.kind {
+        (key_type.clone(), value_type.clone())
+    } else {
+        panic!("Type is not a map");
+    }
+}
+
+pub fn get_tuple_types(ty: &Type) -> Vec<Type> {
+    if let TypeKind::Tuple(types) = &ty.inner.kind {
+        types.clone()
+    } else {
+        panic!("Type is not a tuple");
+    }
+}
+
+/// A function returning a tuple returns each of its values, so the returns
+/// of a signature are flattened
+pub fn flatten_returns(returns: Vec<Type>) -> Vec<Type> {
+    match returns.as_slice() {
+        [typ] if is_tuple(typ) => get_tuple_types(typ),
+        _ => returns,
+    }
+}
+
+/// The type of a call, a tuple when the function returns multiple values
+pub fn return_type(func_type: &FunctionType) -> Option<Type> {
+    match func_type.returns.as_slice() {
+        [] => None,
+        [typ] => Some(typ.clone()),
+        returns => Some(tuple(returns.to_vec())),
+    }
+}
+
 pub fn create_type(kind: TypeKind) -> Type {
     Type {
         inner: Arc::new(Inner {
@@ -307,6 +458,33 @@ pub fn create_type(kind: TypeKind) -> Type {
     }
 }
 
+/// Gets the spec and type arguments of a struct, enum or interface, which
+/// tells apart instances of generic types
+pub fn generic_spec(typ: &Type) -> Option<(&NameSpecification, &Vec<Type>)> {
+    match &typ.inner.kind {
+        TypeKind::Struct(struct_type) => Some((&struct_type.spec, &struct_type.type_args)),
+        TypeKind::Enum(enum_type) => Some((&enum_type.spec, &enum_type.type_args)),
+        TypeKind::Interface(interface_type) => {
+            Some((&interface_type.spec, &interface_type.type_args))
+        }
+        _ => None,
+    }
+}
+
+fn spec_name(spec: &NameSpecification, type_args: &[Type]) -> String {
+    if type_args.is_empty() {
+        format!("{}:{}:{}", spec.package, spec.file, spec.name)
+    } else {
+        format!(
+            "{}:{}:{}<{}>",
+            spec.package,
+            spec.file,
+            spec.name,
+            type_args.iter().map(name).collect::<Vec<_>>().join(", ")
+        )
+    }
+}
+
 pub fn name(typ: &Type) -> String {
     match &typ.inner.kind {
         TypeKind::Bad => "bad".into(),
@@ -317,14 +495,14 @@ pub fn name(typ: &Type) -> String {
         TypeKind::Bool => "bool".into(),
         TypeKind::UnknownReference => "unknown_reference".into(),
         TypeKind::Array(element_type) => format!("[]{}", name(element_type)),
-        TypeKind::Struct(struct_type) => format!(
-            "{}:{}:{}",
-            struct_type.spec.package, struct_type.spec.file, struct_type.spec.name
-        ),
-        TypeKind::Enum(enum_type) => format!(
-            "{}:{}:{}",
-            enum_type.spec.package, enum_type.spec.file, enum_type.spec.name
-        ),
+        TypeKind::Map(key_type, value_type) => {
+            format!("map[{}]{}", name(key_type), name(value_type))
+        }
+        TypeKind::Tuple(types) => {
+            format!("({})", types.iter().map(name).collect::<Vec<_>>().join(", "))
+        }
+        TypeKind::Struct(struct_type) => spec_name(&struct_type.spec, &struct_type.type_args),
+        TypeKind::Enum(enum_type) => spec_name(&enum_type.spec, &enum_type.type_args),
         TypeKind::Function(func_type) => format!(
             "fn({}) -> ({})",
             func_type
@@ -340,10 +518,9 @@ pub fn name(typ: &Type) -> String {
                 .collect::<Vec<_>>()
                 .join(", ")
         ),
-        TypeKind::Interface(interface_type) => format!(
-            "{}:{}:{}",
-            interface_type.spec.package, interface_type.spec.file, interface_type.spec.name
-        ),
+        TypeKind::Interface(interface_type) => {
+            spec_name(&interface_type.spec, &interface_type.type_args)
+        }
     }
 }
 
@@ -410,16 +587,54 @@ pub fn array(element_type: Type) -> Type {
     array_type
 }
 
-pub fn struct_type(spec: NameSpecification, fields: Vec<(String, Type)>) -> Type {
+pub fn map(key_type: Type, value_type: Type) -> Type {
+    // memoized like arrays, so the same key and value types give the same map type
+    static MAP_TYPES: OnceLock<RwLock<HashMap<(usize, usize), Type>>> = OnceLock::new();
+    let map_types = MAP_TYPES.get_or_init(|| RwLock::new(HashMap::new()));
+    let mut map_types = map_types.write().unwrap();
+    let key = (key_type.inner.hash, value_type.inner.hash);
+    if let Some(map_type) = map_types.get(&key) {
+        return map_type.clone();
+    }
+    let map_type = create_type(TypeKind::Map(key_type, value_type));
+    map_types.insert(key, map_type.clone());
+    map_type
+}
+
+pub fn tuple(types: Vec<Type>) -> Type {
+    // memoized like arrays, so the same element types give the same tuple type
+    static TUPLE_TYPES: OnceLock<RwLock<HashMap<Vec<usize>, Type>>> = OnceLock::new();
+    let tuple_types = TUPLE_TYPES.get_or_init(|| RwLock::new(HashMap::new()));
+    let mut tuple_types = tuple_types.write().unwrap();
+    let key = types.iter().map(|t| t.inner.hash).collect::<Vec<_>>();
+    if let Some(tuple_type) = tuple_types.get(&key) {
+        return tuple_type.clone();
+    }
+    let tuple_type = create_type(TypeKind::Tuple(types));
+    tuple_types.insert(key, tuple_type.clone());
+    tuple_type
+}
+
+pub fn struct_type(
+    spec: NameSpecification,
+    type_args: Vec<Type>,
+    fields: Vec<(String, Type)>,
+) -> Type {
     create_type(TypeKind::Struct(StructType {
         spec,
+        type_args,
         fields: RwLock::new(fields),
     }))
 }
 
-pub fn enum_type(spec: NameSpecification, variants: Vec<(String, Vec<Type>)>) -> Type {
+pub fn enum_type(
+    spec: NameSpecification,
+    type_args: Vec<Type>,
+    variants: Vec<(String, Vec<Type>)>,
+) -> Type {
     create_type(TypeKind::Enum(EnumType {
         spec,
+        type_args,
         variants: RwLock::new(variants),
     }))
 }
@@ -428,9 +643,14 @@ pub fn function_type(params: Vec<Type>, returns: Vec<Type>) -> Type {
     create_type(TypeKind::Function(FunctionType { params, returns }))
 }
 
-pub fn interface_type(spec: NameSpecification, methods: Vec<(String, FunctionType)>) -> Type {
+pub fn interface_type(
+    spec: NameSpecification,
+    type_args: Vec<Type>,
+    methods: Vec<(String, FunctionType)>,
+) -> Type {
     create_type(TypeKind::Interface(InterfaceType {
         spec,
+        type_args,
         methods: RwLock::new(methods),
     }))
 }